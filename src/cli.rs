@@ -1,51 +1,455 @@
 use anyhow::Result;
-use clap::Parser;
-use yeth::error::YethError;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use crate::color::ColorChoice;
+use crate::template::Template;
+use yeth::version_writes::VersionFormat;
+use std::io::Write;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(name = "yeth")]
 #[command(about = "A utility for building dependency graphs between applications", long_about = None)]
 pub struct Cli {
-    /// Root directory to search for applications
-    #[arg(short, long, default_value = ".")]
-    pub root: PathBuf,
+    /// What to do; without a subcommand, hashes are output as with `yeth hash`
+    #[command(subcommand)]
+    pub command: Option<Command>,
 
-    /// Name of specific application to output hash for (defaults to all)
-    #[arg(short, long)]
+    /// Root directory to search for applications [default: $YETH_ROOT, else the nearest
+    /// directory above with yeth.workspace.toml or .git, else .]
+    #[arg(short, long, global = true)]
+    pub root: Option<PathBuf>,
+
+    /// Don't search parent directories for the root
+    #[arg(long, global = true)]
+    pub no_root_search: bool,
+
+    /// Name of the application to output hash for, or a glob such as 'worker-*' (defaults to all)
+    #[arg(short, long, global = true)]
     pub app: Option<String>,
 
-    /// Show only hash without application name (works only with --app)
-    #[arg(short = 'H', long, requires = "app")]
+    /// Output hashes only for applications with this tag (repeatable, any tag matches)
+    #[arg(long, value_name = "TAG", conflicts_with_all = ["app", "impact"], global = true)]
+    pub tag: Vec<String>,
+
+    /// Leave out applications with this name or matching this glob (repeatable).
+    /// An application another one depends on can't be left out.
+    #[arg(long, value_name = "APP", global = true)]
+    pub exclude_app: Vec<String>,
+
+    /// With --app, show how its hash is built from its transitive dependencies
+    #[arg(long, requires = "app", global = true)]
+    pub why: bool,
+
+    /// Output hashes for an application and every application that depends on it
+    #[arg(long, value_name = "APP", conflicts_with = "app", global = true)]
+    pub impact: Option<String>,
+
+    /// Show only hash without application name (works only with --app matching one application)
+    #[arg(short = 'H', long, requires = "app", global = true)]
     pub hash_only: bool,
 
     /// Show more logs and execution time statistics
-    #[arg(short = 'v', long)]
+    #[arg(short = 'v', long, global = true)]
     pub verbose: bool,
 
-    /// Show dependency graph
-    #[arg(short = 'g', long)]
+    /// Print only hashes; with --changed, --since, --diff, --baseline or --dry-run print nothing and report through the exit code
+    #[arg(short = 'q', long, conflicts_with = "verbose", global = true)]
+    pub quiet: bool,
+
+    /// Color human-readable output; json, github, --template and --print0 output is never colored
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto, global = true)]
+    pub color: ColorChoice,
+
+    /// Print run statistics as a JSON object to stderr
+    #[arg(long, global = true)]
+    pub stats_json: bool,
+
+    /// Show dependency graph (same as `yeth graph`)
+    #[arg(short = 'g', long, global = true)]
     pub show_graph: bool,
 
+    /// With `yeth graph`, print only the dependency tree of this application
+    #[arg(long, value_name = "APP", global = true)]
+    pub graph_app: Option<String>,
+
+    /// With `yeth graph`, how many levels of application dependencies to expand
+    #[arg(long, value_name = "N", global = true)]
+    pub graph_depth: Option<usize>,
+
+    /// Write the discovered applications and hashing order as JSON to a file
+    #[arg(long, value_name = "FILE", global = true)]
+    pub dump_state: Option<PathBuf>,
+
+    /// List applications that no other application depends on
+    #[arg(long, global = true)]
+    pub orphans: bool,
+
+    /// List discovered applications and their directories without hashing (same as `yeth list`)
+    #[arg(long, global = true)]
+    pub list_apps: bool,
+
     /// Save each application's hash to yeth.version next to yeth.toml
-    #[arg(short = 'w', long)]
+    #[arg(short = 'w', long, global = true)]
     pub write_versions: bool,
 
+    /// With --write-versions, print which yeth.version files would change and write nothing.
+    /// Exits with status 1 if any file would change.
+    #[arg(long, requires = "write_versions", global = true)]
+    pub dry_run: bool,
+
+    /// Save every application's hash to yeth.lock at the root
+    #[arg(long, conflicts_with_all = ["app", "tag", "impact", "exclude_app", "keep_going"], global = true)]
+    pub write_lock: bool,
+
+    /// Fail if yeth.lock at the root is missing or doesn't match the computed hashes (same as `yeth check`)
+    #[arg(long, conflicts_with_all = ["app", "tag", "impact", "exclude_app", "keep_going", "write_lock"], global = true)]
+    pub frozen: bool,
+
+    /// Layout of yeth.version files written with --write-versions
+    #[arg(long, value_enum, default_value_t = VersionFileFormat::Plain, global = true)]
+    pub version_format: VersionFileFormat,
+
     /// Short hash mode
-    #[arg(short = 's', long)]
+    #[arg(short = 's', long, global = true)]
     pub short_hash: bool,
 
     /// Short hash length
-    #[arg(short = 'l', long, default_value = "10")]
+    #[arg(short = 'l', long, default_value = "10", global = true)]
     pub short_hash_length: usize,
 
-    /// Run benchmarking mode with specified number of iterations
-    #[arg(long)]
+    /// Run benchmarking mode with specified number of iterations (same as `yeth bench N`)
+    #[arg(long, global = true)]
     pub bench: Option<usize>,
+
+    /// Hash everything twice from scratch and fail if any application's hash differs.
+    /// Prints how long each run took.
+    #[arg(long, conflicts_with_all = ["app", "tag", "impact", "exclude_app", "bench"], global = true)]
+    pub verify: bool,
+
+    /// Most threads, and so files open at once, used for parallel work [default: number of CPUs]
+    #[arg(short, long, value_name = "N", global = true)]
+    pub jobs: Option<usize>,
+
+    /// Size in bytes of the buffer used to read files while hashing
+    #[arg(long, value_name = "BYTES", default_value_t = yeth::cfg::DEFAULT_READ_BUFFER_SIZE, global = true)]
+    pub read_buffer_size: usize,
+
+    /// Memory-map large files instead of reading them
+    #[arg(long, global = true)]
+    pub mmap: bool,
+
+    /// Mix each application's name into its hash, so renaming it changes the hash (changes every hash once)
+    #[arg(long, global = true)]
+    pub hash_app_name: bool,
+
+    /// Hash CRLF as LF in text files of every application
+    #[arg(long, global = true)]
+    pub normalize_eol: bool,
+
+    /// Mix this string into every application's hash, e.g. to invalidate caches after a toolchain bump
+    #[arg(long, value_name = "STRING", global = true)]
+    pub salt: Option<String>,
+
+    /// Follow symbolic links while hashing directories
+    #[arg(long, global = true)]
+    pub follow_symlinks: bool,
+
+    /// Hash where each symbolic link points instead of skipping it, so repointing a link counts as a change
+    #[arg(long, conflicts_with = "follow_symlinks", global = true)]
+    pub hash_symlink_targets: bool,
+
+    /// Leave files that can't be read out of the hash with a warning instead of failing
+    #[arg(long, global = true)]
+    pub skip_unreadable: bool,
+
+    /// Maximum directory depth to hash below each application (discovery is not limited)
+    #[arg(long, global = true)]
+    pub max_depth: Option<usize>,
+
+    /// Hash only files tracked by git
+    #[arg(long, global = true)]
+    pub tracked_only: bool,
+
+    /// Hash files as committed in a git revision instead of the working tree
+    #[arg(long, value_name = "GIT_REF", conflicts_with = "tracked_only", global = true)]
+    pub git_rev: Option<String>,
+
+    /// Treat path dependencies on an application directory as dependencies on that application
+    #[arg(long, global = true)]
+    pub promote_path_deps: bool,
+
+    /// Expand $VAR, ${VAR} and ~ in dependency and exclude paths
+    #[arg(long, global = true)]
+    pub expand_env: bool,
+
+    /// Fail on suspicious configuration, such as a path dependency inside another application,
+    /// instead of warning
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Fail on path dependencies that resolve outside the root directory
+    #[arg(long, global = true)]
+    pub deny_external_paths: bool,
+
+    /// Warn about unknown keys in yeth.toml instead of failing
+    #[arg(long, global = true)]
+    pub lenient: bool,
+
+    /// Output only applications whose hash differs from their yeth.version file
+    #[arg(long, global = true)]
+    pub changed: bool,
+
+    /// Save every application's full hash to one file: JSON, or TOML or YAML for a .toml or .yaml path
+    #[arg(long, visible_alias = "baseline-write", value_name = "FILE", global = true)]
+    pub output_file: Option<PathBuf>,
+
+    /// With --changed, compare with the hashes saved by --output-file instead of yeth.version files
+    #[arg(long, value_name = "FILE", requires = "changed", global = true)]
+    pub input_file: Option<PathBuf>,
+
+    /// Separate output records with NUL instead of newline (for `xargs -0`)
+    #[arg(short = '0', long, global = true)]
+    pub print0: bool,
+
+    /// Output only applications affected by changes since the branch forked from a git ref (e.g. origin/main)
+    #[arg(long, value_name = "GIT_REF", conflicts_with = "changed", global = true)]
+    pub since: Option<String>,
+
+    /// Write the results to a file, replacing it only when the run succeeds ("-" for stdout)
+    #[arg(short, long, value_name = "FILE", conflicts_with = "stream", global = true)]
+    pub output: Option<PathBuf>,
+
+    /// Show paths in the graph and in errors as absolute paths instead of relative to the root
+    #[arg(long, global = true)]
+    pub absolute_paths: bool,
+
+    /// Warn when several applications end up with the same hash
+    #[arg(long, global = true)]
+    pub warn_collisions: bool,
+
+    /// Warn about exclude patterns in yeth.toml files that excluded nothing
+    #[arg(long, global = true)]
+    pub warn_unused_excludes: bool,
+
+    /// Print each hash as soon as it is computed, in dependency order instead of sorted
+    #[arg(long, conflicts_with_all = ["app", "tag", "impact", "changed", "since", "format"], global = true)]
+    pub stream: bool,
+
+    /// Hash every application that can be hashed and report failures at the end
+    #[arg(long, conflicts_with_all = ["impact", "stream"], global = true)]
+    pub keep_going: bool,
+
+    /// Add each application's directory, relative to the root, to `<hash> <app>` lines
+    #[arg(long, conflicts_with = "hash_only", global = true)]
+    pub show_dir: bool,
+
+    /// Format of each output line, with {name}, {hash} and {dir} placeholders (e.g. "{name}={hash}")
+    #[arg(long, value_parser = Template::parse, conflicts_with_all = ["hash_only", "show_dir", "format"], global = true)]
+    pub template: Option<Template>,
+
+    /// Compare the hashes with a file from an earlier run (`--format json` output, or --output-file) and print what changed
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["template", "hash_only", "show_dir", "stream", "changed", "since"], global = true)]
+    pub diff: Option<PathBuf>,
+
+    /// Compare the hashes with a baseline manifest (JSON, TOML or YAML), print each application's status
+    /// and exit with 1 when any differs
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["template", "hash_only", "show_dir", "stream", "changed", "since", "diff"], global = true)]
+    pub baseline: Option<PathBuf>,
+
+    /// Print an image reference per application, <registry>/<app>:<tag prefix><hash>, with --short-hash-length hash characters
+    #[arg(long, conflicts_with_all = ["template", "hash_only", "show_dir", "stream", "diff", "baseline"], global = true)]
+    pub image_tag: bool,
+
+    /// Registry and namespace put before image names with --image-tag (e.g. registry.example.com/team)
+    #[arg(long, value_name = "PREFIX", requires = "image_tag", global = true)]
+    pub registry: Option<String>,
+
+    /// Text put before the hash in --image-tag tags
+    #[arg(long, value_name = "PREFIX", default_value = "yeth-", requires = "image_tag", global = true)]
+    pub tag_prefix: String,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    pub format: OutputFormat,
+
+    /// Order of applications in the output; with topo, --app prints its whole dependency
+    /// chain and json is an array
+    #[arg(long, visible_alias = "order", value_enum, default_value_t = SortBy::Name, global = true)]
+    pub sort_by: SortBy,
+
+    /// Print a shell completion script and exit
+    #[arg(long, value_enum, value_name = "SHELL", hide = true, global = true)]
+    pub completions: Option<Shell>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `<hash> <app>` lines
+    Text,
+    /// JSON document (app → hash, or the graph with --show-graph)
+    Json,
+    /// `apps=<json array>` line for $GITHUB_OUTPUT (appended to it when set)
+    Github,
+    /// Graphviz digraph of application dependencies, with `yeth graph`
+    Dot,
+}
+
+/// Subcommands, each the same as one of the older flags that still work
+#[derive(Subcommand, Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Output application hashes, the default
+    Hash,
+    /// Show the dependency graph (--show-graph)
+    Graph,
+    /// List discovered applications and their directories without hashing (--list-apps)
+    List,
+    /// Fail if yeth.lock is missing or doesn't match the computed hashes (--frozen)
+    Check,
+    /// Hash repeatedly and report timings (--bench N)
+    Bench {
+        /// Number of iterations
+        iterations: usize,
+    },
+    /// Write a commented yeth.toml template for a new application
+    Init {
+        /// Application directory, created if needed [default: current directory]
+        path: Option<PathBuf>,
+        /// Application name or path relative to the application to list as a dependency (repeatable)
+        #[arg(long = "dep", value_name = "DEPENDENCY")]
+        dependencies: Vec<String>,
+        /// Replace an existing yeth.toml
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersionFileFormat {
+    /// Just the hash
+    Plain,
+    /// Full and short hash, hash scheme, timestamp and dependency hashes
+    Json,
+}
+
+impl From<VersionFileFormat> for VersionFormat {
+    fn from(format: VersionFileFormat) -> Self {
+        match format {
+            VersionFileFormat::Plain => VersionFormat::Plain,
+            VersionFileFormat::Json => VersionFormat::Json,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    /// Application name
+    Name,
+    /// Application directory
+    Path,
+    /// Dependencies before the applications that use them
+    Topo,
 }
 
 impl Cli {
-    pub fn validate(self) -> Result<Self, YethError> {
+    /// Turn a subcommand into the flag it stands for, and check what clap can't:
+    /// combinations that only conflict once a subcommand is translated
+    pub fn validate(mut self) -> Result<Self, clap::Error> {
+        match &self.command {
+            None | Some(Command::Hash) | Some(Command::Init { .. }) => {}
+            Some(Command::Graph) => self.show_graph = true,
+            Some(Command::List) => self.list_apps = true,
+            Some(Command::Check) => {
+                if let Some(flag) = first_set(&[
+                    ("--app", self.app.is_some()),
+                    ("--tag", !self.tag.is_empty()),
+                    ("--impact", self.impact.is_some()),
+                    ("--exclude-app", !self.exclude_app.is_empty()),
+                    ("--keep-going", self.keep_going),
+                ]) {
+                    return Err(conflict(&format!("{} can't be used with `yeth check`", flag)));
+                }
+                self.frozen = true;
+            }
+            Some(Command::Bench { iterations }) => {
+                if self.bench.is_some() {
+                    return Err(conflict("--bench can't be used with `yeth bench`"));
+                }
+                if self.verify {
+                    return Err(conflict("--verify can't be used with `yeth bench`"));
+                }
+                self.bench = Some(*iterations);
+            }
+        }
+
+        // Subcommands other than hash don't write anything
+        if !matches!(self.command, None | Some(Command::Hash))
+            && let Some(flag) = first_set(&[
+                ("--write-versions", self.write_versions),
+                ("--write-lock", self.write_lock),
+                ("--output-file", self.output_file.is_some()),
+            ])
+        {
+            return Err(conflict(&format!("{} only applies to `yeth hash`", flag)));
+        }
+        if self.image_tag && self.format == OutputFormat::Github {
+            return Err(conflict("--image-tag prints text or json, not --format github"));
+        }
+        if self.diff.is_some() && self.format == OutputFormat::Github {
+            return Err(conflict("--diff prints text or json, not --format github"));
+        }
+        if self.baseline.is_some() && self.format == OutputFormat::Github {
+            return Err(conflict("--baseline prints text or json, not --format github"));
+        }
+        if self.format == OutputFormat::Dot && (self.graph_app.is_some() || self.graph_depth.is_some()) {
+            return Err(conflict("--format dot always prints the whole graph"));
+        }
+        if !self.show_graph {
+            if self.graph_app.is_some() {
+                return Err(missing("--graph-app needs `yeth graph` (or --show-graph)"));
+            }
+            if self.graph_depth.is_some() {
+                return Err(missing("--graph-depth needs `yeth graph` (or --show-graph)"));
+            }
+            if self.format == OutputFormat::Dot {
+                return Err(conflict("--format dot is only available for `yeth graph`"));
+            }
+        }
         Ok(self)
     }
 }
+
+/// The first of `flags` that was given
+fn first_set(flags: &[(&'static str, bool)]) -> Option<&'static str> {
+    flags.iter().find(|(_, set)| *set).map(|(flag, _)| *flag)
+}
+
+fn conflict(message: &str) -> clap::Error {
+    Cli::command().error(ErrorKind::ArgumentConflict, message)
+}
+
+fn missing(message: &str) -> clap::Error {
+    Cli::command().error(ErrorKind::MissingRequiredArgument, message)
+}
+
+/// Write the completion script for `shell` covering every flag
+pub fn write_completions(shell: Shell, out: &mut impl Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "yeth", out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_write_completions() {
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            write_completions(*shell, &mut script);
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("short-hash-length"), "no flags in {} completions", shell);
+        }
+    }
+}
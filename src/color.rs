@@ -0,0 +1,104 @@
+use anstyle::{AnsiColor, Style};
+use clap::ValueEnum;
+use std::io::IsTerminal;
+use yeth::dependency_graph::TreeStyle;
+
+/// When to color output, given with `--color`
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only on a terminal, unless NO_COLOR is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Styles for human-readable output. A disabled palette returns text as is, so
+/// callers style unconditionally and machine formats just use `Palette::plain()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    pub fn plain() -> Self {
+        Self { enabled: false }
+    }
+
+    /// Palette for one output stream: forced by `choice`, otherwise colored only
+    /// when the stream is a terminal and NO_COLOR is unset or empty
+    pub fn new(choice: ColorChoice, stream: &impl IsTerminal) -> Self {
+        let enabled = match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                stream.is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        };
+        Self { enabled }
+    }
+
+    pub fn app(&self, text: &str) -> String {
+        self.paint(Style::new().bold(), text)
+    }
+
+    pub fn hash(&self, text: &str) -> String {
+        self.paint(Style::new().dimmed(), text)
+    }
+
+    pub fn changed(&self, text: &str) -> String {
+        self.paint(AnsiColor::Yellow.on_default(), text)
+    }
+
+    pub fn error(&self, text: &str) -> String {
+        self.paint(AnsiColor::Red.on_default().bold(), text)
+    }
+
+    pub fn path(&self, text: &str) -> String {
+        self.paint(AnsiColor::Cyan.on_default(), text)
+    }
+
+    /// The same styles for `YethEngine::render_dependency_tree_styled`
+    pub fn tree_style(&self) -> TreeStyle {
+        if !self.enabled {
+            return TreeStyle::default();
+        }
+        TreeStyle {
+            app: Style::new().bold(),
+            path: AnsiColor::Cyan.on_default(),
+        }
+    }
+
+    fn paint(&self, style: Style, text: &str) -> String {
+        if self.enabled {
+            format!("{}{}{:#}", style, text, style)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette() {
+        let colored = Palette::new(ColorChoice::Always, &std::io::stdout());
+        assert_eq!(colored.app("api"), "\x1b[1mapi\x1b[0m");
+        assert_eq!(colored.hash("3177ac1a"), "\x1b[2m3177ac1a\x1b[0m");
+        assert_eq!(colored.changed("update"), "\x1b[33mupdate\x1b[0m");
+        assert_eq!(colored.error("Error:"), "\x1b[1m\x1b[31mError:\x1b[0m");
+        assert_eq!(colored.path("shared"), "\x1b[36mshared\x1b[0m");
+        assert_ne!(colored.tree_style(), TreeStyle::default());
+
+        // Never colored when turned off, and never on a file
+        let file = tempfile::tempfile().unwrap();
+        for plain in [Palette::plain(), Palette::new(ColorChoice::Never, &std::io::stdout()), Palette::new(ColorChoice::Auto, &file)] {
+            assert_eq!(plain.app("api"), "api");
+            assert_eq!(plain.hash("3177ac1a"), "3177ac1a");
+            assert_eq!(plain.error("Error:"), "Error:");
+            assert_eq!(plain.tree_style(), TreeStyle::default());
+        }
+    }
+}
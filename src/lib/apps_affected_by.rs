@@ -0,0 +1,159 @@
+use crate::cfg::{App, Config, Dependency, ExcludePattern, VERSION_FILE};
+use crate::find_dependents::find_dependents;
+use crate::hash_directory::should_exclude;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Map changed files to the apps they affect, including transitive dependents.
+/// A file affects the innermost app whose directory contains it (unless excluded there)
+/// and every app with a path dependency covering it. Other files are ignored.
+pub fn apps_affected_by(
+    changed_files: &[PathBuf],
+    apps: &HashMap<String, App>,
+    config: &Config,
+) -> Vec<String> {
+    // Exclusions as applied while hashing: the app's own plus the root .yethignore,
+    // and for the app's own directory its include list and extensions (path dependencies add their own
+    // excludes below)
+    let scopes: Vec<AppScope> = apps
+        .iter()
+        .map(|(app_name, app)| {
+            let mut own_exclude = app.exclude_patterns.clone();
+            own_exclude.extend(config.ignore_patterns.iter().cloned());
+            own_exclude.extend(app.own_file_filters());
+            AppScope {
+                app_name,
+                app,
+                dir: canonical(&app.dir),
+                hashed_dir: canonical(app.hashed_dir()),
+                own_exclude,
+            }
+        })
+        .collect();
+
+    let mut affected = BTreeSet::new();
+
+    for file in changed_files {
+        let file = canonical(file);
+
+        // Own files: the innermost app only, since parents don't hash nested apps
+        let owner = scopes
+            .iter()
+            .filter(|scope| file.starts_with(&scope.dir))
+            .max_by_key(|scope| scope.dir.components().count());
+        if let Some(scope) = owner {
+            let is_version_file = file.file_name().is_some_and(|n| n == VERSION_FILE);
+            let is_hashed = file.starts_with(&scope.hashed_dir) && !should_exclude(&file, &scope.hashed_dir, &scope.own_exclude);
+            if !is_version_file && is_hashed {
+                affected.insert(scope.app_name.clone());
+            }
+        }
+
+        // Path dependencies covering the file
+        for scope in &scopes {
+            let covered = scope.app.dependencies.iter().any(|dep| match dep {
+                Dependency::Path(path) => {
+                    let mut dep_exclude = scope.app.path_dependency_exclude(path);
+                    dep_exclude.extend(config.ignore_patterns.iter().cloned());
+                    let path = canonical(path);
+                    file.starts_with(&path) && !should_exclude(&file, &path, &dep_exclude)
+                }
+                Dependency::App(_) => false,
+            });
+            if covered {
+                affected.insert(scope.app_name.clone());
+            }
+        }
+    }
+
+    let directly_affected: Vec<String> = affected.iter().cloned().collect();
+    for app_name in directly_affected {
+        if let Ok(dependents) = find_dependents(&app_name, apps) {
+            affected.extend(dependents);
+        }
+    }
+
+    affected.into_iter().collect()
+}
+
+/// An app with its canonical directories and the exclusions used while hashing it
+struct AppScope<'a> {
+    app_name: &'a String,
+    app: &'a App,
+    dir: PathBuf,
+    /// `App::hashed_dir`, inside `dir`
+    hashed_dir: PathBuf,
+    own_exclude: Vec<ExcludePattern>,
+}
+
+/// Canonicalize a path, keeping deleted files addressable through their parent
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => canonical(parent).join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::ExcludePattern;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn app(root: &Path, name: &str, dependencies: Vec<Dependency>) -> (String, App) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        (
+            name.to_string(),
+            App {
+                name: name.to_string(),
+                dir,
+                dependencies,
+                exclude_patterns: vec![ExcludePattern::Name("dist".to_string())],
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_apps_affected_by() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("shared")).unwrap();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+
+        // common <- backend <- frontend, tools depends on ../shared, other stands alone
+        let apps: HashMap<String, App> = [
+            app(root, "common", vec![]),
+            app(root, "backend", vec![Dependency::App("common".to_string())]),
+            app(root, "frontend", vec![Dependency::App("backend".to_string())]),
+            app(root, "tools", vec![Dependency::Path(root.join("shared"))]),
+            app(root, "other", vec![]),
+        ]
+        .into_iter()
+        .collect();
+
+        // Change in a leaf app propagates to its dependents
+        let affected = apps_affected_by(&[root.join("backend").join("main.rs")], &apps, &config);
+        assert_eq!(affected, vec!["backend", "frontend"]);
+
+        let affected = apps_affected_by(&[root.join("common").join("lib.rs")], &apps, &config);
+        assert_eq!(affected, vec!["backend", "common", "frontend"]);
+
+        // Change under a path dependency, including a deleted file
+        let affected = apps_affected_by(&[root.join("shared").join("deleted.proto")], &apps, &config);
+        assert_eq!(affected, vec!["tools"]);
+
+        // Excluded files, version files and files outside every app are ignored
+        let ignored = [
+            root.join("other").join("dist").join("bundle.js"),
+            root.join("other").join(VERSION_FILE),
+            root.join("README.md"),
+        ];
+        assert!(apps_affected_by(&ignored, &apps, &config).is_empty());
+    }
+}
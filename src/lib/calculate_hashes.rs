@@ -1,54 +1,430 @@
-use crate::cfg::{App, Dependency};
+use crate::cfg::{resolved_path, App, Config, Dependency, ExcludePattern};
 use crate::error::YethError;
-use crate::compute_final_hash::compute_final_hash;
-use crate::hash_directory::{hash_directory, hash_path};
+use crate::compute_final_hash::{compute_final_hash, dependency_entry};
+use crate::hash_directory::{hash_directory_observed, hash_path};
+use crate::run_stats::RunStats;
+use crate::version_writes::DependencyHash;
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 /// Calculate hashes for a list of ordered applications
 pub fn calculate_hashes(
     ordered_apps: Vec<String>,
     apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
 ) -> Result<HashMap<String, String>, YethError> {
+    calculate_hashes_streaming(ordered_apps, apps, config, stats, |_, _| {})
+}
+
+/// Calculate hashes for a list of ordered applications, calling `on_hash`
+/// with each app name and final hash as soon as it is computed
+pub fn calculate_hashes_streaming<F>(
+    ordered_apps: Vec<String>,
+    apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
+    mut on_hash: F,
+) -> Result<HashMap<String, String>, YethError>
+where
+    F: FnMut(&str, &str),
+{
+    calculate_hashes_while(ordered_apps, apps, config, stats, |app_name, final_hash| {
+        on_hash(app_name, final_hash);
+        true
+    })
+}
+
+/// Like `calculate_hashes_streaming`, but stops once `on_hash` returns false and
+/// returns the hashes computed up to then
+pub(crate) fn calculate_hashes_while<F>(
+    ordered_apps: Vec<String>,
+    apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
+    mut on_hash: F,
+) -> Result<HashMap<String, String>, YethError>
+where
+    F: FnMut(&str, &str) -> bool,
+{
     let mut hashes = HashMap::new();
+    let mut path_cache = PathHashCache::default();
+    let nonce = run_nonce();
     for app_name in ordered_apps {
-        let app = apps.get(&app_name).unwrap();
-        let own_hash = hash_directory(&app.dir, &app.exclude_patterns)?;
+        let final_hash = hash_app(&app_name, apps, &hashes, &mut path_cache, &nonce, config, stats)?;
+        let go_on = on_hash(&app_name, &final_hash);
 
-        let mut dep_hashes_owned: Vec<String> = Vec::new();
+        hashes.insert(app_name.clone(), final_hash);
+        if !go_on {
+            break;
+        }
+    }
+    Ok(hashes)
+}
 
-        for dep in &app.dependencies {
-            match dep {
+/// Calculate hashes for a list of ordered applications without stopping at the first
+/// failure. Returns the hashes that succeeded and, in order, each app that failed with
+/// its error; apps depending on a failed app fail too.
+pub fn calculate_hashes_keep_going(
+    ordered_apps: Vec<String>,
+    apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
+) -> (HashMap<String, String>, Vec<(String, YethError)>) {
+    let mut hashes = HashMap::new();
+    let mut failures: Vec<(String, YethError)> = Vec::new();
+    let mut path_cache = PathHashCache::default();
+    let nonce = run_nonce();
+    for app_name in ordered_apps {
+        let dependencies = apps.get(&app_name).map(|app| app.dependencies.as_slice()).unwrap_or_default();
+        let failed_dep = dependencies.iter().find_map(|dep| match dep {
+            Dependency::App(dep_name) if failures.iter().any(|(failed, _)| failed == dep_name) => {
+                Some(dep_name.clone())
+            }
+            _ => None,
+        });
+        let result = match failed_dep {
+            Some(dependency) => Err(YethError::DependencyFailed {
+                app: app_name.clone(),
+                dependency,
+            }),
+            None => hash_app(&app_name, apps, &hashes, &mut path_cache, &nonce, config, stats),
+        };
+        match result {
+            Ok(final_hash) => {
+                hashes.insert(app_name, final_hash);
+            }
+            Err(error) => failures.push((app_name, error)),
+        }
+    }
+    (hashes, failures)
+}
+
+/// Calculate hashes for a list of ordered applications, keeping each app's own
+/// directory hash and the hash every dependency contributed
+pub fn calculate_hash_details(
+    ordered_apps: Vec<String>,
+    apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<HashMap<String, AppHashDetail>, YethError> {
+    let mut hashes = HashMap::new();
+    let mut details = HashMap::new();
+    let mut path_cache = PathHashCache::default();
+    let nonce = run_nonce();
+    for app_name in ordered_apps {
+        let detail = hash_app_detail(&app_name, apps, &hashes, &mut path_cache, &nonce, config, stats)?;
+        hashes.insert(app_name.clone(), detail.final_hash.clone());
+        details.insert(app_name, detail);
+    }
+    Ok(details)
+}
+
+/// How one app's final hash was put together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppHashDetail {
+    /// Hash of the app's own directory
+    pub own_hash: String,
+    pub final_hash: String,
+    /// Each dependency with the hash it contributed, in declaration order
+    pub dependencies: Vec<(Dependency, String)>,
+}
+
+/// Hash one application; its app dependencies must already be in `hashes`
+fn hash_app(
+    app_name: &str,
+    apps: &HashMap<String, App>,
+    hashes: &HashMap<String, String>,
+    path_cache: &mut PathHashCache,
+    nonce: &str,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<String, YethError> {
+    hash_app_detail(app_name, apps, hashes, path_cache, nonce, config, stats).map(|detail| detail.final_hash)
+}
+
+fn hash_app_detail(
+    app_name: &str,
+    apps: &HashMap<String, App>,
+    hashes: &HashMap<String, String>,
+    path_cache: &mut PathHashCache,
+    nonce: &str,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<AppHashDetail, YethError> {
+    let app = apps
+        .get(app_name)
+        .ok_or_else(|| YethError::UnknownAppInOrder(app_name.to_string()))?;
+    let started = stats.app_timings_enabled().then(Instant::now);
+    let observer = stats.observer();
+    if let Some(observer) = observer {
+        observer.on_app_start(app);
+    }
+
+    // Nested apps are hashed on their own; a parent only sees them through a dependency
+    let mut own_exclude = app.exclude_patterns.clone();
+    own_exclude.extend(nested_app_dirs(app, apps));
+    own_exclude.extend(app.own_file_filters());
+    let on_file = |file: &Path, digest: &str| {
+        if let Some(observer) = observer {
+            observer.on_file_hashed(app, file, digest);
+        }
+    };
+    let own =
+        hash_directory_observed(app.hashed_dir(), &own_exclude, app.normalizes_eol(config), config, stats, on_file)?;
+    let mut own_hash = own.hash;
+    if config.hash_app_name {
+        own_hash = compute_final_hash(&own_hash, &[&format!("app:{}", app.name)], None);
+    }
+
+    let mut dependencies = Vec::with_capacity(app.dependencies.len());
+    for dep in &app.dependencies {
+        let dep_hash = match dep {
+            Dependency::App(dep_name) => hashes
+                .get(dep_name)
+                .ok_or_else(|| incorrect_order(app_name, dep_name))?
+                .clone(),
+            Dependency::Path(path) => {
+                let exclude = app.path_dependency_exclude(path);
+                let hash = path_cache.get_or_hash(path, app_name, &exclude, app.normalizes_eol(config), config, stats)?;
+                if let Some(observer) = observer {
+                    observer.on_path_dependency(app, path, &hash);
+                }
+                hash
+            }
+        };
+        dependencies.push((dep.clone(), dep_hash));
+    }
+
+    let mut dep_hashes_owned: Vec<String> = dependencies
+        .iter()
+        .map(|(dep, hash)| dependency_entry(&dependency_identity(dep, config), hash))
+        .collect();
+
+    if app.always_changed {
+        dep_hashes_owned.push(dependency_entry("always_changed", nonce));
+    }
+    // The hash depends on the set of dependencies, not the order they are listed in
+    dep_hashes_owned.sort();
+
+    let dep_hash_refs: Vec<&str> = dep_hashes_owned.iter().map(|s| s.as_str()).collect();
+    if let Some(start) = started {
+        stats.record_app(app_name, start.elapsed(), own.files, own.bytes);
+    }
+    let final_hash = compute_final_hash(&own_hash, &dep_hash_refs, config.salt.as_deref());
+    if let Some(observer) = observer {
+        observer.on_app_finished(app, &final_hash);
+    }
+    Ok(AppHashDetail {
+        final_hash,
+        own_hash,
+        dependencies,
+    })
+}
+
+/// The hash each dependency of `app_name` contributes to its final hash, named as in
+/// the final hash. App dependencies are looked up in `hashes`, path dependencies are hashed again.
+pub fn dependency_hashes(
+    app_name: &str,
+    apps: &HashMap<String, App>,
+    hashes: &HashMap<String, String>,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<Vec<DependencyHash>, YethError> {
+    let app = apps
+        .get(app_name)
+        .ok_or_else(|| crate::suggest::app_not_found(app_name, apps))?;
+    app.dependencies
+        .iter()
+        .map(|dep| {
+            let hash = match dep {
                 Dependency::App(dep_name) => {
-                    let dep_hash: &String =
-                        hashes.get(dep_name).ok_or(YethError::IncorrectOrder)?;
-                    dep_hashes_owned.push(dep_hash.clone());
+                    hashes.get(dep_name).ok_or_else(|| incorrect_order(app_name, dep_name))?.clone()
                 }
                 Dependency::Path(path) => {
-                    let path_hash = hash_path(path, &app.exclude_patterns)?;
-                    dep_hashes_owned.push(path_hash);
+                    let exclude = app.path_dependency_exclude(path);
+                    hash_path(path, app_name, &exclude, app.normalizes_eol(config), config, stats)?
                 }
+            };
+            Ok(DependencyHash {
+                dependency: dependency_identity(dep, config),
+                hash,
+            })
+        })
+        .collect()
+}
+
+/// A dependency as named in the final hash: the app, or the path relative to the root
+/// with `/` separators (`..` for a path outside it), so the hash doesn't depend on where
+/// the repository is checked out. Only a path on another drive is named absolutely.
+fn dependency_identity(dep: &Dependency, config: &Config) -> String {
+    match dep {
+        Dependency::App(dep_name) => format!("app:{}", dep_name),
+        Dependency::Path(path) => {
+            let path = resolved_path(path);
+            let root = resolved_path(&config.root);
+            let common = path.components().zip(root.components()).take_while(|(a, b)| a == b).count();
+            if common == 0 {
+                return format!("path:{}", path.display());
             }
+            let components: Vec<_> = root
+                .components()
+                .skip(common)
+                .map(|_| "..".into())
+                .chain(path.components().skip(common).map(|c| c.as_os_str().to_string_lossy()))
+                .collect();
+            format!("path:{}", components.join("/"))
         }
+    }
+}
+
+fn incorrect_order(app_name: &str, dep_name: &str) -> YethError {
+    YethError::IncorrectOrder {
+        app: app_name.to_string(),
+        missing_dep: dep_name.to_string(),
+    }
+}
 
-        let dep_hash_refs: Vec<&str> = dep_hashes_owned.iter().map(|s| s.as_str()).collect();
-        let final_hash = compute_final_hash(&own_hash, &dep_hash_refs);
+/// A value unique to this hashing run: current time, process id and a per-process counter
+fn run_nonce() -> String {
+    static RUNS: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{:x}-{:x}-{:x}",
+        nanos,
+        std::process::id(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    )
+}
 
-        hashes.insert(app_name.clone(), final_hash);
+/// Path dependency hashes computed during a single run, keyed by canonical path
+/// and the hashing settings of the dependent app (they affect the result)
+#[derive(Default)]
+struct PathHashCache {
+    entries: HashMap<(PathBuf, Vec<ExcludePattern>, bool), String>,
+    misses: usize,
+}
+
+impl PathHashCache {
+    fn get_or_hash(
+        &mut self,
+        path: &Path,
+        app_name: &str,
+        exclude: &[ExcludePattern],
+        normalize_eol: bool,
+        config: &Config,
+        stats: &RunStats,
+    ) -> Result<String, YethError> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let key = (canonical, exclude.to_vec(), normalize_eol);
+        if let Some(hash) = self.entries.get(&key) {
+            return Ok(hash.clone());
+        }
+
+        self.misses += 1;
+        let hash = hash_path(path, app_name, exclude, normalize_eol, config, stats)?;
+        self.entries.insert(key, hash.clone());
+        Ok(hash)
     }
-    Ok(hashes)
+}
+
+/// Find directories of other apps located inside this app's directory
+fn nested_app_dirs(app: &App, apps: &HashMap<String, App>) -> Vec<ExcludePattern> {
+    let app_dir = app.dir.canonicalize().unwrap_or_else(|_| app.dir.clone());
+    apps.values()
+        .filter_map(|other| {
+            let other_dir = other.dir.canonicalize().unwrap_or_else(|_| other.dir.clone());
+            (other_dir != app_dir && other_dir.starts_with(&app_dir))
+                .then_some(ExcludePattern::AbsolutePath(other_dir))
+        })
+        .collect()
 }
 
 /// Calculate hashes for a specific app and its dependencies
 pub fn calculate_hashes_for_app(
     app_name: &str,
     apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
 ) -> Result<HashMap<String, String>, YethError> {
-    // Find all dependencies for the specified app
-    let dependency_order = crate::find_app_dependencies::find_app_dependencies(app_name, apps)?;
-    
-    // Calculate hashes only for the specified app and its dependencies
-    calculate_hashes(dependency_order, apps)
+    calculate_hashes_for_apps(&[app_name.to_string()], apps, config, stats)
+}
+
+/// Calculate hashes for several apps and the union of their dependencies
+pub fn calculate_hashes_for_apps(
+    app_names: &[String],
+    apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<HashMap<String, String>, YethError> {
+    // Find all dependencies for the specified apps
+    let dependency_order = combined_dependency_order(app_names, apps)?;
+    validate_path_dependencies(&dependency_order, apps)?;
+
+    // Calculate hashes only for the specified apps and their dependencies
+    calculate_hashes(dependency_order, apps, config, stats)
+}
+
+/// Dependency orders of several apps merged into one. Each order lists dependencies
+/// before the app, so appending unseen names keeps the combined order valid.
+pub(crate) fn combined_dependency_order(
+    app_names: &[String],
+    apps: &HashMap<String, App>,
+) -> Result<Vec<String>, YethError> {
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    for app_name in app_names {
+        for dep_name in crate::find_app_dependencies::find_app_dependencies(app_name, apps)? {
+            if seen.insert(dep_name.clone()) {
+                order.push(dep_name);
+            }
+        }
+    }
+    Ok(order)
+}
+
+/// Calculate hashes for an app and every app that transitively depends on it.
+/// Dependencies of those apps are hashed too, but only the impacted apps are returned.
+pub fn calculate_impact_hashes(
+    app_name: &str,
+    apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<HashMap<String, String>, YethError> {
+    let mut impacted = crate::find_dependents::find_dependents(app_name, apps)?;
+    impacted.push(app_name.to_string());
+
+    let computation_order = combined_dependency_order(&impacted, apps)?;
+
+    validate_path_dependencies(&computation_order, apps)?;
+
+    let mut hashes = calculate_hashes(computation_order, apps, config, stats)?;
+    hashes.retain(|name, _| impacted.contains(name));
+    Ok(hashes)
+}
+
+/// Check that every path dependency of the given apps exists, as `topological_sort`
+/// does for a full run, so a missing path names the app that needs it
+fn validate_path_dependencies(app_names: &[String], apps: &HashMap<String, App>) -> Result<(), YethError> {
+    for app_name in app_names {
+        let Some(app) = apps.get(app_name) else {
+            continue;
+        };
+        for dep in &app.dependencies {
+            if let Dependency::Path(path) = dep
+                && let Err(error) = path.metadata()
+            {
+                return Err(YethError::path_dependency(path, app_name, error, false));
+            }
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -63,6 +439,8 @@ mod tests {
         // Create a temporary directory for our test
         let temp_dir = TempDir::new().unwrap();
         let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
 
         // Create app1 directory and files
         let app1_dir = root.join("app1");
@@ -95,6 +473,7 @@ mod tests {
                 dir: app1_dir.clone(),
                 dependencies: vec![],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -106,6 +485,7 @@ mod tests {
                 dir: app2_dir.clone(),
                 dependencies: vec![Dependency::App("app1".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -122,12 +502,13 @@ mod tests {
                 dir: app3_dir.clone(),
                 dependencies: vec![Dependency::Path(shared_dir.clone())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
         // Test calculate_hashes with ordered apps
         let ordered_apps = vec!["app1".to_string(), "app2".to_string(), "app3".to_string()];
-        let result = calculate_hashes(ordered_apps, &apps);
+        let result = calculate_hashes(ordered_apps, &apps, &config, &stats);
 
         assert!(result.is_ok(), "Failed to calculate hashes: {:?}", result.err());
         let hashes = result.unwrap();
@@ -157,7 +538,7 @@ mod tests {
         // Test that modifying a file changes the hash
         fs::write(&app1_file1, "Modified App1 content").unwrap();
         let ordered_apps = vec!["app1".to_string(), "app2".to_string()];
-        let result = calculate_hashes(ordered_apps, &apps);
+        let result = calculate_hashes(ordered_apps, &apps, &config, &stats);
         assert!(result.is_ok());
         let new_hashes = result.unwrap();
         
@@ -173,6 +554,8 @@ mod tests {
         // Create a temporary directory for our test
         let temp_dir = TempDir::new().unwrap();
         let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
 
         // Create app1 directory and files
         let app1_dir = root.join("app1");
@@ -197,6 +580,7 @@ mod tests {
                 dir: app1_dir,
                 dependencies: vec![],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -208,15 +592,600 @@ mod tests {
                 dir: app2_dir,
                 dependencies: vec![Dependency::App("app1".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
         // Test calculate_hashes with incorrect order (app2 before app1)
         let ordered_apps = vec!["app2".to_string(), "app1".to_string()];
-        let result = calculate_hashes(ordered_apps, &apps);
+        let result = calculate_hashes(ordered_apps, &apps, &config, &stats);
 
         // Should return an error due to incorrect order
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), YethError::IncorrectOrder));
+        let error = result.unwrap_err();
+        assert!(matches!(&error, YethError::IncorrectOrder { app, missing_dep } if app == "app2" && missing_dep == "app1"));
+        assert!(error.to_string().contains("'app2'"));
+        assert!(error.to_string().contains("'app1'"));
+    }
+
+    #[test]
+    fn test_calculate_hashes_with_stale_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
+
+        let app_dir = root.join("app1");
+        fs::create_dir_all(&app_dir).unwrap();
+        let apps = HashMap::from([(
+            "app1".to_string(),
+            App {
+                name: "app1".to_string(),
+                dir: app_dir,
+                ..Default::default()
+            },
+        )]);
+
+        // An order planned before "removed" was deleted
+        let ordered_apps = vec!["app1".to_string(), "removed".to_string()];
+        let result = calculate_hashes(ordered_apps.clone(), &apps, &config, &stats);
+        assert!(matches!(result, Err(YethError::UnknownAppInOrder(name)) if name == "removed"));
+
+        let (hashes, failures) = calculate_hashes_keep_going(ordered_apps, &apps, &config, &stats);
+        assert!(hashes.contains_key("app1"));
+        assert!(matches!(&failures[..], [(name, YethError::UnknownAppInOrder(_))] if name == "removed"));
+    }
+
+    #[test]
+    fn test_calculate_hashes_with_nested_apps() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
+
+        // platform contains the emailer app in a subdirectory
+        let platform_dir = root.join("platform");
+        let emailer_dir = platform_dir.join("workers").join("emailer");
+        fs::create_dir_all(&emailer_dir).unwrap();
+        fs::write(platform_dir.join("main.rs"), "platform").unwrap();
+        let emailer_file = emailer_dir.join("main.rs");
+        fs::write(&emailer_file, "emailer").unwrap();
+
+        let mut apps = HashMap::new();
+        apps.insert(
+            "platform".to_string(),
+            App {
+                name: "platform".to_string(),
+                dir: platform_dir.clone(),
+                dependencies: vec![],
+                exclude_patterns: vec![],
+                ..Default::default()
+            },
+        );
+        apps.insert(
+            "emailer".to_string(),
+            App {
+                name: "emailer".to_string(),
+                dir: emailer_dir.clone(),
+                dependencies: vec![],
+                exclude_patterns: vec![],
+                ..Default::default()
+            },
+        );
+
+        let ordered_apps = vec!["emailer".to_string(), "platform".to_string()];
+        let before = calculate_hashes(ordered_apps.clone(), &apps, &config, &stats).unwrap();
+
+        // Changing only the child leaves the parent's hash untouched
+        fs::write(&emailer_file, "emailer v2").unwrap();
+        let after = calculate_hashes(ordered_apps.clone(), &apps, &config, &stats).unwrap();
+        assert_ne!(before.get("emailer"), after.get("emailer"));
+        assert_eq!(before.get("platform"), after.get("platform"));
+
+        // Declaring the child as a dependency brings its changes in through the dependency hash
+        apps.get_mut("platform").unwrap().dependencies = vec![Dependency::App("emailer".to_string())];
+        let with_dep = calculate_hashes(ordered_apps.clone(), &apps, &config, &stats).unwrap();
+        fs::write(&emailer_file, "emailer v3").unwrap();
+        let with_dep_after = calculate_hashes(ordered_apps, &apps, &config, &stats).unwrap();
+        assert_ne!(with_dep.get("platform"), with_dep_after.get("platform"));
+    }
+
+    #[test]
+    fn test_path_hash_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
+
+        let shared_dir = root.join("shared");
+        fs::create_dir_all(shared_dir.join("docs")).unwrap();
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(shared_dir.join("lib.js"), "shared").unwrap();
+        fs::write(shared_dir.join("docs").join("README.md"), "docs").unwrap();
+
+        let mut cache = PathHashCache::default();
+
+        // The same directory reached through different spellings is hashed once
+        let first = cache.get_or_hash(&shared_dir, "app", &[], false, &config, &stats).unwrap();
+        let second = cache
+            .get_or_hash(&root.join("app").join("..").join("shared"), "app", &[], false, &config, &stats)
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(cache.misses, 1);
+
+        // Only the miss read files: lib.js (6 bytes) and docs/README.md (4 bytes)
+        assert_eq!(stats.files_hashed(), 2);
+        assert_eq!(stats.bytes_hashed(), 10);
+        assert_eq!(first, hash_path(&shared_dir, "app", &[], false, &config, &stats).unwrap());
+
+        // Different exclude patterns get their own entry
+        let exclude = vec![ExcludePattern::Name("docs".to_string())];
+        let excluded = cache.get_or_hash(&shared_dir, "app", &exclude, false, &config, &stats).unwrap();
+        assert_ne!(first, excluded);
+        assert_eq!(cache.misses, 2);
+    }
+
+    #[test]
+    fn test_calculate_impact_hashes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
+
+        // Diamond: base <- left, base <- right, left + right <- top; extra <- right
+        let mut apps = HashMap::new();
+        for (name, deps) in [
+            ("base", vec![]),
+            ("extra", vec![]),
+            ("left", vec!["base"]),
+            ("right", vec!["base", "extra"]),
+            ("top", vec!["left", "right"]),
+            ("other", vec![]),
+        ] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.rs"), name).unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    dependencies: deps.into_iter().map(|d| Dependency::App(d.to_string())).collect(),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let all_hashes = calculate_hashes(
+            crate::topological_sort::topological_sort(&apps).unwrap(),
+            &apps,
+            &config,
+            &stats,
+        )
+        .unwrap();
+
+        // Only the app and its dependents are returned, with the same hashes as a full run
+        let impact = calculate_impact_hashes("left", &apps, &config, &stats).unwrap();
+        let mut impacted: Vec<&String> = impact.keys().collect();
+        impacted.sort();
+        assert_eq!(impacted, vec!["left", "top"]);
+        for (name, hash) in &impact {
+            assert_eq!(all_hashes.get(name), Some(hash));
+        }
+
+        let impact = calculate_impact_hashes("base", &apps, &config, &stats).unwrap();
+        let mut impacted: Vec<&String> = impact.keys().collect();
+        impacted.sort();
+        assert_eq!(impacted, vec!["base", "left", "right", "top"]);
+        assert_eq!(impact.get("top"), all_hashes.get("top"));
+
+        assert!(matches!(
+            calculate_impact_hashes("missing", &apps, &config, &stats),
+            Err(YethError::AppNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_calculate_hashes_streaming() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
+
+        let mut apps = HashMap::new();
+        for (name, deps) in [("lib", vec![]), ("service", vec!["lib"])] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.rs"), name).unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    dependencies: deps.into_iter().map(|d| Dependency::App(d.to_string())).collect(),
+                    ..Default::default()
+                },
+            );
+        }
+
+        // Results arrive in processing order and match the returned map
+        let ordered_apps = vec!["lib".to_string(), "service".to_string()];
+        let mut streamed = Vec::new();
+        let hashes = calculate_hashes_streaming(ordered_apps, &apps, &config, &stats, |app_name, hash| {
+            streamed.push((app_name.to_string(), hash.to_string()));
+        })
+        .unwrap();
+
+        assert_eq!(streamed.len(), 2);
+        assert_eq!(streamed[0].0, "lib");
+        assert_eq!(streamed[1].0, "service");
+        for (app_name, hash) in &streamed {
+            assert_eq!(hashes.get(app_name), Some(hash));
+        }
+    }
+
+    #[test]
+    fn test_calculate_hashes_always_changed() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
+
+        // generator is always changed, service depends on it, lib is regular
+        let mut apps = HashMap::new();
+        for (name, deps, always_changed) in [
+            ("generator", vec![], true),
+            ("lib", vec![], false),
+            ("service", vec!["generator", "lib"], false),
+        ] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.rs"), name).unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    dependencies: deps.into_iter().map(|d| Dependency::App(d.to_string())).collect(),
+                    always_changed,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let ordered_apps = vec!["generator".to_string(), "lib".to_string(), "service".to_string()];
+        let first = calculate_hashes(ordered_apps.clone(), &apps, &config, &stats).unwrap();
+        let second = calculate_hashes(ordered_apps, &apps, &config, &stats).unwrap();
+
+        // Nothing changed on disk, yet the app and its dependent differ between runs
+        assert_ne!(first.get("generator"), second.get("generator"));
+        assert_ne!(first.get("service"), second.get("service"));
+        assert_eq!(first.get("lib"), second.get("lib"));
+    }
+
+    #[test]
+    fn test_calculate_hashes_dependency_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
+
+        let shared_dir = root.join("shared");
+        fs::create_dir_all(&shared_dir).unwrap();
+        fs::write(shared_dir.join("lib.rs"), "shared").unwrap();
+
+        // Two apps with identical content and the same dependencies listed in different order
+        let mut apps = HashMap::new();
+        let dependencies = vec![
+            Dependency::App("left".to_string()),
+            Dependency::App("right".to_string()),
+            Dependency::Path(shared_dir.clone()),
+        ];
+        for (name, deps) in [
+            ("left", vec![]),
+            ("right", vec![]),
+            ("forward", dependencies.clone()),
+            ("reversed", dependencies.into_iter().rev().collect()),
+        ] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            let content = if name == "left" || name == "right" { name } else { "same" };
+            fs::write(dir.join("main.rs"), content).unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    dependencies: deps,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let ordered_apps = ["left", "right", "forward", "reversed"].map(String::from).to_vec();
+        let hashes = calculate_hashes(ordered_apps, &apps, &config, &stats).unwrap();
+        assert_eq!(hashes.get("forward"), hashes.get("reversed"));
+    }
+
+    #[test]
+    fn test_calculate_hashes_dependency_identity() {
+        // The same repository in two checkouts: hashes only depend on what is inside
+        let temp_dir = TempDir::new().unwrap();
+        let hash_with = |checkout: &str, vendor: &str| {
+            let root = temp_dir.path().join(checkout);
+            for copy in ["vendor-a", "vendor-b"] {
+                fs::create_dir_all(root.join(copy)).unwrap();
+                fs::write(root.join(copy).join("lib.rs"), "identical").unwrap();
+            }
+            let dir = root.join("service");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+            let service = App {
+                name: "service".to_string(),
+                dependencies: vec![Dependency::Path(dir.join("..").join(vendor))],
+                dir,
+                ..Default::default()
+            };
+            let apps = HashMap::from([("service".to_string(), service)]);
+            let config = Config::builder().root(root).build().unwrap();
+            calculate_hashes(vec!["service".to_string()], &apps, &config, &RunStats::default()).unwrap()["service"].clone()
+        };
+
+        // Swapping a dependency for an identical copy elsewhere is a different input
+        assert_ne!(hash_with("one", "vendor-a"), hash_with("one", "vendor-b"));
+        assert_eq!(hash_with("one", "vendor-a"), hash_with("two", "vendor-a"));
+    }
+
+    #[test]
+    fn test_dependency_identity_outside_root() {
+        // A sibling checkout next to the repository, in two different places
+        let temp_dir = TempDir::new().unwrap();
+        let identity_in = |checkout: &str| {
+            let parent = temp_dir.path().join(checkout);
+            fs::create_dir_all(parent.join("platform-protos")).unwrap();
+            fs::create_dir_all(parent.join("repo").join("api")).unwrap();
+            let dep = Dependency::Path(parent.join("repo").join("api").join("../../platform-protos"));
+            let config = Config::builder().root(parent.join("repo")).build().unwrap();
+            dependency_identity(&dep, &config)
+        };
+
+        assert_eq!(identity_in("ci"), "path:../platform-protos");
+        assert_eq!(identity_in("home/dev/src"), "path:../platform-protos");
+    }
+
+    #[test]
+    fn test_dependency_hashes_identity() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("vendor")).unwrap();
+        fs::write(root.join("vendor").join("lib.rs"), "vendored").unwrap();
+        let dir = root.join("service");
+        fs::create_dir_all(&dir).unwrap();
+        let service = App {
+            name: "service".to_string(),
+            dependencies: vec![
+                Dependency::App("lib".to_string()),
+                Dependency::Path(dir.join("..").join("vendor")),
+            ],
+            dir,
+            ..Default::default()
+        };
+        let apps = HashMap::from([("service".to_string(), service)]);
+        let hashes = HashMap::from([("lib".to_string(), "a".repeat(64))]);
+        let config = Config::builder().root(root).build().unwrap();
+
+        // Named like in the final hash, not by the absolute path of this checkout
+        let dependencies = dependency_hashes("service", &apps, &hashes, &config, &RunStats::default()).unwrap();
+        let names: Vec<_> = dependencies.iter().map(|dep| dep.dependency.as_str()).collect();
+        assert_eq!(names, vec!["app:lib", "path:vendor"]);
+        assert_eq!(dependencies[0].hash, "a".repeat(64));
+    }
+
+    #[test]
+    fn test_calculate_hashes_hash_app_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let hash = |hash_app_name: bool, name: &str| {
+            let config = Config::builder()
+                .root(root.to_path_buf())
+                .hash_app_name(hash_app_name)
+                .build()
+                .unwrap();
+            let apps = crate::discover_apps::discover_apps(&config).unwrap();
+            let hashes = calculate_hashes(vec![name.to_string()], &apps, &config, &RunStats::default()).unwrap();
+            hashes[name].clone()
+        };
+
+        let app_dir = root.join("billing");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("yeth.toml"), "[app]\n").unwrap();
+        fs::write(app_dir.join("main.rs"), "fn main() {}").unwrap();
+        let plain = hash(false, "billing");
+        let named = hash(true, "billing");
+        assert_ne!(plain, named);
+
+        // Rename the directory: only the name-aware hash notices
+        fs::rename(&app_dir, root.join("payments")).unwrap();
+        assert_eq!(hash(false, "payments"), plain);
+        assert_ne!(hash(true, "payments"), named);
+    }
+
+    #[test]
+    fn test_calculate_hashes_for_app_missing_path_dependency() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+
+        let mut apps = HashMap::new();
+        for (name, dependencies) in [
+            ("lib", vec![Dependency::Path(root.join("missing"))]),
+            ("service", vec![Dependency::App("lib".to_string())]),
+        ] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    dependencies,
+                    ..Default::default()
+                },
+            );
+        }
+
+        // The missing path is reported with the app that declares it, even through a dependency
+        let error = calculate_hashes_for_app("service", &apps, &config, &RunStats::default()).unwrap_err();
+        assert!(matches!(&error, YethError::PathDependencyNotFound(path, app) if path == &root.join("missing") && app == "lib"));
+    }
+
+    #[test]
+    fn test_calculate_hashes_normalize_line_endings() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // The same sources checked out on Windows and on Linux, plus a binary file
+        let mut apps = HashMap::new();
+        for (name, eol) in [("windows", "\r\n"), ("linux", "\n")] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.rs"), ["fn main() {", "}", ""].join(eol)).unwrap();
+            fs::write(dir.join("logo.bin"), b"\0\r\n").unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let hashes = |normalize_line_endings: bool| {
+            let config = Config::builder()
+                .root(root.to_path_buf())
+                .normalize_line_endings(normalize_line_endings)
+                .build()
+                .unwrap();
+            let ordered_apps = vec!["windows".to_string(), "linux".to_string()];
+            calculate_hashes(ordered_apps, &apps, &config, &RunStats::default()).unwrap()
+        };
+
+        let exact = hashes(false);
+        assert_ne!(exact["windows"], exact["linux"]);
+        let normalized = hashes(true);
+        assert_eq!(normalized["windows"], normalized["linux"]);
+    }
+
+    #[test]
+    fn test_calculate_hashes_keep_going() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let stats = RunStats::default();
+
+        // "broken" fails to hash (its path dependency vanished), "service" depends on it
+        let mut apps = HashMap::new();
+        for (name, deps) in [
+            ("broken", vec![Dependency::Path(root.join("missing"))]),
+            ("service", vec![Dependency::App("broken".to_string())]),
+            ("healthy", vec![]),
+        ] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.rs"), name).unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    dependencies: deps,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let ordered_apps = ["broken", "service", "healthy"].map(String::from).to_vec();
+        assert!(calculate_hashes(ordered_apps.clone(), &apps, &config, &stats).is_err());
+
+        let (hashes, failures) = calculate_hashes_keep_going(ordered_apps, &apps, &config, &stats);
+        assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["healthy"]);
+        assert_eq!(failures.len(), 2);
+        assert!(matches!(failures[0], (ref app, YethError::PathVanished { .. }) if app == "broken"));
+        assert!(matches!(failures[1], (ref app, YethError::DependencyFailed { .. }) if app == "service"));
+    }
+
+    #[test]
+    fn test_calculate_hashes_path_dependency_exclude() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+
+        // proto is a plain directory with sources and generated code
+        let proto_dir = root.join("proto");
+        fs::create_dir_all(&proto_dir).unwrap();
+        fs::write(proto_dir.join("api.proto"), "message Api {}").unwrap();
+        fs::write(proto_dir.join("api.pb.go"), "generated v1").unwrap();
+
+        let app_dir = root.join("service");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(
+            app_dir.join("yeth.toml"),
+            "[app]\ndependencies = [{ path = \"../proto\", exclude = [\"*.pb.go\"] }]\n",
+        )
+        .unwrap();
+
+        let hash = || {
+            let apps = crate::discover_apps::discover_apps(&config).unwrap();
+            let hashes = calculate_hashes(vec!["service".to_string()], &apps, &config, &RunStats::default()).unwrap();
+            hashes["service"].clone()
+        };
+        let before = hash();
+
+        // Regenerated output doesn't matter, the sources do
+        fs::write(proto_dir.join("api.pb.go"), "generated v2").unwrap();
+        assert_eq!(hash(), before);
+        fs::write(proto_dir.join("api.proto"), "message Api { string id = 1; }").unwrap();
+        assert_ne!(hash(), before);
+    }
+
+    #[test]
+    fn test_calculate_hashes_hash_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+
+        let app_dir = root.join("service");
+        fs::create_dir_all(app_dir.join("src")).unwrap();
+        fs::create_dir_all(app_dir.join("docs")).unwrap();
+        fs::write(app_dir.join("yeth.toml"), "[app]\nhash_dir = \"src\"\n").unwrap();
+        fs::write(app_dir.join("src").join("main.rs"), "fn main() {}").unwrap();
+        fs::write(app_dir.join("docs").join("README.md"), "draft").unwrap();
+
+        let hash = || {
+            let apps = crate::discover_apps::discover_apps(&config).unwrap();
+            let hashes = calculate_hashes(vec!["service".to_string()], &apps, &config, &RunStats::default()).unwrap();
+            hashes["service"].clone()
+        };
+        let before = hash();
+
+        // Only the configured subdirectory counts
+        fs::write(app_dir.join("docs").join("README.md"), "final").unwrap();
+        fs::write(app_dir.join("notes.txt"), "scratch").unwrap();
+        assert_eq!(hash(), before);
+        fs::write(app_dir.join("src").join("main.rs"), "fn main() { run() }").unwrap();
+        assert_ne!(hash(), before);
+
+        // It has to be a directory inside the app
+        for (hash_dir, outside) in [("missing", false), ("../", true)] {
+            fs::write(app_dir.join("yeth.toml"), format!("[app]\nhash_dir = {:?}\n", hash_dir)).unwrap();
+            let err = crate::discover_apps::discover_apps(&config).unwrap_err();
+            if outside {
+                assert!(matches!(&err, YethError::HashDirOutsideApp { app, .. } if app == "service"));
+            } else {
+                assert!(matches!(&err, YethError::HashDirNotFound { app, .. } if app == "service"));
+            }
+        }
     }
 }
@@ -1,26 +1,202 @@
-use serde::Deserialize;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Component, Path};
 use std::path::PathBuf;
 
 use crate::error::YethError;
+use crate::glob_match::is_glob;
 
 
 pub const CONFIG_FILE: &str = "yeth.toml";
+pub const VERSION_FILE: &str = "yeth.version";
+/// Every app's hash, saved at the root by `--write-lock`
+pub const LOCK_FILE: &str = "yeth.lock";
+/// Exclude patterns applied to every hashed directory, read from the root
+pub const IGNORE_FILE: &str = ".yethignore";
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 8192;
+/// Marks the workspace root for the upward root search
+pub const WORKSPACE_FILE: &str = "yeth.workspace.toml";
+/// Environment variable naming the root when none is set explicitly
+pub const ROOT_ENV_VAR: &str = "YETH_ROOT";
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub root: PathBuf,
+    /// How `root` was chosen
+    pub root_source: RootSource,
+    /// Follow symbolic links while hashing directories
+    pub follow_symlinks: bool,
+    /// Warn about unknown keys in yeth.toml instead of failing
+    pub lenient: bool,
+    /// Maximum depth of directory walks while hashing, counted from the hashed
+    /// directory (1 = only files directly inside it). Discovery always walks the
+    /// whole tree, so a limit never hides nested apps.
+    pub max_depth: Option<usize>,
+    /// Hash only files tracked by git (in the index), read from the working tree
+    pub tracked_only: bool,
+    /// Expand `$VAR`, `${VAR}` and `~` in dependency and exclude paths
+    pub expand_env: bool,
+    /// Patterns from the root .yethignore plus the root lockfile, merged with each app's excludes
+    pub ignore_patterns: Vec<ExcludePattern>,
+    /// Hash files as committed in this git revision instead of the working tree
+    pub git_rev: Option<String>,
+    /// Turn path dependencies that point at an app directory into app dependencies
+    pub promote_path_deps: bool,
+    /// Suppress warnings
+    pub quiet: bool,
+    /// Size in bytes of the buffer used to read files while hashing
+    pub read_buffer_size: usize,
+    /// Memory-map large files instead of reading them
+    pub use_mmap: bool,
+    /// Mix the app name into its own hash, so renaming an app changes its hash.
+    /// Turning this on changes every hash once.
+    pub hash_app_name: bool,
+    /// Hash CRLF as LF in text files of every app, as if each set `normalize_eol`
+    pub normalize_line_endings: bool,
+    /// Most threads (and so files open at once) used for parallel work
+    pub jobs: usize,
+    /// Show paths in graphs and errors as they are instead of relative to `root`
+    pub absolute_paths: bool,
+    /// Mixed into every app's final hash, so changing it invalidates every cache
+    pub salt: Option<String>,
+    /// Fail on suspicious configuration that is otherwise only warned about
+    pub strict: bool,
+    /// Reject path dependencies that resolve outside `root`
+    pub deny_external_paths: bool,
+    /// Hash the target path of each symlink that isn't followed, instead of skipping it
+    pub hash_symlink_targets: bool,
+    /// Leave files that can't be read out of directory hashes with a warning, instead of failing
+    pub skip_unreadable: bool,
+    /// Skip apps whose yeth.toml is a directory or a dangling symlink with a warning
+    pub keep_going: bool,
 }
 
 impl Config {
     pub fn builder() -> ConfigBuilder {
         ConfigBuilder::default()
     }
+
+    /// Report a non-fatal problem to stderr
+    pub fn warn(&self, message: impl std::fmt::Display) {
+        if self.quiet {
+            return;
+        }
+        eprintln!("Warning: {}", message);
+    }
+
+    /// `path` as shown to users: relative to `root` unless `absolute_paths` is set.
+    /// Paths outside the root are kept as they are.
+    pub fn display_path(&self, path: &Path) -> PathBuf {
+        if self.absolute_paths {
+            return path.to_path_buf();
+        }
+        // A missing path (or dangling link) is resolved through its parent
+        let resolved = path.canonicalize().ok().or_else(|| {
+            let parent = path.parent()?.canonicalize().ok()?;
+            Some(parent.join(path.file_name()?))
+        });
+        let canonical = resolved
+            .zip(self.root.canonicalize().ok())
+            .and_then(|(path, root)| path.strip_prefix(root).ok().map(Path::to_path_buf));
+        let lexical = || {
+            let relative = path.strip_prefix(&self.root).ok()?;
+            Some(relative.components().filter(|c| *c != Component::CurDir).collect())
+        };
+        match canonical.or_else(lexical) {
+            Some(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+            Some(relative) => relative,
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Whether `path` resolves to somewhere outside `root`
+    pub fn is_external(&self, path: &Path) -> bool {
+        !resolved_path(path).starts_with(resolved_path(&self.root))
+    }
+}
+
+/// `path` made absolute with symlinks resolved. Whatever doesn't exist yet is
+/// appended to its nearest existing ancestor, with `.` and `..` resolved lexically.
+pub(crate) fn resolved_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    let absolute = lexically_normalized(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+    absolute
+        .ancestors()
+        .skip(1)
+        .find_map(|ancestor| {
+            let canonical = ancestor.canonicalize().ok()?;
+            Some(canonical.join(absolute.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or(absolute)
+}
+
+/// `path` with `.` and `..` components resolved without touching the filesystem
+pub(crate) fn lexically_normalized(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Where `Config::root` came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootSource {
+    /// Set with `ConfigBuilder::root`
+    Explicit,
+    /// Read from `YETH_ROOT`
+    Env,
+    /// Nearest ancestor of the current directory containing this marker
+    Marker(&'static str),
+    /// Nothing else applied, the current directory is used
+    CurrentDir,
+}
+
+impl fmt::Display for RootSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RootSource::Explicit => write!(f, "set explicitly"),
+            RootSource::Env => write!(f, "from ${}", ROOT_ENV_VAR),
+            RootSource::Marker(marker) => write!(f, "contains {}", marker),
+            RootSource::CurrentDir => write!(f, "no workspace marker found, using the current directory"),
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct ConfigBuilder {
     root: Option<PathBuf>,
+    follow_symlinks: bool,
+    lenient: bool,
+    max_depth: Option<usize>,
+    tracked_only: bool,
+    expand_env: bool,
+    git_rev: Option<String>,
+    promote_path_deps: bool,
+    quiet: bool,
+    read_buffer_size: Option<usize>,
+    use_mmap: bool,
+    hash_app_name: bool,
+    normalize_line_endings: bool,
+    jobs: Option<usize>,
+    absolute_paths: bool,
+    salt: Option<String>,
+    strict: bool,
+    deny_external_paths: bool,
+    hash_symlink_targets: bool,
+    skip_unreadable: bool,
+    keep_going: bool,
+    search_upward: Option<bool>,
 }
 
 impl ConfigBuilder {
@@ -29,37 +205,367 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn tracked_only(mut self, tracked_only: bool) -> Self {
+        self.tracked_only = tracked_only;
+        self
+    }
+
+    pub fn expand_env(mut self, expand_env: bool) -> Self {
+        self.expand_env = expand_env;
+        self
+    }
+
+    pub fn git_rev(mut self, git_rev: Option<String>) -> Self {
+        self.git_rev = git_rev;
+        self
+    }
+
+    pub fn promote_path_deps(mut self, promote_path_deps: bool) -> Self {
+        self.promote_path_deps = promote_path_deps;
+        self
+    }
+
+    /// Resolve a path dependency on an app's directory, like `./libs/auth`, to that
+    /// app and its dependencies; another name for `promote_path_deps`
+    pub fn resolve_path_deps_to_apps(self, resolve: bool) -> Self {
+        self.promote_path_deps(resolve)
+    }
+
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    pub fn read_buffer_size(mut self, read_buffer_size: usize) -> Self {
+        self.read_buffer_size = Some(read_buffer_size);
+        self
+    }
+
+    pub fn use_mmap(mut self, use_mmap: bool) -> Self {
+        self.use_mmap = use_mmap;
+        self
+    }
+
+    /// Mix the app name into its own hash (off by default; changes every hash once)
+    pub fn hash_app_name(mut self, hash_app_name: bool) -> Self {
+        self.hash_app_name = hash_app_name;
+        self
+    }
+
+    /// Hash CRLF as LF in text files of every app, not just those with `normalize_eol`
+    pub fn normalize_line_endings(mut self, normalize_line_endings: bool) -> Self {
+        self.normalize_line_endings = normalize_line_endings;
+        self
+    }
+
+    /// Limit parallel work to `jobs` threads (default: number of CPUs)
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Show paths in graphs and errors as they are instead of relative to the root
+    pub fn absolute_paths(mut self, absolute_paths: bool) -> Self {
+        self.absolute_paths = absolute_paths;
+        self
+    }
+
+    /// Mix `salt` into every app's final hash (None keeps hashes unchanged)
+    pub fn salt(mut self, salt: Option<String>) -> Self {
+        self.salt = salt;
+        self
+    }
+
+    /// Fail on suspicious configuration (such as a path dependency inside another app)
+    /// instead of warning
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Make a path dependency that resolves outside the root an error
+    pub fn deny_external_paths(mut self, deny_external_paths: bool) -> Self {
+        self.deny_external_paths = deny_external_paths;
+        self
+    }
+
+    /// Hash the target path of symlinks instead of skipping them, so repointing a link
+    /// changes the hash. Has no effect with `follow_symlinks`, which hashes the targets' content.
+    pub fn hash_symlink_targets(mut self, hash_symlink_targets: bool) -> Self {
+        self.hash_symlink_targets = hash_symlink_targets;
+        self
+    }
+
+    /// Leave unreadable files out of directory hashes with a warning instead of failing.
+    /// Off by default: a skipped file silently changes what the hash covers.
+    pub fn skip_unreadable(mut self, skip_unreadable: bool) -> Self {
+        self.skip_unreadable = skip_unreadable;
+        self
+    }
+
+    /// Skip apps whose yeth.toml can't be a config (a directory or a dangling symlink)
+    /// with a warning instead of failing discovery
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Look for `yeth.workspace.toml` or `.git` above the current directory when
+    /// no root is given (on by default)
+    pub fn search_upward(mut self, search_upward: bool) -> Self {
+        self.search_upward = Some(search_upward);
+        self
+    }
+
     pub fn build(self) -> Result<Config, YethError> {
+        let current_dir = std::env::current_dir().ok();
+        let (root, root_source) = resolve_root(
+            self.root,
+            std::env::var_os(ROOT_ENV_VAR),
+            current_dir.as_deref(),
+            self.search_upward.unwrap_or(true),
+        );
+        let read_buffer_size = self.read_buffer_size.unwrap_or(DEFAULT_READ_BUFFER_SIZE);
+        if read_buffer_size == 0 {
+            return Err(YethError::ZeroReadBufferSize);
+        }
+        let jobs = self.jobs.unwrap_or_else(default_jobs);
+        if jobs == 0 {
+            return Err(YethError::ZeroJobs);
+        }
+
+        let ignore_path = root.join(IGNORE_FILE);
+        let mut ignore_patterns = match std::fs::read_to_string(&ignore_path) {
+            Ok(content) => parse_ignore_patterns(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(source) => {
+                return Err(YethError::ConfigReadError {
+                    path: ignore_path,
+                    source,
+                });
+            }
+        };
+        // The lockfile holds the hashes, so it must never feed into them (matters when the root is an app)
+        let lock_path = root.canonicalize().unwrap_or_else(|_| root.clone()).join(LOCK_FILE);
+        ignore_patterns.push(ExcludePattern::AbsolutePath(lock_path));
+
         Ok(Config {
-            root: self.root.unwrap_or_else(|| PathBuf::from(".")),
+            root,
+            root_source,
+            follow_symlinks: self.follow_symlinks,
+            lenient: self.lenient,
+            max_depth: self.max_depth,
+            tracked_only: self.tracked_only,
+            expand_env: self.expand_env,
+            ignore_patterns,
+            git_rev: self.git_rev,
+            promote_path_deps: self.promote_path_deps,
+            quiet: self.quiet,
+            read_buffer_size,
+            use_mmap: self.use_mmap,
+            hash_app_name: self.hash_app_name,
+            normalize_line_endings: self.normalize_line_endings,
+            jobs,
+            absolute_paths: self.absolute_paths,
+            salt: self.salt,
+            strict: self.strict,
+            deny_external_paths: self.deny_external_paths,
+            hash_symlink_targets: self.hash_symlink_targets,
+            skip_unreadable: self.skip_unreadable,
+            keep_going: self.keep_going,
         })
     }
 }
 
+/// Number of CPUs, the default for `jobs`
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Pick the root: explicit value, then `YETH_ROOT`, then the nearest ancestor of
+/// `current_dir` with a workspace marker, then `.`
+fn resolve_root(
+    explicit: Option<PathBuf>,
+    env_root: Option<OsString>,
+    current_dir: Option<&Path>,
+    search_upward: bool,
+) -> (PathBuf, RootSource) {
+    if let Some(root) = explicit {
+        return (root, RootSource::Explicit);
+    }
+    if let Some(root) = env_root.filter(|root| !root.is_empty()) {
+        return (PathBuf::from(root), RootSource::Env);
+    }
+    if search_upward && let Some(current_dir) = current_dir {
+        for dir in current_dir.ancestors() {
+            for marker in [WORKSPACE_FILE, ".git"] {
+                if dir.join(marker).exists() {
+                    return (dir.to_path_buf(), RootSource::Marker(marker));
+                }
+            }
+        }
+    }
+    (PathBuf::from("."), RootSource::CurrentDir)
+}
+
+/// Parse .yethignore content: one pattern per line, `#` comments and blank lines skipped.
+/// Globs and patterns with `/` match paths relative to the hashed directory, plain names
+/// match any file or directory with that name.
+pub fn parse_ignore_patterns(content: &str) -> Vec<ExcludePattern> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.trim_end_matches('/'))
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            if is_glob(line) || line.contains('/') {
+                ExcludePattern::Glob(line.to_string())
+            } else {
+                ExcludePattern::Name(line.to_string())
+            }
+        })
+        .collect()
+}
+
+
+/// Content of a yeth.toml as it takes part in the hash: the config re-serialized in
+/// one canonical form without `[app.meta]` and `tags`, so metadata and tag edits never
+/// change the hash, whatever comments or formatting surround them.
+/// Unparsable content is hashed byte for byte.
+pub(crate) fn hashed_config_content(content: Vec<u8>) -> Vec<u8> {
+    let Ok(text) = std::str::from_utf8(&content) else {
+        return content;
+    };
+    let Ok(mut table) = toml::from_str::<toml::Table>(text) else {
+        return content;
+    };
+    if let Some(app) = table.get_mut("app").and_then(|app| app.as_table_mut()) {
+        app.remove("meta");
+        app.remove("tags");
+    }
+    toml::to_string(&table).map(String::into_bytes).unwrap_or(content)
+}
+
+/// Top-level keys accepted in yeth.toml
+pub const CONFIG_KEYS: &[&str] = &["app"];
+
+/// Keys accepted in the `[app]` table of yeth.toml
+pub const APP_KEYS: &[&str] = &[
+    "name",
+    "dependencies",
+    "exclude",
+    "include",
+    "hash_dir",
+    "extensions",
+    "normalize_eol",
+    "always_changed",
+    "meta",
+    "tags",
+];
 
-#[derive(Deserialize, Debug)]
+/// Parsed yeth.toml. Every key is optional: an empty file or a bare `[app]`
+/// describes an application with no dependencies and no exclusions.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfig {
+    #[serde(default)]
     pub app: AppInfo,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
 pub struct AppInfo {
-    pub dependencies: Vec<String>,
+    /// Name to use instead of the directory's, required for an app at the root
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub dependencies: Vec<DependencySpec>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Hash only files matching one of these patterns, relative to the hashed directory
+    #[serde(default)]
+    pub include: Option<Vec<String>>,
+    /// Hash this subdirectory instead of the whole app directory
+    #[serde(default)]
+    pub hash_dir: Option<String>,
+    /// Hash only files with one of these extensions (case-insensitive, no dot)
+    #[serde(default)]
+    pub extensions: Option<Vec<String>>,
+    /// Hash CRLF as LF in text files
+    #[serde(default)]
+    pub normalize_eol: bool,
+    /// Treat the app as changed on every run
+    #[serde(default)]
+    pub always_changed: bool,
+    /// Free-form metadata (`[app.meta]`), passed through to JSON output and never hashed
+    #[serde(default)]
+    pub meta: HashMap<String, toml::Value>,
+    /// Categories for selecting apps with `--tag`, never hashed
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// One entry of `dependencies`: a bare string, or an inline table naming an app or a path
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum DependencySpec {
+    /// `"app1"` or `"../shared"`
+    Plain(String),
+    /// `{ app = "app1" }`
+    App(AppDependencySpec),
+    /// `{ path = "../proto", exclude = ["*.pb.go"] }`
+    Path(PathDependencySpec),
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AppDependencySpec {
+    pub app: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PathDependencySpec {
+    pub path: String,
+    /// Excluded from this dependency only, on top of the app's own excludes
     #[serde(default)]
     pub exclude: Vec<String>,
 }
 
 /// Exclusion pattern
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ExcludePattern {
     /// Simple name (node_modules) - excluded wherever it appears
     Name(String),
     /// Absolute path - excludes specific file/directory
     AbsolutePath(PathBuf),
+    /// Glob (*.log, build/**) - matched against names, or relative paths if it has `/`
+    Glob(String),
+    /// Everything except files matching one of these include globs, relative to the
+    /// hashed directory (a directory pattern covers its whole subtree)
+    NotIncluded(Vec<String>),
+    /// Every file whose extension is not in this list (lowercase, without the dot)
+    OtherExtension(Vec<String>),
 }
 
 /// Dependency type
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Dependency {
     /// Dependency on another application
     App(String),
@@ -69,8 +575,8 @@ pub enum Dependency {
 
 impl Dependency {
     pub fn parse(dep_str: &str, app_dir: &Path) -> Self {
-        if dep_str.contains('/') || dep_str.starts_with('.') {
-            let path = app_dir.join(dep_str);
+        if is_path_like(dep_str) {
+            let path = join_config_path(app_dir, dep_str);
             Dependency::Path(path)
         } else {
             Dependency::App(dep_str.to_string())
@@ -78,11 +584,181 @@ impl Dependency {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Check if a config string refers to a path rather than a name.
+/// Both `/` and `\` are treated as separators so configs behave the same on every platform.
+pub fn is_path_like(value: &str) -> bool {
+    is_exclude_path(value) || value.starts_with('.')
+}
+
+/// Check if an exclude entry is a path rather than a name matched everywhere. Unlike
+/// `is_path_like`, a name starting with a dot (`.env`) is still a name.
+pub fn is_exclude_path(value: &str) -> bool {
+    value.contains(['/', '\\', std::path::MAIN_SEPARATOR]) || value == "." || value == ".."
+}
+
+/// Join a path from a config file onto `base`, splitting on both `/` and `\`
+/// so the resulting PathBuf uses the native separator
+pub fn join_config_path(base: &Path, value: &str) -> PathBuf {
+    if Path::new(value).is_absolute() {
+        return PathBuf::from(value);
+    }
+
+    let mut path = base.to_path_buf();
+    for component in value.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        path.push(component);
+    }
+    path
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct App {
     #[allow(dead_code)]
     pub name: String,
     pub dir: PathBuf,
     pub dependencies: Vec<Dependency>,
     pub exclude_patterns: Vec<ExcludePattern>,
+    /// Subdirectory of `dir` hashed instead of the whole directory
+    pub hash_dir: Option<PathBuf>,
+    /// Extra exclusions for individual path dependencies, keyed by dependency path
+    pub path_dependency_excludes: HashMap<PathBuf, Vec<ExcludePattern>>,
+    /// When set, only matching files of the app's own directory are hashed
+    pub include_patterns: Option<Vec<String>>,
+    /// When set, only files of the app's own directory with these extensions are hashed
+    pub extensions: Option<Vec<String>>,
+    /// Hash CRLF as LF in text files, for own files and path dependencies
+    pub normalize_eol: bool,
+    /// Mix a per-run nonce into the final hash, so the app and its dependents always change
+    pub always_changed: bool,
+    /// Free-form metadata from `[app.meta]`, not part of the hash
+    pub meta: HashMap<String, toml::Value>,
+    /// Categories from `tags`, not part of the hash
+    pub tags: Vec<String>,
+}
+
+impl App {
+    /// The app directory relative to `root` ("." for the root itself), or as is when
+    /// it lies outside the root
+    pub fn relative_dir(&self, root: &Path) -> PathBuf {
+        match self.dir.strip_prefix(root) {
+            Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => self.dir.clone(),
+        }
+    }
+
+    /// The directory whose files make up the app's own hash: `hash_dir` when set,
+    /// otherwise `dir`
+    pub fn hashed_dir(&self) -> &Path {
+        self.hash_dir.as_deref().unwrap_or(&self.dir)
+    }
+
+    /// Whether CRLF is hashed as LF for this app, by its own setting or the global one
+    pub fn normalizes_eol(&self, config: &Config) -> bool {
+        self.normalize_eol || config.normalize_line_endings
+    }
+
+    /// Patterns selecting which of the app's own files are hashed (include list and
+    /// extensions), on top of its excludes
+    pub fn own_file_filters(&self) -> Vec<ExcludePattern> {
+        let mut filters = Vec::new();
+        if let Some(include) = &self.include_patterns {
+            filters.push(ExcludePattern::NotIncluded(include.clone()));
+        }
+        if let Some(extensions) = &self.extensions {
+            filters.push(ExcludePattern::OtherExtension(extensions.clone()));
+        }
+        filters
+    }
+
+    /// Exclusions used while hashing one of the app's path dependencies
+    pub fn path_dependency_exclude(&self, path: &Path) -> Vec<ExcludePattern> {
+        let mut exclude = self.exclude_patterns.clone();
+        if let Some(extra) = self.path_dependency_excludes.get(path) {
+            exclude.extend(extra.iter().cloned());
+        }
+        exclude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_dependency_parse_separators() {
+        let app_dir = PathBuf::from("/repo/app");
+
+        // Forward and backward slashes resolve to the same path
+        let forward = Dependency::parse("../shared/lib", &app_dir);
+        let backward = Dependency::parse("..\\shared\\lib", &app_dir);
+        let expected = app_dir.join("..").join("shared").join("lib");
+        assert_eq!(forward, Dependency::Path(expected.clone()));
+        assert_eq!(backward, Dependency::Path(expected));
+
+        // A backslash without a leading dot is still a path
+        assert_eq!(
+            Dependency::parse("target\\debug", &app_dir),
+            Dependency::Path(app_dir.join("target").join("debug"))
+        );
+
+        // Plain names are app dependencies
+        assert_eq!(
+            Dependency::parse("backend", &app_dir),
+            Dependency::App("backend".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_ignore_patterns() {
+        let patterns = parse_ignore_patterns("# comment\n\nnode_modules\n*.log\nbuild/\n/docs/generated\n");
+        assert_eq!(
+            patterns,
+            vec![
+                ExcludePattern::Name("node_modules".to_string()),
+                ExcludePattern::Glob("*.log".to_string()),
+                ExcludePattern::Name("build".to_string()),
+                ExcludePattern::Glob("/docs/generated".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_app_relative_dir() {
+        let app = |dir: &str| App {
+            dir: PathBuf::from(dir),
+            ..Default::default()
+        };
+        let root = Path::new("/repo");
+        assert_eq!(app("/repo/services/api").relative_dir(root), PathBuf::from("services/api"));
+        assert_eq!(app("/repo").relative_dir(root), PathBuf::from("."));
+        assert_eq!(app("/elsewhere/api").relative_dir(root), PathBuf::from("/elsewhere/api"));
+    }
+
+    #[test]
+    fn test_resolve_root() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = temp_dir.path().join("repo");
+        let workspace = repo.join("services");
+        let cwd = workspace.join("api").join("src");
+        fs::create_dir_all(&cwd).unwrap();
+        fs::create_dir_all(repo.join(".git")).unwrap();
+
+        // Explicit root wins over the environment, the environment over the search
+        let explicit = resolve_root(Some(PathBuf::from("x")), Some("y".into()), Some(&cwd), true);
+        assert_eq!(explicit, (PathBuf::from("x"), RootSource::Explicit));
+        let env = resolve_root(None, Some("y".into()), Some(&cwd), true);
+        assert_eq!(env, (PathBuf::from("y"), RootSource::Env));
+
+        // The nearest marker is used
+        let found = resolve_root(None, None, Some(&cwd), true);
+        assert_eq!(found, (repo.clone(), RootSource::Marker(".git")));
+        fs::write(workspace.join(WORKSPACE_FILE), "").unwrap();
+        let found = resolve_root(None, Some("".into()), Some(&cwd), true);
+        assert_eq!(found, (workspace, RootSource::Marker(WORKSPACE_FILE)));
+
+        // Without the search, the current directory
+        let fallback = resolve_root(None, None, Some(&cwd), false);
+        assert_eq!(fallback, (PathBuf::from("."), RootSource::CurrentDir));
+    }
 }
@@ -0,0 +1,77 @@
+use crate::cfg::{App, VERSION_FILE};
+use crate::error::YethError;
+use crate::version_writes::parse_version_file;
+use std::collections::HashMap;
+use std::fs;
+
+/// Find applications whose hash differs from the one saved in their yeth.version file.
+/// A missing version file counts as changed; a saved short hash is compared as a prefix.
+pub fn changed_apps(
+    apps: &HashMap<String, App>,
+    hashes: &HashMap<String, String>,
+) -> Result<Vec<String>, YethError> {
+    let mut changed = Vec::new();
+
+    for (app_name, hash) in hashes {
+        let app = apps
+            .get(app_name)
+            .ok_or_else(|| crate::suggest::app_not_found(app_name, apps))?;
+        let version_file = app.dir.join(VERSION_FILE);
+
+        let saved_hash = match fs::read_to_string(&version_file) {
+            Ok(content) => parse_version_file(&content).0,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        if saved_hash.is_empty() || !hash.starts_with(&saved_hash) {
+            changed.push(app_name.clone());
+        }
+    }
+
+    changed.sort();
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn make_app(root: &Path, name: &str) -> App {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        App {
+            name: name.to_string(),
+            dir,
+            dependencies: vec![],
+            exclude_patterns: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_changed_apps() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let mut apps = HashMap::new();
+        for name in ["unchanged", "short", "modified", "missing"] {
+            apps.insert(name.to_string(), make_app(root, name));
+        }
+
+        let hash = "a".repeat(64);
+        let mut hashes = HashMap::new();
+        for name in apps.keys() {
+            hashes.insert(name.clone(), hash.clone());
+        }
+
+        fs::write(root.join("unchanged").join(VERSION_FILE), &hash).unwrap();
+        fs::write(root.join("short").join(VERSION_FILE), "aaaaaaaaaa\n").unwrap();
+        fs::write(root.join("modified").join(VERSION_FILE), "b".repeat(64)).unwrap();
+
+        let changed = changed_apps(&apps, &hashes).unwrap();
+        assert_eq!(changed, vec!["missing", "modified"]);
+    }
+}
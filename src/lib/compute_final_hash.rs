@@ -1,15 +1,27 @@
 use sha2::{Digest, Sha256};
 
-/// Compute the final hash by combining the app's own hash with its dependencies' hashes
-pub fn compute_final_hash(own_hash: &str, dep_hashes: &[&str]) -> String {
+/// Compute the final hash by combining the app's own hash with its dependencies' hashes.
+/// A salt is mixed in last; without one the hash is the same as before salts existed.
+pub fn compute_final_hash(own_hash: &str, dep_hashes: &[&str], salt: Option<&str>) -> String {
     let mut hasher = Sha256::new();
     hasher.update(own_hash.as_bytes());
     for dep_hash in dep_hashes {
         hasher.update(dep_hash.as_bytes());
     }
+    if let Some(salt) = salt {
+        hasher.update(b"salt:");
+        hasher.update(salt.as_bytes());
+    }
     format!("{:x}", hasher.finalize())
 }
 
+/// One dependency as fed into `compute_final_hash`: what it is and the hash it
+/// contributes, so swapping a dependency for another with the same content still
+/// changes the result
+pub fn dependency_entry(identity: &str, hash: &str) -> String {
+    format!("{}\0{}\n", identity, hash)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -19,7 +31,7 @@ mod tests {
         // Test with empty dependencies
         let own_hash = "a1b2c3d4e5f6";
         let dep_hashes: Vec<&str> = vec![];
-        let result = compute_final_hash(own_hash, &dep_hashes);
+        let result = compute_final_hash(own_hash, &dep_hashes, None);
         
         // The result should be different from the own hash when no dependencies
         assert_ne!(result, own_hash);
@@ -28,7 +40,7 @@ mod tests {
         // Test with single dependency
         let dep_hash1 = "f6e5d4c3b2a1";
         let dep_hashes: Vec<&str> = vec![dep_hash1];
-        let result = compute_final_hash(own_hash, &dep_hashes);
+        let result = compute_final_hash(own_hash, &dep_hashes, None);
         
         // The result should be different from both inputs
         assert_ne!(result, own_hash);
@@ -38,7 +50,7 @@ mod tests {
         // Test with multiple dependencies
         let dep_hash2 = "z9y8x7w6v5u4";
         let dep_hashes: Vec<&str> = vec![dep_hash1, dep_hash2];
-        let result = compute_final_hash(own_hash, &dep_hashes);
+        let result = compute_final_hash(own_hash, &dep_hashes, None);
         
         // The result should be different from all inputs
         assert_ne!(result, own_hash);
@@ -47,13 +59,43 @@ mod tests {
         assert_eq!(result.len(), 64);
         
         // Test that the same inputs always produce the same output
-        let result1 = compute_final_hash(own_hash, &dep_hashes);
-        let result2 = compute_final_hash(own_hash, &dep_hashes);
+        let result1 = compute_final_hash(own_hash, &dep_hashes, None);
+        let result2 = compute_final_hash(own_hash, &dep_hashes, None);
         assert_eq!(result1, result2);
         
         // Test that different dependency order produces different results
         let dep_hashes_reordered: Vec<&str> = vec![dep_hash2, dep_hash1];
-        let result_reordered = compute_final_hash(own_hash, &dep_hashes_reordered);
+        let result_reordered = compute_final_hash(own_hash, &dep_hashes_reordered, None);
         assert_ne!(result, result_reordered);
     }
+
+    #[test]
+    fn test_compute_final_hash_salt() {
+        let dep_hashes = ["f6e5d4c3b2a1"];
+        let unsalted = compute_final_hash("a1b2c3d4e5f6", &dep_hashes, None);
+
+        // No salt keeps the plain digest of the inputs
+        let mut hasher = Sha256::new();
+        hasher.update(b"a1b2c3d4e5f6f6e5d4c3b2a1");
+        assert_eq!(unsalted, format!("{:x}", hasher.finalize()));
+
+        let v1 = compute_final_hash("a1b2c3d4e5f6", &dep_hashes, Some("toolchain-1"));
+        let v2 = compute_final_hash("a1b2c3d4e5f6", &dep_hashes, Some("toolchain-2"));
+        assert_ne!(v1, unsalted);
+        assert_ne!(v1, v2);
+        assert_eq!(v1, compute_final_hash("a1b2c3d4e5f6", &dep_hashes, Some("toolchain-1")));
+    }
+
+    #[test]
+    fn test_dependency_entry() {
+        // The same hash under another name is another input
+        let vendor_a = dependency_entry("path:vendor-a", "f6e5d4c3b2a1");
+        let vendor_b = dependency_entry("path:vendor-b", "f6e5d4c3b2a1");
+        assert_ne!(
+            compute_final_hash("a1b2c3d4e5f6", &[&vendor_a], None),
+            compute_final_hash("a1b2c3d4e5f6", &[&vendor_b], None)
+        );
+        // Identity and hash can't run into each other
+        assert_ne!(dependency_entry("app:ab", "c"), dependency_entry("app:a", "bc"));
+    }
 }
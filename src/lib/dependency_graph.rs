@@ -0,0 +1,389 @@
+use crate::cfg::{App, Config, Dependency};
+use crate::error::YethError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Serializable view of the dependency graph, including reverse edges
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    pub apps: BTreeMap<String, GraphNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphNode {
+    /// Absolute application directory
+    pub dir: PathBuf,
+    /// Applications this app depends on
+    pub app_dependencies: Vec<String>,
+    /// Files and directories this app depends on
+    pub path_dependencies: Vec<PathDependencyNode>,
+    /// Applications that directly depend on this app
+    pub dependents: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathDependencyNode {
+    /// Resolved absolute path
+    pub path: PathBuf,
+    /// Whether the path currently exists
+    pub exists: bool,
+}
+
+impl DependencyGraph {
+    pub fn from_apps(apps: &HashMap<String, App>) -> Self {
+        let mut nodes: BTreeMap<String, GraphNode> = apps
+            .iter()
+            .map(|(app_name, app)| {
+                let mut app_dependencies = Vec::new();
+                let mut path_dependencies = Vec::new();
+                for dep in &app.dependencies {
+                    match dep {
+                        Dependency::App(dep_name) => app_dependencies.push(dep_name.clone()),
+                        Dependency::Path(path) => path_dependencies.push(PathDependencyNode {
+                            path: absolute_path(path),
+                            exists: path.exists(),
+                        }),
+                    }
+                }
+
+                let node = GraphNode {
+                    dir: absolute_path(&app.dir),
+                    app_dependencies,
+                    path_dependencies,
+                    dependents: Vec::new(),
+                };
+                (app_name.clone(), node)
+            })
+            .collect();
+
+        for (app_name, app) in apps {
+            for dep in &app.dependencies {
+                if let Dependency::App(dep_name) = dep
+                    && let Some(node) = nodes.get_mut(dep_name)
+                {
+                    node.dependents.push(app_name.clone());
+                }
+            }
+        }
+        for node in nodes.values_mut() {
+            node.dependents.sort();
+        }
+
+        DependencyGraph { apps: nodes }
+    }
+
+    /// Render as a Graphviz digraph: one node per app, an edge from each app to
+    /// every app it depends on, sorted by name
+    pub fn to_dot(&self) -> String {
+        let quote = |name: &str| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = String::from("digraph yeth {\n");
+        for (app_name, node) in &self.apps {
+            dot.push_str(&format!("  {};\n", quote(app_name)));
+            let mut dependencies: Vec<&String> = node.app_dependencies.iter().collect();
+            dependencies.sort();
+            for dependency in dependencies {
+                dot.push_str(&format!("  {} -> {};\n", quote(app_name), quote(dependency)));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Styles wrapped around parts of a rendered tree, for terminals. The default is
+/// plain text without escape codes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeStyle {
+    /// Application names
+    pub app: anstyle::Style,
+    /// Path dependencies
+    pub path: anstyle::Style,
+}
+
+/// Render the dependency graph as text trees, one block of lines per top-level app.
+/// Without `root_app` every app is listed (sorted by name); with it only that app.
+/// App dependencies are expanded down to `max_depth` levels (1 when listing every app,
+/// unlimited for a single app); an app already expanded in the same tree is shown as
+/// `name (…)` instead, which also stops cycles. Path dependencies are always leaves,
+/// shown with `Config::display_path`.
+pub fn render_tree(
+    config: &Config,
+    apps: &HashMap<String, App>,
+    root_app: Option<&str>,
+    max_depth: Option<usize>,
+) -> Result<Vec<Vec<String>>, YethError> {
+    render_tree_styled(config, apps, root_app, max_depth, TreeStyle::default())
+}
+
+/// `render_tree` with app names and path dependencies wrapped in `style`
+pub fn render_tree_styled(
+    config: &Config,
+    apps: &HashMap<String, App>,
+    root_app: Option<&str>,
+    max_depth: Option<usize>,
+    style: TreeStyle,
+) -> Result<Vec<Vec<String>>, YethError> {
+    let roots: Vec<&String> = match root_app {
+        Some(app_name) => {
+            let (name, _) = apps
+                .get_key_value(app_name)
+                .ok_or_else(|| crate::suggest::app_not_found(app_name, apps))?;
+            vec![name]
+        }
+        None => {
+            let mut names: Vec<&String> = apps.keys().collect();
+            names.sort();
+            names
+        }
+    };
+    let max_depth = max_depth.unwrap_or(if root_app.is_some() { usize::MAX } else { 1 });
+    let tree = Tree { config, apps, style };
+
+    Ok(roots
+        .into_iter()
+        .map(|app_name| {
+            let mut lines = vec![paint(style.app, app_name)];
+            if apps[app_name].dependencies.is_empty() {
+                lines.push("  └─ (no dependencies)".to_string());
+            }
+            let mut expanded = HashSet::from([app_name.as_str()]);
+            tree.render_dependencies(app_name, "  ", max_depth, &mut expanded, &mut lines);
+            lines
+        })
+        .collect())
+}
+
+/// What every level of a rendered tree needs
+struct Tree<'a> {
+    config: &'a Config,
+    apps: &'a HashMap<String, App>,
+    style: TreeStyle,
+}
+
+impl<'a> Tree<'a> {
+    fn render_dependencies(
+        &self,
+        app_name: &str,
+        indent: &str,
+        depth_left: usize,
+        expanded: &mut HashSet<&'a str>,
+        lines: &mut Vec<String>,
+    ) {
+        let Some(app) = self.apps.get(app_name) else {
+            return;
+        };
+        if depth_left == 0 {
+            return;
+        }
+
+        for (i, dep) in app.dependencies.iter().enumerate() {
+            let is_last = i == app.dependencies.len() - 1;
+            let prefix = if is_last { "└─" } else { "├─" };
+            match dep {
+                Dependency::App(dep_name) => {
+                    let Some((dep_name, _)) = self.apps.get_key_value(dep_name) else {
+                        lines.push(format!("{}{} {} (app, not found)", indent, prefix, paint(self.style.app, dep_name)));
+                        continue;
+                    };
+                    if !expanded.insert(dep_name.as_str()) {
+                        lines.push(format!("{}{} {} (…)", indent, prefix, paint(self.style.app, dep_name)));
+                        continue;
+                    }
+                    lines.push(format!("{}{} {} (app)", indent, prefix, paint(self.style.app, dep_name)));
+                    let child_indent = format!("{}{}", indent, if is_last { "   " } else { "│  " });
+                    self.render_dependencies(dep_name, &child_indent, depth_left - 1, expanded, lines);
+                }
+                Dependency::Path(path) => {
+                    let kind = if path.is_file() { "file" } else { "dir" };
+                    let shown = self.config.display_path(path).display().to_string();
+                    lines.push(format!("{}{} {} ({})", indent, prefix, paint(self.style.path, &shown), kind));
+                }
+            }
+        }
+    }
+}
+
+/// `text` wrapped in the escape codes of `style`, as is for the plain style
+fn paint(style: anstyle::Style, text: &str) -> String {
+    format!("{}{}{:#}", style, text, style)
+}
+
+/// Resolve a path to an absolute one, canonical when it exists
+fn absolute_path(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_dependency_graph() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let shared_dir = root.join("shared");
+        fs::create_dir_all(&shared_dir).unwrap();
+
+        let mut apps = HashMap::new();
+        apps.insert(
+            "app1".to_string(),
+            App {
+                name: "app1".to_string(),
+                dir: root.join("app1"),
+                dependencies: vec![Dependency::Path(shared_dir.clone())],
+                exclude_patterns: vec![],
+                ..Default::default()
+            },
+        );
+        apps.insert(
+            "app2".to_string(),
+            App {
+                name: "app2".to_string(),
+                dir: root.join("app2"),
+                dependencies: vec![
+                    Dependency::App("app1".to_string()),
+                    Dependency::Path(root.join("missing")),
+                ],
+                exclude_patterns: vec![],
+                ..Default::default()
+            },
+        );
+
+        let graph = DependencyGraph::from_apps(&apps);
+
+        let app1 = graph.apps.get("app1").unwrap();
+        assert!(app1.app_dependencies.is_empty());
+        assert_eq!(
+            app1.path_dependencies,
+            vec![PathDependencyNode { path: shared_dir, exists: true }]
+        );
+        assert_eq!(app1.dependents, vec!["app2"]);
+
+        let app2 = graph.apps.get("app2").unwrap();
+        assert_eq!(app2.app_dependencies, vec!["app1"]);
+        assert!(!app2.path_dependencies[0].exists);
+        assert!(app2.dependents.is_empty());
+
+        // Round trip through JSON
+        let json = serde_json::to_string(&graph).unwrap();
+        let restored: DependencyGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(graph, restored);
+        // Graphviz: apps and app edges only
+        assert_eq!(graph.to_dot(), "digraph yeth {\n  \"app1\";\n  \"app2\";\n  \"app2\" -> \"app1\";\n}\n");
+    }
+
+    fn fixture() -> HashMap<String, App> {
+        let app = |name: &str, dependencies: Vec<Dependency>| {
+            let app = App {
+                name: name.to_string(),
+                dir: PathBuf::from("/repo").join(name),
+                dependencies,
+                ..Default::default()
+            };
+            (name.to_string(), app)
+        };
+        let on = |name: &str| Dependency::App(name.to_string());
+        HashMap::from([
+            app("web", vec![on("api"), on("common")]),
+            app("api", vec![on("common"), Dependency::Path(PathBuf::from("/repo/shared"))]),
+            app("common", vec![on("cycle")]),
+            app("cycle", vec![on("common")]),
+        ])
+    }
+
+    fn repo_config() -> Config {
+        Config::builder().root(PathBuf::from("/repo")).build().unwrap()
+    }
+
+    #[test]
+    fn test_render_tree_single_app() {
+        let apps = fixture();
+        let config = repo_config();
+
+        let tree = render_tree(&config, &apps, Some("web"), None).unwrap();
+        assert_eq!(
+            tree,
+            vec![vec![
+                "web",
+                "  ├─ api (app)",
+                "  │  ├─ common (app)",
+                "  │  │  └─ cycle (app)",
+                "  │  │     └─ common (…)",
+                "  │  └─ shared (dir)",
+                "  └─ common (…)",
+            ]]
+        );
+
+        // Paths relative to the root unless absolute paths are asked for
+        let absolute = Config::builder()
+            .root(PathBuf::from("/repo"))
+            .absolute_paths(true)
+            .build()
+            .unwrap();
+        let tree = render_tree(&absolute, &apps, Some("api"), None).unwrap();
+        assert_eq!(tree[0].last().unwrap(), "  └─ /repo/shared (dir)");
+
+        let tree = render_tree(&config, &apps, Some("web"), Some(1)).unwrap();
+        assert_eq!(tree, vec![vec!["web", "  ├─ api (app)", "  └─ common (app)"]]);
+
+        assert!(matches!(
+            render_tree(&config, &apps, Some("wbe"), None),
+            Err(YethError::AppNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_render_tree_styled() {
+        let apps = fixture();
+        let config = repo_config();
+        let style = TreeStyle {
+            app: anstyle::Style::new().bold(),
+            path: anstyle::AnsiColor::Cyan.on_default(),
+        };
+
+        let tree = render_tree_styled(&config, &apps, Some("api"), Some(1), style).unwrap();
+        assert_eq!(
+            tree,
+            vec![vec![
+                "\x1b[1mapi\x1b[0m",
+                "  ├─ \x1b[1mcommon\x1b[0m (app)",
+                "  └─ \x1b[36mshared\x1b[0m (dir)",
+            ]]
+        );
+
+        // The default style adds nothing
+        let plain = render_tree_styled(&config, &apps, Some("web"), None, TreeStyle::default()).unwrap();
+        assert_eq!(plain, render_tree(&config, &apps, Some("web"), None).unwrap());
+    }
+
+    #[test]
+    fn test_render_tree_all_apps() {
+        let config = repo_config();
+        let mut apps = fixture();
+        apps.insert(
+            "lonely".to_string(),
+            App {
+                name: "lonely".to_string(),
+                ..Default::default()
+            },
+        );
+
+        // Direct dependencies of every app, sorted by name
+        let tree = render_tree(&config, &apps, None, None).unwrap();
+        assert_eq!(
+            tree,
+            vec![
+                vec!["api", "  ├─ common (app)", "  └─ shared (dir)"],
+                vec!["common", "  └─ cycle (app)"],
+                vec!["cycle", "  └─ common (app)"],
+                vec!["lonely", "  └─ (no dependencies)"],
+                vec!["web", "  ├─ api (app)", "  └─ common (app)"],
+            ]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use crate::error::YethError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// How the hashes of two runs differ, each set sorted by app name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HashDiff {
+    /// Apps only in the second run
+    pub added: BTreeSet<String>,
+    /// Apps only in the first run
+    pub removed: BTreeSet<String>,
+    /// Apps in both runs with different hashes
+    pub changed: BTreeSet<String>,
+}
+
+impl HashDiff {
+    /// Whether both runs have the same apps with the same hashes
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the app → hash maps of two runs
+pub fn diff_hashes(before: &HashMap<String, String>, after: &HashMap<String, String>) -> HashDiff {
+    let mut diff = HashDiff::default();
+    for (app, hash) in after {
+        match before.get(app) {
+            None => {
+                diff.added.insert(app.clone());
+            }
+            Some(old_hash) if old_hash != hash => {
+                diff.changed.insert(app.clone());
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = before.keys().filter(|app| !after.contains_key(*app)).cloned().collect();
+    diff
+}
+
+/// Compare current hashes with saved ones, which may be shortened (see `compare_manifests`)
+pub fn diff_with_saved(saved: &HashMap<String, String>, current: &HashMap<String, String>) -> HashDiff {
+    let mut diff = HashDiff::default();
+    for (app, status) in compare_manifests(saved, current).apps {
+        match status {
+            ManifestStatus::New => diff.added.insert(app),
+            ManifestStatus::Removed => diff.removed.insert(app),
+            ManifestStatus::Changed => diff.changed.insert(app),
+            ManifestStatus::Unchanged => continue,
+        };
+    }
+    diff
+}
+
+/// How one app compares between a baseline manifest and the current hashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestStatus {
+    Unchanged,
+    Changed,
+    /// Only in the current hashes
+    New,
+    /// Only in the baseline
+    Removed,
+}
+
+impl fmt::Display for ManifestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ManifestStatus::Unchanged => "unchanged",
+            ManifestStatus::Changed => "changed",
+            ManifestStatus::New => "new",
+            ManifestStatus::Removed => "removed",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// The status of every app in either manifest, sorted by app name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ManifestDiff {
+    pub apps: BTreeMap<String, ManifestStatus>,
+}
+
+impl ManifestDiff {
+    /// Whether any app is other than unchanged
+    pub fn has_changes(&self) -> bool {
+        self.apps.values().any(|status| *status != ManifestStatus::Unchanged)
+    }
+}
+
+/// Compare a baseline manifest with the current hashes. A baseline hash is compared
+/// as a prefix of the current one, so manifests saved with short hashes still match.
+pub fn compare_manifests(old: &HashMap<String, String>, new: &HashMap<String, String>) -> ManifestDiff {
+    let mut apps: BTreeMap<String, ManifestStatus> = old
+        .keys()
+        .filter(|app| !new.contains_key(*app))
+        .map(|app| (app.clone(), ManifestStatus::Removed))
+        .collect();
+    for (app, hash) in new {
+        let status = match old.get(app) {
+            None => ManifestStatus::New,
+            Some(old_hash) if !old_hash.is_empty() && hash.starts_with(old_hash.as_str()) => ManifestStatus::Unchanged,
+            Some(_) => ManifestStatus::Changed,
+        };
+        apps.insert(app.clone(), status);
+    }
+    ManifestDiff { apps }
+}
+
+/// A saved hash: either the hash itself or an object with a `hash` field, as
+/// printed by `--format json`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedHash {
+    Hash(String),
+    Entry { hash: String },
+}
+
+/// Read hashes saved as a JSON object of app → hash, or app → `{ "hash": ... }`
+/// as `yeth --format json` prints them. A `.toml` or `.yaml` file holds the same
+/// as a table or mapping.
+pub fn load_hashes(path: &Path) -> Result<HashMap<String, String>, YethError> {
+    let content = fs::read_to_string(path)?;
+    let saved: HashMap<String, SavedHash> = match ManifestFormat::of(path) {
+        ManifestFormat::Json => serde_json::from_str(&content)?,
+        ManifestFormat::Toml => toml::from_str(&content).map_err(|source| YethError::TomlParseError {
+            path: path.to_path_buf(),
+            source,
+        })?,
+        ManifestFormat::Yaml => serde_yaml::from_str(&content).map_err(|source| YethError::YamlParseError {
+            path: path.to_path_buf(),
+            source,
+        })?,
+    };
+    Ok(saved
+        .into_iter()
+        .map(|(app, saved)| match saved {
+            SavedHash::Hash(hash) | SavedHash::Entry { hash } => (app, hash),
+        })
+        .collect())
+}
+
+/// Write hashes as one app → hash object sorted by app, as TOML or YAML for a `.toml`
+/// or `.yaml` path and JSON otherwise, so `load_hashes` reads them back unchanged
+pub fn save_hashes(path: &Path, hashes: &HashMap<String, String>) -> Result<(), YethError> {
+    let sorted: BTreeMap<&String, &String> = hashes.iter().collect();
+    let content = match ManifestFormat::of(path) {
+        ManifestFormat::Json => serde_json::to_string_pretty(&sorted)? + "\n",
+        ManifestFormat::Toml => toml::to_string(&sorted).expect("string maps always serialize"),
+        ManifestFormat::Yaml => serde_yaml::to_string(&sorted).expect("string maps always serialize"),
+    };
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// File format of saved hashes, told by the extension
+enum ManifestFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ManifestFormat {
+    fn of(path: &Path) -> Self {
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("toml") => ManifestFormat::Toml,
+            Some("yaml" | "yml") => ManifestFormat::Yaml,
+            _ => ManifestFormat::Json,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn hashes(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries.iter().map(|(app, hash)| (app.to_string(), hash.to_string())).collect()
+    }
+
+    fn set(apps: &[&str]) -> BTreeSet<String> {
+        apps.iter().map(|app| app.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff_hashes() {
+        let before = hashes(&[("api", "a1"), ("web", "w1"), ("old", "o1"), ("common", "c1")]);
+        let after = hashes(&[("api", "a2"), ("web", "w1"), ("new", "n1"), ("common", "c2")]);
+
+        let diff = diff_hashes(&before, &after);
+        assert_eq!(diff.added, set(&["new"]));
+        assert_eq!(diff.removed, set(&["old"]));
+        assert_eq!(diff.changed, set(&["api", "common"]));
+        assert!(!diff.is_empty());
+
+        assert!(diff_hashes(&before, &before).is_empty());
+        assert_eq!(diff_hashes(&HashMap::new(), &after).added.len(), 4);
+        assert_eq!(diff_hashes(&before, &HashMap::new()).removed.len(), 4);
+    }
+
+    #[test]
+    fn test_load_hashes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("hashes.json");
+
+        fs::write(&path, r#"{"api": "a1", "web": "w1"}"#).unwrap();
+        assert_eq!(load_hashes(&path).unwrap(), hashes(&[("api", "a1"), ("web", "w1")]));
+
+        fs::write(&path, r#"{"api": {"hash": "a1", "dir": "services/api", "meta": {}}}"#).unwrap();
+        assert_eq!(load_hashes(&path).unwrap(), hashes(&[("api", "a1")]));
+
+        fs::write(&path, r#"["api"]"#).unwrap();
+        assert!(matches!(load_hashes(&path), Err(YethError::JsonError(_))));
+    }
+
+    #[test]
+    fn test_compare_manifests() {
+        let old = hashes(&[("api", "a1b2"), ("web", "w1w2w3"), ("old", "o1"), ("common", "c1c2"), ("blank", "")]);
+        let new = hashes(&[("api", "a1b2c3d4"), ("web", "w1w2w4"), ("new", "n1"), ("common", "c1c3"), ("blank", "b1")]);
+
+        let diff = compare_manifests(&old, &new);
+        let expected = [
+            ("api", ManifestStatus::Unchanged),
+            ("blank", ManifestStatus::Changed),
+            ("common", ManifestStatus::Changed),
+            ("new", ManifestStatus::New),
+            ("old", ManifestStatus::Removed),
+            ("web", ManifestStatus::Changed),
+        ];
+        assert_eq!(diff.apps, expected.into_iter().map(|(app, status)| (app.to_string(), status)).collect());
+        assert!(diff.has_changes());
+        assert_eq!(
+            serde_json::to_value(&diff).unwrap(),
+            serde_json::json!({"api": "unchanged", "blank": "changed", "common": "changed", "new": "new", "old": "removed", "web": "changed"})
+        );
+
+        // A longer baseline hash is not a prefix of a shorter current one
+        assert!(compare_manifests(&hashes(&[("api", "a1b2c3")]), &hashes(&[("api", "a1b2")])).has_changes());
+        assert!(!compare_manifests(&new, &new).has_changes());
+        assert!(!ManifestDiff::default().has_changes());
+    }
+
+    #[test]
+    fn test_save_hashes_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let saved = hashes(&[("api", "a1b2"), ("web.v2", "w1"), ("common", "c1")]);
+        for file in ["hashes.json", "hashes.toml", "hashes.yaml"] {
+            let path = temp_dir.path().join(file);
+            save_hashes(&path, &saved).unwrap();
+            let loaded = load_hashes(&path).unwrap();
+            assert_eq!(loaded, saved);
+            assert!(diff_hashes(&loaded, &saved).is_empty());
+        }
+        let toml = fs::read_to_string(temp_dir.path().join("hashes.toml")).unwrap();
+        assert_eq!(toml, "api = \"a1b2\"\ncommon = \"c1\"\n\"web.v2\" = \"w1\"\n");
+
+        // Shortened saved hashes compare with the start of the current ones
+        let current = hashes(&[("api", "a1b2c3"), ("web.v2", "w2"), ("new", "n1")]);
+        let diff = diff_with_saved(&hashes(&[("api", "a1b2"), ("web.v2", "w1"), ("common", "c1")]), &current);
+        assert_eq!(diff.changed, set(&["web.v2"]));
+        assert_eq!(diff.added, set(&["new"]));
+        assert_eq!(diff.removed, set(&["common"]));
+    }
+}
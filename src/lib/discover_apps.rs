@@ -1,66 +1,402 @@
-use crate::cfg::{App, AppConfig, Config, Dependency, ExcludePattern, CONFIG_FILE};
+use crate::cfg::{
+    is_exclude_path, is_path_like, join_config_path, resolved_path, App, AppConfig, AppDependencySpec, Config, Dependency,
+    DependencySpec, ExcludePattern, PathDependencySpec, APP_KEYS, CONFIG_FILE, CONFIG_KEYS,
+};
 use crate::error::YethError;
+use crate::expand_env::expand_env;
+use crate::glob_match::is_glob;
+use crate::suggest::closest_matches;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
 use std::{collections::HashMap, fs};
 use walkdir::WalkDir;
 
-/// Discover all applications in the configured root directory
+
+/// Discover all applications in the configured root directory.
+/// The tree is walked once, then the configs found are read and parsed in parallel
+/// on at most `config.jobs` threads.
 pub fn discover_apps(config: &Config) -> Result<HashMap<String, App>, YethError> {
-    WalkDir::new(&config.root)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_name() == CONFIG_FILE)
-        .map(|entry| {
-            let app_dir = entry
-                .path()
-                .parent()
-                .ok_or_else(|| {
-                    YethError::NoParentDir(entry.path().to_string_lossy().to_string())
-                })?
-                .to_path_buf();
-
-            let app_name = app_dir
-                .file_name()
-                .ok_or_else(|| YethError::NoFileName(app_dir.to_string_lossy().to_string()))?
-                .to_string_lossy()
-                .into_owned();
-
-            let app_config_content = fs::read_to_string(entry.path())?;
-            let app_config: AppConfig = toml::from_str(&app_config_content)?;
-
-            let dependencies = app_config
-                .app
-                .dependencies
-                .iter()
-                .map(|dep_string| Dependency::parse(dep_string, &app_dir))
-                .collect::<Vec<Dependency>>();
+    let config_paths = find_config_files(&config.root)?;
+
+    let load_all = || -> Vec<Result<(String, App), YethError>> {
+        config_paths
+            .par_iter()
+            .map(|config_path| load_app(config_path, config))
+            .collect()
+    };
+    let loaded = match rayon::ThreadPoolBuilder::new().num_threads(config.jobs).build() {
+        Ok(pool) => pool.install(load_all),
+        // Without threads of our own, parse on the caller's thread
+        Err(_) => config_paths.iter().map(|config_path| load_app(config_path, config)).collect(),
+    };
+
+    // Results keep the sorted traversal order, so the reported error and the
+    // app kept on a name clash don't depend on thread scheduling
+    let mut apps: HashMap<String, App> = HashMap::with_capacity(loaded.len());
+    for result in loaded {
+        let (app_name, app) = match result {
+            Ok(loaded) => loaded,
+            Err(error @ (YethError::ConfigIsDirectory(_) | YethError::DanglingConfigSymlink { .. }))
+                if config.keep_going =>
+            {
+                config.warn(format_args!("skipping application: {}", error.with_display_paths(config)));
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        if let Some(existing) = apps.get(&app_name) {
+            return Err(YethError::DuplicateAppName {
+                name: app_name,
+                first: existing.dir.clone(),
+                second: app.dir,
+            });
+        }
+        apps.insert(app_name, app);
+    }
+
+    if config.promote_path_deps {
+        promote_path_dependencies(&mut apps);
+    }
+    check_path_dependencies_in_apps(&apps, config)?;
+    Ok(apps)
+}
+
+/// Warn about path dependencies inside another app's directory, where an app
+/// dependency is usually meant; an error with `config.strict`. Apps enclosing the
+/// declaring app (such as an app at the root) don't count, and a path inside the
+/// declaring app itself is rejected later by the topological sort.
+fn check_path_dependencies_in_apps(apps: &HashMap<String, App>, config: &Config) -> Result<(), YethError> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let app_dirs: Vec<(&String, PathBuf)> = apps
+        .iter()
+        .map(|(app_name, app)| (app_name, canonical(&app.dir)))
+        .collect();
 
-            let exclude_patterns = app_config
-                .app
-                .exclude
+    let mut app_names: Vec<&String> = apps.keys().collect();
+    app_names.sort();
+    for app_name in app_names {
+        let app_dir = canonical(&apps[app_name].dir);
+        for dep in &apps[app_name].dependencies {
+            let Dependency::Path(path) = dep else {
+                continue;
+            };
+            let target = canonical(path);
+            if target.starts_with(&app_dir) {
+                continue;
+            }
+            let owner = app_dirs
                 .iter()
-                .map(|pattern| {
-                    if pattern.contains("/") || pattern.starts_with(".") {
-                        let absolute_path = app_dir.join(pattern);
-                        ExcludePattern::AbsolutePath(
-                            absolute_path.canonicalize().unwrap_or(absolute_path),
-                        )
-                    } else {
-                        ExcludePattern::Name(pattern.clone())
-                    }
-                })
-                .collect::<Vec<ExcludePattern>>();
-
-            Ok((
-                app_name.clone(),
-                App {
-                    name: app_name,
-                    dir: app_dir,
-                    dependencies,
-                    exclude_patterns,
-                },
-            ))
+                .filter(|(_, dir)| target.starts_with(dir) && !app_dir.starts_with(dir))
+                .max_by_key(|(_, dir)| dir.components().count());
+            if let Some((owner, _)) = owner {
+                let error = YethError::PathDependencyInsideApp {
+                    path: path.clone(),
+                    app: app_name.clone(),
+                    owner: owner.to_string(),
+                };
+                if config.strict {
+                    return Err(error);
+                }
+                config.warn(error.with_display_paths(config));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replace path dependencies on another app's directory with a dependency on that app,
+/// so its own dependencies are taken into account too
+fn promote_path_dependencies(apps: &mut HashMap<String, App>) {
+    let app_dirs: HashMap<PathBuf, String> = apps
+        .iter()
+        .map(|(app_name, app)| {
+            let dir = app.dir.canonicalize().unwrap_or_else(|_| app.dir.clone());
+            (dir, app_name.clone())
         })
-        .collect()
+        .collect();
+
+    for app in apps.values_mut() {
+        for dep in &mut app.dependencies {
+            if let Dependency::Path(path) = dep
+                && let Ok(canonical) = path.canonicalize()
+                && let Some(dep_name) = app_dirs.get(&canonical)
+                && *dep_name != app.name
+            {
+                *dep = Dependency::App(dep_name.clone());
+            }
+        }
+    }
+}
+
+/// Collect every yeth.toml below the root, sorted by path. A directory that can't be
+/// read is an error, since apps inside it would silently go missing.
+fn find_config_files(root: &Path) -> Result<Vec<PathBuf>, YethError> {
+    let mut config_paths = Vec::new();
+    for entry in WalkDir::new(root) {
+        let entry = entry.map_err(|error| YethError::DiscoveryWalkError {
+            path: error.path().unwrap_or(root).to_path_buf(),
+            source: error.into(),
+        })?;
+        if entry.file_name() == CONFIG_FILE {
+            config_paths.push(entry.into_path());
+        }
+    }
+    config_paths.sort();
+    Ok(config_paths)
+}
+
+/// Reject a yeth.toml that is a directory or a dangling symlink, which can't be read
+fn check_config_file(config_path: &Path) -> Result<(), YethError> {
+    match fs::metadata(config_path) {
+        Ok(metadata) if metadata.is_dir() => Err(YethError::ConfigIsDirectory(config_path.to_path_buf())),
+        Err(error)
+            if error.kind() == std::io::ErrorKind::NotFound
+                && fs::symlink_metadata(config_path).is_ok_and(|m| m.file_type().is_symlink()) =>
+        {
+            Err(YethError::DanglingConfigSymlink {
+                path: config_path.to_path_buf(),
+                target: fs::read_link(config_path).unwrap_or_default(),
+            })
+        }
+        // Anything else is reported when the file is read
+        _ => Ok(()),
+    }
+}
+
+/// Name an app after its directory
+fn app_name_for_dir(app_dir: &Path) -> Result<String, YethError> {
+    let file_name = app_dir
+        .file_name()
+        .ok_or_else(|| YethError::NoFileName(app_dir.to_string_lossy().to_string()))?;
+    // App names must round-trip exactly, so invalid UTF-8 is an error rather than lossy
+    Ok(file_name
+        .to_str()
+        .ok_or_else(|| YethError::NonUtf8AppName(app_dir.to_path_buf()))?
+        .to_string())
+}
+
+/// The `name` set in yeth.toml, or the directory's name. The root directory's own
+/// name depends on where the repository is checked out, so an app there must set one.
+fn app_name(name: Option<String>, app_dir: &Path, config_path: &Path, config: &Config) -> Result<String, YethError> {
+    match name {
+        Some(name) if name.trim().is_empty() || is_path_like(&name) => Err(YethError::InvalidAppName {
+            name,
+            path: config_path.to_path_buf(),
+        }),
+        Some(name) => Ok(name),
+        None if app_dir == config.root => Err(YethError::RootAppWithoutName(config_path.to_path_buf())),
+        None => app_name_for_dir(app_dir),
+    }
+}
+
+/// Read and parse one yeth.toml into an application
+fn load_app(config_path: &Path, config: &Config) -> Result<(String, App), YethError> {
+    let app_dir = config_path
+        .parent()
+        .ok_or_else(|| YethError::NoParentDir(config_path.to_string_lossy().to_string()))?
+        .to_path_buf();
+
+    check_config_file(config_path)?;
+    let app_config_content =
+        fs::read_to_string(config_path).map_err(|source| YethError::ConfigReadError {
+            path: config_path.to_path_buf(),
+            source,
+        })?;
+    let mut app_config = parse_app_config(config_path, &app_config_content, config)?;
+    let app_name = app_name(app_config.app.name.take(), &app_dir, config_path, config)?;
+
+    let expand = |value: &String| -> Result<String, YethError> {
+        if config.expand_env {
+            expand_env(value)
+        } else {
+            Ok(value.clone())
+        }
+    };
+
+    let mut dependencies = Vec::with_capacity(app_config.app.dependencies.len());
+    let mut path_dependency_excludes = HashMap::new();
+    for spec in &app_config.app.dependencies {
+        match spec {
+            DependencySpec::Plain(dep_string) => {
+                let dep_string = expand(dep_string)?;
+                dependencies.push(match Dependency::parse(&dep_string, &app_dir) {
+                    Dependency::Path(path) => Dependency::Path(resolve_path_dependency(&dep_string, path, &app_name, config)?),
+                    dependency => dependency,
+                });
+            }
+            DependencySpec::App(AppDependencySpec { app }) => {
+                dependencies.push(Dependency::App(app.clone()));
+            }
+            DependencySpec::Path(PathDependencySpec { path, exclude }) => {
+                // Relative patterns apply inside the dependency, like app excludes in the app
+                let raw = expand(path)?;
+                let path = resolve_path_dependency(&raw, join_config_path(&app_dir, &raw), &app_name, config)?;
+                let exclude = exclude
+                    .iter()
+                    .map(|pattern| Ok(parse_exclude_pattern(expand(pattern)?, &path)))
+                    .collect::<Result<Vec<ExcludePattern>, YethError>>()?;
+                if !exclude.is_empty() {
+                    path_dependency_excludes.insert(path.clone(), exclude);
+                }
+                dependencies.push(Dependency::Path(path));
+            }
+        }
+    }
+
+    let exclude_patterns = app_config
+        .app
+        .exclude
+        .iter()
+        .map(|pattern| Ok(parse_exclude_pattern(expand(pattern)?, &app_dir)))
+        .collect::<Result<Vec<ExcludePattern>, YethError>>()?;
+
+    // An empty list would silently hash nothing, which is never what was meant
+    let include_patterns = match app_config.app.include {
+        Some(include) if include.is_empty() => {
+            return Err(YethError::EmptyInclude(config_path.to_path_buf()));
+        }
+        include => include,
+    };
+    let hash_dir = match app_config.app.hash_dir {
+        Some(hash_dir) => Some(resolve_hash_dir(&expand(&hash_dir)?, &app_dir, &app_name)?),
+        None => None,
+    };
+    let extensions = match app_config.app.extensions {
+        Some(extensions) if extensions.is_empty() => {
+            return Err(YethError::EmptyExtensions(config_path.to_path_buf()));
+        }
+        extensions => extensions.map(|extensions| {
+            extensions
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect()
+        }),
+    };
+
+    Ok((
+        app_name.clone(),
+        App {
+            name: app_name,
+            dir: app_dir,
+            dependencies,
+            exclude_patterns,
+            hash_dir,
+            path_dependency_excludes,
+            include_patterns,
+            extensions,
+            normalize_eol: app_config.app.normalize_eol,
+            always_changed: app_config.app.always_changed,
+            meta: app_config.app.meta,
+            tags: app_config.app.tags,
+        },
+    ))
+}
+
+/// The subdirectory named by `hash_dir`, which must be a directory inside the app
+fn resolve_hash_dir(hash_dir: &str, app_dir: &Path, app_name: &str) -> Result<PathBuf, YethError> {
+    let path = join_config_path(app_dir, hash_dir);
+    if !path.is_dir() {
+        return Err(YethError::HashDirNotFound {
+            path,
+            app: app_name.to_string(),
+        });
+    }
+    if !resolved_path(&path).starts_with(resolved_path(app_dir)) {
+        return Err(YethError::HashDirOutsideApp {
+            hash_dir: hash_dir.to_string(),
+            app: app_name.to_string(),
+        });
+    }
+    Ok(path)
+}
+
+/// A path dependency outside the root is kept canonical, so it reads the same
+/// however it was reached; one inside the root is kept as written
+fn resolve_path_dependency(raw: &str, path: PathBuf, app_name: &str, config: &Config) -> Result<PathBuf, YethError> {
+    if !config.is_external(&path) {
+        return Ok(path);
+    }
+    let resolved = resolved_path(&path);
+    if config.deny_external_paths {
+        return Err(YethError::ExternalPathDependency {
+            raw: raw.to_string(),
+            resolved,
+            app: app_name.to_string(),
+        });
+    }
+    if fs::metadata(&resolved).is_err() {
+        return Err(YethError::ExternalPathDependencyNotFound {
+            raw: raw.to_string(),
+            resolved,
+            app: app_name.to_string(),
+        });
+    }
+    Ok(resolved)
+}
+
+/// Turn an exclude entry into a pattern; paths are relative to `base`
+fn parse_exclude_pattern(pattern: String, base: &Path) -> ExcludePattern {
+    if is_exclude_path(&pattern) {
+        let absolute_path = join_config_path(base, &pattern);
+        ExcludePattern::AbsolutePath(absolute_path.canonicalize().unwrap_or(absolute_path))
+    } else if is_glob(&pattern) {
+        ExcludePattern::Glob(pattern)
+    } else {
+        ExcludePattern::Name(pattern)
+    }
+}
+
+/// Parse a yeth.toml, rejecting keys outside the schema (or warning about them when lenient)
+fn parse_app_config(path: &Path, content: &str, config: &Config) -> Result<AppConfig, YethError> {
+    let parse_error = |source| YethError::TomlParseError {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut table: toml::Table = toml::from_str(content).map_err(parse_error)?;
+
+    let unknown_top: Vec<String> = table
+        .keys()
+        .filter(|key| !CONFIG_KEYS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    for key in unknown_top {
+        report_unknown_key(path, &key, &key, CONFIG_KEYS, config)?;
+        table.remove(&key);
+    }
+
+    if let Some(app) = table.get_mut("app").and_then(|app| app.as_table_mut()) {
+        let unknown_app: Vec<String> = app
+            .keys()
+            .filter(|key| !APP_KEYS.contains(&key.as_str()))
+            .cloned()
+            .collect();
+        for key in unknown_app {
+            report_unknown_key(path, &format!("app.{}", key), &key, APP_KEYS, config)?;
+            app.remove(&key);
+        }
+    }
+
+    toml::Value::Table(table).try_into().map_err(parse_error)
+}
+
+fn report_unknown_key(
+    path: &Path,
+    full_key: &str,
+    key: &str,
+    known_keys: &[&str],
+    config: &Config,
+) -> Result<(), YethError> {
+    let error = YethError::UnknownConfigKey {
+        path: path.to_path_buf(),
+        key: full_key.to_string(),
+        suggestion: closest_matches(key, known_keys.iter().copied(), 1).pop(),
+    };
+    if config.lenient {
+        config.warn(error);
+        Ok(())
+    } else {
+        Err(error)
+    }
 }
 
 #[cfg(test)]
@@ -107,7 +443,7 @@ exclude = []
 
         // Create a shared directory for path dependency
         let shared_dir = root.join("shared");
-        fs::create_dir_all(&shared_dir.join("lib")).unwrap();
+        fs::create_dir_all(shared_dir.join("lib")).unwrap();
 
         // Create Config with our temporary directory as root
         let config = Config::builder().root(root.to_path_buf()).build().unwrap();
@@ -185,6 +521,510 @@ exclude = []
         // Test discover_apps with invalid config
         let result = discover_apps(&config);
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), YethError::TomlParseError(_)));
+        let error = result.unwrap_err();
+        assert!(matches!(&error, YethError::TomlParseError { path, .. } if path == &app1_config));
+        assert!(error.to_string().contains(&app1_config.display().to_string()));
+    }
+
+    #[test]
+    fn test_discover_apps_with_unknown_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let app1_dir = root.join("app1");
+        fs::create_dir_all(&app1_dir).unwrap();
+        fs::write(app1_dir.join("yeth.toml"), r#"
+[app]
+dependencies = []
+exlude = ["node_modules"]
+"#).unwrap();
+
+        // Strict by default: the typo is reported with the file and a suggestion
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let error = discover_apps(&config).unwrap_err();
+        match &error {
+            YethError::UnknownConfigKey { path, key, suggestion } => {
+                assert_eq!(path, &app1_dir.join("yeth.toml"));
+                assert_eq!(key, "app.exlude");
+                assert_eq!(suggestion.as_deref(), Some("exclude"));
+            }
+            other => panic!("Expected UnknownConfigKey, got {:?}", other),
+        }
+        assert!(error.to_string().contains("did you mean 'exclude'?"));
+
+        // Lenient mode ignores the key
+        let config = Config::builder().root(root.to_path_buf()).lenient(true).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        assert_eq!(apps.get("app1").unwrap().exclude_patterns.len(), 0);
+    }
+
+    #[test]
+    fn test_discover_apps_minimal_configs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // Empty [app] table, missing dependencies key, and a completely empty file
+        let configs = [
+            ("empty_table", "[app]\n"),
+            ("no_dependencies", "[app]\nexclude = [\"dist\"]\n"),
+            ("empty_file", ""),
+        ];
+        for (name, content) in configs {
+            let app_dir = root.join(name);
+            fs::create_dir_all(&app_dir).unwrap();
+            fs::write(app_dir.join("yeth.toml"), content).unwrap();
+        }
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+
+        assert_eq!(apps.len(), 3);
+        for (name, _) in configs {
+            assert!(apps.get(name).unwrap().dependencies.is_empty());
+        }
+        assert!(apps.get("empty_table").unwrap().exclude_patterns.is_empty());
+        assert_eq!(apps.get("no_dependencies").unwrap().exclude_patterns.len(), 1);
+        assert!(apps.get("empty_file").unwrap().exclude_patterns.is_empty());
+    }
+
+    #[test]
+    fn test_discover_apps_with_backslash_path_dependency() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let shared_lib = root.join("shared").join("lib");
+        fs::create_dir_all(&shared_lib).unwrap();
+        let app_dir = root.join("app1");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("yeth.toml"), r#"
+[app]
+dependencies = ['..\shared\lib']
+"#).unwrap();
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+
+        let app1 = apps.get("app1").unwrap();
+        match &app1.dependencies[0] {
+            Dependency::Path(path) => {
+                assert_eq!(path.canonicalize().unwrap(), shared_lib.canonicalize().unwrap())
+            }
+            other => panic!("Expected Path dependency, got {:?}", other),
+        }
+
+        // Resolves as an existing path rather than a missing app
+        assert!(crate::topological_sort::topological_sort(&apps).is_ok());
+    }
+
+    #[test]
+    fn test_discover_apps_with_env_expansion() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let sdk_dir = root.join("sdk");
+        fs::create_dir_all(&sdk_dir).unwrap();
+        let app_dir = root.join("app1");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("yeth.toml"), r#"
+[app]
+dependencies = ["${YETH_TEST_SDK_ROOT}/include"]
+exclude = ["$YETH_TEST_SDK_ROOT/docs"]
+"#).unwrap();
+
+        // SAFETY: the variable name is unique to this test, nothing else reads or writes it
+        unsafe { std::env::set_var("YETH_TEST_SDK_ROOT", &sdk_dir) };
+
+        // Opt-in: without expansion the variable stays literal
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        assert_ne!(
+            apps.get("app1").unwrap().dependencies[0],
+            Dependency::Path(sdk_dir.join("include"))
+        );
+
+        let config = Config::builder().root(root.to_path_buf()).expand_env(true).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        let app1 = apps.get("app1").unwrap();
+        assert_eq!(app1.dependencies[0], Dependency::Path(sdk_dir.join("include")));
+        assert_eq!(app1.exclude_patterns[0], ExcludePattern::AbsolutePath(sdk_dir.join("docs")));
+
+        // Undefined variables are an error, not a literal path
+        fs::write(app_dir.join("yeth.toml"), "[app]\ndependencies = [\"$YETH_TEST_UNDEFINED/x\"]\n").unwrap();
+        let result = discover_apps(&config);
+        assert!(matches!(result, Err(YethError::UndefinedEnvVar { .. })));
+    }
+
+    #[test]
+    fn test_discover_apps_parallel_matches_serial() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // A chain of apps spread over nested group directories
+        for i in 0..64 {
+            let app_dir = root.join(format!("group{}", i % 4)).join(format!("app{}", i));
+            fs::create_dir_all(&app_dir).unwrap();
+            let dependencies = if i == 0 { String::new() } else { format!("\"app{}\"", i - 1) };
+            fs::write(
+                app_dir.join("yeth.toml"),
+                format!("[app]\ndependencies = [{}]\nexclude = [\"dist\"]\n", dependencies),
+            )
+            .unwrap();
+        }
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let parallel = discover_apps(&config).unwrap();
+        let serial: HashMap<String, App> = find_config_files(root)
+            .unwrap()
+            .iter()
+            .map(|config_path| load_app(config_path, &config))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(parallel.len(), 64);
+        assert_eq!(parallel.len(), serial.len());
+        for (app_name, app) in &serial {
+            let found = parallel.get(app_name).unwrap();
+            assert_eq!(found.dir, app.dir);
+            assert_eq!(found.dependencies, app.dependencies);
+            assert_eq!(found.exclude_patterns, app.exclude_patterns);
+        }
+    }
+
+    #[test]
+    fn test_discover_apps_duplicate_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        for group in ["a", "b"] {
+            let app_dir = root.join(group).join("api");
+            fs::create_dir_all(&app_dir).unwrap();
+            fs::write(app_dir.join("yeth.toml"), "[app]\n").unwrap();
+        }
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        match discover_apps(&config) {
+            Err(YethError::DuplicateAppName { name, first, second }) => {
+                assert_eq!(name, "api");
+                assert_eq!(first, root.join("a").join("api"));
+                assert_eq!(second, root.join("b").join("api"));
+            }
+            other => panic!("expected a duplicate name error, got {:?}", other.map(|apps| apps.len())),
+        }
+    }
+
+    #[test]
+    fn test_discover_apps_include() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let app_dir = root.join("app1");
+        fs::create_dir_all(&app_dir).unwrap();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+
+        fs::write(app_dir.join("yeth.toml"), "[app]\ninclude = [\"src\", \"Dockerfile\"]\n").unwrap();
+        let apps = discover_apps(&config).unwrap();
+        assert_eq!(
+            apps.get("app1").unwrap().include_patterns,
+            Some(vec!["src".to_string(), "Dockerfile".to_string()])
+        );
+
+        // An empty list is a mistake rather than "hash nothing"
+        fs::write(app_dir.join("yeth.toml"), "[app]\ninclude = []\n").unwrap();
+        let result = discover_apps(&config);
+        assert!(matches!(result, Err(YethError::EmptyInclude(_))));
+    }
+
+    #[test]
+    fn test_discover_apps_promote_path_deps() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // shared is an app with its own dependency; backend reaches it by path
+        for (name, config) in [
+            ("base", "[app]\n"),
+            ("shared", "[app]\ndependencies = [\"base\"]\n"),
+            ("backend", "[app]\ndependencies = [\"../shared\", \"../docs\"]\n"),
+        ] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join("yeth.toml"), config).unwrap();
+        }
+        fs::create_dir_all(root.join("docs")).unwrap();
+
+        // Off by default
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        assert!(matches!(apps["backend"].dependencies[0], Dependency::Path(_)));
+
+        let config = Config::builder().root(root.to_path_buf()).promote_path_deps(true).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        assert_eq!(
+            apps["backend"].dependencies,
+            vec![
+                Dependency::App("shared".to_string()),
+                Dependency::Path(root.join("backend").join("..").join("docs")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discover_apps_resolve_path_deps_to_apps() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        for (name, config) in [
+            ("libs/base", "[app]\n"),
+            ("libs/auth", "[app]\ndependencies = [\"../base\"]\n"),
+            ("web", "[app]\ndependencies = [\"./../libs/auth\"]\n"),
+        ] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join("yeth.toml"), config).unwrap();
+        }
+
+        let config = Config::builder()
+            .root(root.to_path_buf())
+            .resolve_path_deps_to_apps(true)
+            .build()
+            .unwrap();
+        assert!(config.promote_path_deps);
+        let apps = discover_apps(&config).unwrap();
+        assert_eq!(apps["web"].dependencies, vec![Dependency::App("auth".to_string())]);
+
+        // The resolved app brings its own dependencies into the order
+        let order = crate::topological_sort::topological_sort(&apps).unwrap();
+        assert_eq!(order, vec!["base", "auth", "web"]);
+    }
+
+    #[test]
+    fn test_discover_apps_path_dependency_inside_other_app() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        for (name, config) in [
+            ("proto", "[app]\n"),
+            ("api", "[app]\ndependencies = [\"../proto/schema\"]\n"),
+        ] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join("yeth.toml"), config).unwrap();
+        }
+        fs::create_dir_all(root.join("proto/schema")).unwrap();
+        // An app at the root encloses every other app, so it is never suggested
+        fs::write(root.join("yeth.toml"), "[app]\nname = \"monorepo\"\n").unwrap();
+
+        // Only a warning by default
+        let config = Config::builder().root(root.to_path_buf()).quiet(true).build().unwrap();
+        assert!(discover_apps(&config).is_ok());
+
+        let config = Config::builder().root(root.to_path_buf()).strict(true).build().unwrap();
+        let err = discover_apps(&config).unwrap_err();
+        assert!(matches!(
+            &err,
+            YethError::PathDependencyInsideApp { app, owner, .. } if app == "api" && owner == "proto"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_apps_unreadable_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("visible")).unwrap();
+        fs::write(root.join("visible").join("yeth.toml"), "[app]\n").unwrap();
+        let hidden = root.join("private");
+        fs::create_dir_all(hidden.join("app")).unwrap();
+        fs::write(hidden.join("app").join("yeth.toml"), "[app]\n").unwrap();
+        fs::set_permissions(&hidden, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let readable = fs::read_dir(&hidden).is_ok();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let result = discover_apps(&config);
+        fs::set_permissions(&hidden, fs::Permissions::from_mode(0o755)).unwrap();
+        if readable {
+            // Running as root, permissions don't apply
+            return;
+        }
+        assert!(matches!(result, Err(YethError::DiscoveryWalkError { path, .. }) if path == hidden));
+    }
+
+    #[test]
+    fn test_discover_apps_config_not_a_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("good")).unwrap();
+        fs::write(root.join("good").join("yeth.toml"), "[app]\n").unwrap();
+        fs::create_dir_all(root.join("broken").join("yeth.toml")).unwrap();
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let err = discover_apps(&config).unwrap_err();
+        assert!(matches!(&err, YethError::ConfigIsDirectory(path) if path.ends_with("broken/yeth.toml")));
+
+        // Skipped with --keep-going
+        let config = Config::builder().root(root.to_path_buf()).keep_going(true).quiet(true).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        assert_eq!(apps.keys().collect::<Vec<_>>(), vec!["good"]);
+
+        #[cfg(unix)]
+        {
+            fs::remove_dir(root.join("broken").join("yeth.toml")).unwrap();
+            std::os::unix::fs::symlink("missing.toml", root.join("broken").join("yeth.toml")).unwrap();
+            let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+            let err = discover_apps(&config).unwrap_err();
+            assert!(matches!(&err, YethError::DanglingConfigSymlink { target, .. } if target == Path::new("missing.toml")));
+        }
+    }
+
+    #[test]
+    fn test_discover_apps_config_at_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        for name in ["api", "web"] {
+            fs::create_dir_all(workspace.join(name)).unwrap();
+            fs::write(workspace.join(name).join("yeth.toml"), "[app]\n").unwrap();
+            fs::write(workspace.join(name).join("main.rs"), name).unwrap();
+        }
+        fs::write(workspace.join("yeth.toml"), "[app]\n").unwrap();
+        fs::write(workspace.join("Makefile"), "all:").unwrap();
+
+        // The root's own name depends on the checkout, so it has to be given
+        let root = workspace.join("api").join("..");
+        let config = Config::builder().root(root.clone()).build().unwrap();
+        let err = discover_apps(&config).unwrap_err();
+        assert!(matches!(&err, YethError::RootAppWithoutName(path) if path.ends_with("yeth.toml")));
+
+        fs::write(workspace.join("yeth.toml"), "[app]\nname = \"platform\"\n").unwrap();
+        let apps = discover_apps(&config).unwrap();
+        let mut names: Vec<_> = apps.keys().cloned().collect();
+        names.sort();
+        assert_eq!(names, vec!["api", "platform", "web"]);
+        assert_eq!(apps["platform"].dir, root);
+
+        // The child apps are left out of the root app's hash
+        let engine = crate::YethEngine::new(config.clone());
+        let before = engine.run().unwrap();
+        fs::write(workspace.join("api").join("main.rs"), "changed").unwrap();
+        let after = crate::YethEngine::new(config.clone()).run().unwrap();
+        assert_ne!(before["api"], after["api"]);
+        assert_eq!(before["web"], after["web"]);
+        assert_eq!(before["platform"], after["platform"]);
+
+        // A name is never a path
+        for name in ["", "../api"] {
+            fs::write(workspace.join("yeth.toml"), format!("[app]\nname = {:?}\n", name)).unwrap();
+            let err = discover_apps(&config).unwrap_err();
+            assert!(matches!(&err, YethError::InvalidAppName { name: invalid, .. } if invalid == name));
+        }
+    }
+
+    #[test]
+    fn test_discover_apps_external_path_dependency() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("repo");
+        let protos = temp_dir.path().join("platform-protos");
+        fs::create_dir_all(root.join("services").join("api")).unwrap();
+        fs::create_dir_all(&protos).unwrap();
+        fs::write(protos.join("api.proto"), "message Api {}").unwrap();
+        fs::write(protos.join("notes.tmp"), "draft").unwrap();
+        fs::write(root.join(".yethignore"), "*.tmp\n").unwrap();
+        let api = root.join("services").join("api");
+        fs::write(api.join("yeth.toml"), "[app]\ndependencies = [\"../../../platform-protos\"]\n").unwrap();
+
+        // Kept canonical, and shown that way in the graph
+        let config = Config::builder().root(root.clone()).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        let canonical = protos.canonicalize().unwrap();
+        assert_eq!(apps["api"].dependencies, vec![Dependency::Path(canonical.clone())]);
+        let tree = crate::dependency_graph::render_tree(&config, &apps, Some("api"), None).unwrap();
+        assert_eq!(tree, vec![vec!["api".to_string(), format!("  └─ {} (dir)", canonical.display())]]);
+
+        // The root's .yethignore doesn't reach it
+        let before = crate::YethEngine::new(config.clone()).run().unwrap();
+        fs::write(protos.join("notes.tmp"), "final").unwrap();
+        let after = crate::YethEngine::new(config.clone()).run().unwrap();
+        assert_ne!(before["api"], after["api"]);
+
+        let config = Config::builder().root(root.clone()).deny_external_paths(true).build().unwrap();
+        let err = discover_apps(&config).unwrap_err();
+        assert!(matches!(&err, YethError::ExternalPathDependency { raw, resolved, .. }
+            if raw == "../../../platform-protos" && resolved == &canonical));
+
+        // A missing one is reported with both the written and the resolved path
+        fs::remove_dir_all(&protos).unwrap();
+        let config = Config::builder().root(root.clone()).build().unwrap();
+        let err = discover_apps(&config).unwrap_err();
+        assert!(matches!(&err, YethError::ExternalPathDependencyNotFound { raw, resolved, app }
+            if raw == "../../../platform-protos" && resolved == &canonical && app == "api"));
+    }
+
+    #[test]
+    fn test_discover_apps_dependency_table_form() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("shared")).unwrap();
+
+        // The same dependencies written as strings and as inline tables
+        for (name, config) in [
+            ("strings", "[app]\ndependencies = [\"lib\", \"../shared\"]\n"),
+            ("tables", "[app]\ndependencies = [{ app = \"lib\" }, { path = \"../shared\" }]\n"),
+            ("lib", "[app]\n"),
+        ] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join("yeth.toml"), config).unwrap();
+        }
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        assert_eq!(
+            apps["strings"].dependencies,
+            vec![
+                Dependency::App("lib".to_string()),
+                Dependency::Path(root.join("strings").join("..").join("shared")),
+            ]
+        );
+        assert_eq!(
+            apps["tables"].dependencies,
+            vec![
+                Dependency::App("lib".to_string()),
+                Dependency::Path(root.join("tables").join("..").join("shared")),
+            ]
+        );
+
+        // A table must be exactly one of the two forms
+        fs::write(
+            root.join("tables").join("yeth.toml"),
+            "[app]\ndependencies = [{ app = \"lib\", path = \"../shared\" }]\n",
+        )
+        .unwrap();
+        assert!(matches!(discover_apps(&config), Err(YethError::TomlParseError { .. })));
+    }
+
+    #[test]
+    fn test_discover_apps_dotfile_exclude() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let app_dir = root.join("api");
+        fs::create_dir_all(app_dir.join("config").join("local")).unwrap();
+        fs::write(app_dir.join("yeth.toml"), "[app]\nexclude = [\".env\", \"./.secrets\", \".*.swp\"]\n").unwrap();
+        fs::write(app_dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let apps = discover_apps(&config).unwrap();
+        assert_eq!(
+            apps["api"].exclude_patterns,
+            vec![
+                ExcludePattern::Name(".env".to_string()),
+                ExcludePattern::AbsolutePath(app_dir.join(".secrets")),
+                ExcludePattern::Glob(".*.swp".to_string()),
+            ]
+        );
+
+        // .env files anywhere in the app leave its hash alone
+        let hash = || crate::YethEngine::new(config.clone()).run().unwrap()["api"].clone();
+        let before = hash();
+        for dir in [app_dir.clone(), app_dir.join("config"), app_dir.join("config").join("local")] {
+            fs::write(dir.join(".env"), "SECRET=1").unwrap();
+        }
+        assert_eq!(hash(), before);
+        fs::write(app_dir.join("config").join("app.env"), "SECRET=1").unwrap();
+        assert_ne!(hash(), before);
     }
 }
@@ -0,0 +1,126 @@
+use crate::calculate_hashes::calculate_hashes_while;
+use crate::cfg::App;
+use crate::error::YethError;
+use crate::run_stats::Phase;
+use crate::version_writes::{self, VersionWriteAction};
+use crate::YethEngine;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Async variants of the engine methods, enabled with the `async` feature. Each one
+/// runs the sync implementation on tokio's blocking thread pool, so the results and
+/// errors are the same and parallel work is still bounded by `Config::jobs`.
+///
+/// The files aren't read with `tokio::fs`: it runs every call on the same blocking
+/// pool, one task per read, so walking and hashing a tree file by file through it
+/// would be slower than doing the whole walk in one blocking task.
+impl YethEngine {
+    /// `discover_apps` without blocking the runtime
+    pub async fn discover_apps_async(&self) -> Result<HashMap<String, App>, YethError> {
+        self.spawn_blocking(|engine| engine.discover_apps()).await
+    }
+
+    /// `run` without blocking the runtime
+    pub async fn run_async(&self) -> Result<HashMap<String, String>, YethError> {
+        self.spawn_blocking(|engine| engine.run()).await
+    }
+
+    /// `run_with_apps` without blocking the runtime
+    pub async fn run_with_apps_async(&self, apps: &HashMap<String, App>) -> Result<HashMap<String, String>, YethError> {
+        let apps = apps.clone();
+        self.spawn_blocking(move |engine| engine.run_with_apps(&apps)).await
+    }
+
+    /// `run_streaming` as a channel: `apps` are sorted and hashed on the blocking pool,
+    /// and each app name and hash is sent as soon as it is computed, in dependency
+    /// order. An error, including a panic in the hashing task, is sent as the last
+    /// item. Dropping the receiver stops hashing after the app in progress.
+    pub async fn run_streaming_async(
+        &self,
+        apps: &HashMap<String, App>,
+    ) -> mpsc::Receiver<Result<(String, String), YethError>> {
+        // Room for every app, so hashing never waits for a slow reader
+        let (sender, receiver) = mpsc::channel(apps.len().max(1));
+        let apps = apps.clone();
+        let engine = self.shared();
+        let task_sender = sender.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            let result = engine.topological_sort(&apps).and_then(|ordered_apps| {
+                engine.timed(Phase::Hashing, || {
+                    calculate_hashes_while(ordered_apps, &apps, &engine.config, &engine.stats, |app_name, hash| {
+                        task_sender.blocking_send(Ok((app_name.to_string(), hash.to_string()))).is_ok()
+                    })
+                })
+            });
+            if let Err(error) = result {
+                // Nobody to tell when the receiver is gone
+                let _ = task_sender.blocking_send(Err(error));
+            }
+        });
+        tokio::spawn(async move {
+            if let Err(error) = task.await {
+                let _ = sender.send(Err(YethError::TaskFailed(error.to_string()))).await;
+            }
+        });
+        receiver
+    }
+
+    /// `topological_sort` without blocking the runtime
+    pub async fn topological_sort_async(&self, apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
+        let apps = apps.clone();
+        self.spawn_blocking(move |engine| engine.topological_sort(&apps)).await
+    }
+
+    /// `calculate_hashes` without blocking the runtime
+    pub async fn calculate_hashes_async(
+        &self,
+        ordered_apps: Vec<String>,
+        apps: &HashMap<String, App>,
+    ) -> Result<HashMap<String, String>, YethError> {
+        let apps = apps.clone();
+        self.spawn_blocking(move |engine| engine.calculate_hashes(ordered_apps, &apps)).await
+    }
+
+    /// `calculate_hashes_for_apps` without blocking the runtime
+    pub async fn calculate_hashes_for_apps_async(
+        &self,
+        app_names: &[String],
+        apps: &HashMap<String, App>,
+    ) -> Result<HashMap<String, String>, YethError> {
+        let (app_names, apps) = (app_names.to_vec(), apps.clone());
+        self.spawn_blocking(move |engine| engine.calculate_hashes_for_apps(&app_names, &apps)).await
+    }
+
+    /// Write the yeth.version files a plan creates or updates. Once started, every
+    /// write runs to completion even if the returned future is dropped, so a
+    /// cancelled caller never leaves the plan half applied.
+    pub async fn apply_version_writes_async(&self, actions: Vec<VersionWriteAction>) -> Result<(), YethError> {
+        self.spawn_blocking(move |_| version_writes::apply_version_writes(&actions)).await
+    }
+
+    /// Run `f` on the blocking pool with an engine from `shared`
+    async fn spawn_blocking<T, F>(&self, f: F) -> Result<T, YethError>
+    where
+        F: FnOnce(&YethEngine) -> Result<T, YethError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let engine = self.shared();
+        match tokio::task::spawn_blocking(move || f(&engine)).await {
+            Ok(result) => result,
+            Err(error) => match error.try_into_panic() {
+                Ok(payload) => std::panic::resume_unwind(payload),
+                // Cancelled, as the runtime is shutting down
+                Err(error) => Err(YethError::TaskFailed(error.to_string())),
+            },
+        }
+    }
+
+    /// An engine for a blocking task, sharing this one's config and stats
+    fn shared(&self) -> YethEngine {
+        YethEngine {
+            config: self.config.clone(),
+            stats: Arc::clone(&self.stats),
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use crate::cfg::Config;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, thiserror::Error)]
 pub enum YethError {
@@ -8,14 +10,44 @@ pub enum YethError {
     #[error("Path dependency '{0}' for '{1}' not found")]
     PathDependencyNotFound(PathBuf, String),
 
+    #[error("Path dependency '{raw}' for '{app}' resolves to '{resolved}', which does not exist")]
+    ExternalPathDependencyNotFound { raw: String, resolved: PathBuf, app: String },
+
+    #[error("Path dependency '{raw}' for '{app}' resolves to '{resolved}', outside the root, which --deny-external-paths forbids")]
+    ExternalPathDependency { raw: String, resolved: PathBuf, app: String },
+
+    #[error("Path dependency '{0}' for '{1}' overlaps the application directory, so its files would be hashed twice")]
+    PathDependencyOverlapsApp(PathBuf, String),
+
+    #[error("Path dependency '{path}' for '{app}' is inside application '{owner}', depend on '{owner}' instead")]
+    PathDependencyInsideApp { path: PathBuf, app: String, owner: String },
+
+    #[error("Path dependency '{link}' for '{app}' is a symlink to '{target}', which does not exist")]
+    DanglingSymlink { link: PathBuf, target: PathBuf, app: String },
+
+    #[error("Path dependency '{path}' for '{app}' disappeared while hashing; was it deleted during the run?")]
+    PathVanished { path: PathBuf, app: String },
+
+    #[error("Permission denied reading path dependency '{path}' for '{app}'")]
+    PathPermissionDenied { path: PathBuf, app: String },
+
     #[error("Path '{0}' is neither a file nor a directory")]
     NorFileOrDirectory(PathBuf),
 
-    #[error("Circular dependency detected")]
-    CircularDependency,
+    #[error("Circular dependency detected: {}", cycle.join(" -> "))]
+    CircularDependency { cycle: Vec<String> },
+
+    #[error("Application '{0}' depends on itself; remove '{0}' from its dependencies")]
+    SelfDependency(String),
+
+    #[error("Application '{app}' was not hashed because its dependency '{dependency}' failed")]
+    DependencyFailed { app: String, dependency: String },
 
-    #[error("Dependency not processed in correct order")]
-    IncorrectOrder,
+    #[error("Application '{app}' was hashed before its dependency '{missing_dep}'; dependencies must come first in the order")]
+    IncorrectOrder { app: String, missing_dep: String },
+
+    #[error("Application '{0}' is in the hashing order but was not discovered")]
+    UnknownAppInOrder(String),
 
     #[error("Config file path has no parent directory: {0}")]
     NoParentDir(String),
@@ -23,18 +55,231 @@ pub enum YethError {
     #[error("App directory path has no file name: {0}")]
     NoFileName(String),
 
-    #[error("Failed to read config file: {0}")]
-    ConfigReadError(#[from] std::io::Error),
+    #[error("App directory name is not valid UTF-8: {0}")]
+    NonUtf8AppName(PathBuf),
+
+    #[error("Failed to read config file '{path}': {source}")]
+    ConfigReadError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Config file '{0}' is a directory, not a file")]
+    ConfigIsDirectory(PathBuf),
+
+    #[error("Config file '{path}' is a symlink to '{target}', which does not exist")]
+    DanglingConfigSymlink { path: PathBuf, target: PathBuf },
+
+    #[error("Failed to read '{path}' while discovering applications: {source}")]
+    DiscoveryWalkError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read directory '{path}' while hashing: {source}")]
+    HashWalkError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse TOML in '{path}': {source}")]
+    TomlParseError {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Failed to parse YAML in '{path}': {source}")]
+    YamlParseError {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[error("'{0}' is at the root, whose directory name depends on where it is checked out: set `name` under [app]")]
+    RootAppWithoutName(PathBuf),
+
+    #[error("Invalid application name '{name}' in '{path}': it must not be empty or look like a path")]
+    InvalidAppName { name: String, path: PathBuf },
+
+    #[error("Application '{app}' has hash_dir '{path}', which is not a directory")]
+    HashDirNotFound { path: PathBuf, app: String },
+
+    #[error("Application '{app}' has hash_dir '{hash_dir}', which is outside its directory")]
+    HashDirOutsideApp { hash_dir: String, app: String },
+
+    #[error("Empty 'include' list in '{0}': remove it to hash every file")]
+    EmptyInclude(PathBuf),
+
+    #[error("Empty 'extensions' list in '{0}': remove it to hash every file")]
+    EmptyExtensions(PathBuf),
 
-    #[error("Failed to parse TOML: {0}")]
-    TomlParseError(#[from] toml::de::Error),
+    #[error("Failed to read '{path}' while hashing: {source}")]
+    HashIoError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error(
+        "Unknown key '{key}' in '{path}'{}",
+        suggestion.as_ref().map(|s| format!(", did you mean '{}'?", s)).unwrap_or_default()
+    )]
+    UnknownConfigKey {
+        path: PathBuf,
+        key: String,
+        suggestion: Option<String>,
+    },
+
+    #[error("Not a git repository: {0}")]
+    NotAGitRepository(PathBuf),
+
+    #[error("Git command failed: {0}")]
+    GitError(String),
+
+    #[error("Background hashing task failed: {0}")]
+    TaskFailed(String),
+
+    #[error("Environment variable '{name}' used in '{value}' is not defined")]
+    UndefinedEnvVar { name: String, value: String },
+
+    #[error("Application name '{name}' is used by both {first} and {second}")]
+    DuplicateAppName {
+        name: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+
+    #[error("Lockfile '{0}' not found, create it with --write-lock")]
+    LockFileNotFound(PathBuf),
+
+    #[error("'{0}' already exists, pass --force to replace it")]
+    ConfigExists(PathBuf),
+
+    #[error("Read buffer size must be greater than zero")]
+    ZeroReadBufferSize,
+
+    #[error("Number of jobs must be greater than zero")]
+    ZeroJobs,
+
+    #[error("No application matches '{0}'")]
+    NoAppMatchesPattern(String),
+
+    #[error("No application is tagged '{0}'")]
+    NoAppWithTag(String),
+
+    #[error("Application '{app}' is excluded with --exclude-app, but '{dependent}' depends on it")]
+    ExcludedAppIsDependency { app: String, dependent: String },
+
+    #[error("Application '{0}' has no characters allowed in an image name")]
+    EmptyImageName(String),
+
+    #[error("Applications '{first}' and '{second}' both become image '{image}'")]
+    DuplicateImageName { image: String, first: String, second: String },
+
+    #[error("Applications {} have different hashes starting with '{short_hash}', use a longer --short-hash-length", apps.join(", "))]
+    ShortHashCollision { short_hash: String, apps: Vec<String> },
+
+    #[error("'{0}' is not a valid image tag: use at most 128 letters, digits, '_', '.' and '-', not starting with '.' or '-'")]
+    InvalidImageTag(String),
 
     #[error("No applications found")]
     NoApplicationsFound,
 
-    #[error("Application '{0}' not found")]
-    AppNotFound(String),
+    #[error(
+        "Application '{name}' not found{}",
+        match suggestions.as_slice() {
+            [] => String::new(),
+            [only] => format!(", did you mean '{}'?", only),
+            [first, second, ..] => format!(", did you mean '{}' or '{}'?", first, second),
+        }
+    )]
+    AppNotFound {
+        name: String,
+        suggestions: Vec<String>,
+    },
 
     #[error("Not implemented")]
     NotImplemented,
 }
+
+impl YethError {
+    /// Describe why path dependency `path` of `app` can't be read: a dangling symlink,
+    /// missing permissions, or a missing path. A missing path is reported as vanished
+    /// once `checked` (its existence was verified before hashing started).
+    pub(crate) fn path_dependency(path: &Path, app: &str, error: std::io::Error, checked: bool) -> Self {
+        let is_link = std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+        match error.kind() {
+            ErrorKind::NotFound if is_link => YethError::DanglingSymlink {
+                link: path.to_path_buf(),
+                target: std::fs::read_link(path).unwrap_or_default(),
+                app: app.to_string(),
+            },
+            ErrorKind::PermissionDenied => YethError::PathPermissionDenied {
+                path: path.to_path_buf(),
+                app: app.to_string(),
+            },
+            ErrorKind::NotFound if checked => YethError::PathVanished {
+                path: path.to_path_buf(),
+                app: app.to_string(),
+            },
+            ErrorKind::NotFound => YethError::PathDependencyNotFound(path.to_path_buf(), app.to_string()),
+            _ => YethError::IoError(error),
+        }
+    }
+
+    /// Rewrite the paths in this error with `Config::display_path`
+    pub fn with_display_paths(self, config: &Config) -> Self {
+        let show = |path: PathBuf| config.display_path(&path);
+        match self {
+            YethError::PathDependencyNotFound(path, app) => YethError::PathDependencyNotFound(show(path), app),
+            YethError::PathDependencyOverlapsApp(path, app) => {
+                YethError::PathDependencyOverlapsApp(show(path), app)
+            }
+            YethError::PathDependencyInsideApp { path, app, owner } => {
+                YethError::PathDependencyInsideApp { path: show(path), app, owner }
+            }
+            YethError::DanglingSymlink { link, target, app } => YethError::DanglingSymlink {
+                link: show(link),
+                target,
+                app,
+            },
+            YethError::PathVanished { path, app } => YethError::PathVanished { path: show(path), app },
+            YethError::PathPermissionDenied { path, app } => YethError::PathPermissionDenied { path: show(path), app },
+            YethError::HashIoError { path, source } => YethError::HashIoError { path: show(path), source },
+            YethError::NorFileOrDirectory(path) => YethError::NorFileOrDirectory(show(path)),
+            YethError::NonUtf8AppName(path) => YethError::NonUtf8AppName(show(path)),
+            YethError::ConfigReadError { path, source } => YethError::ConfigReadError { path: show(path), source },
+            YethError::ConfigIsDirectory(path) => YethError::ConfigIsDirectory(show(path)),
+            YethError::DanglingConfigSymlink { path, target } => YethError::DanglingConfigSymlink {
+                path: show(path),
+                target,
+            },
+            YethError::DiscoveryWalkError { path, source } => YethError::DiscoveryWalkError { path: show(path), source },
+            YethError::HashWalkError { path, source } => YethError::HashWalkError { path: show(path), source },
+            YethError::TomlParseError { path, source } => YethError::TomlParseError { path: show(path), source },
+            YethError::YamlParseError { path, source } => YethError::YamlParseError { path: show(path), source },
+            YethError::RootAppWithoutName(path) => YethError::RootAppWithoutName(show(path)),
+            YethError::InvalidAppName { name, path } => YethError::InvalidAppName { name, path: show(path) },
+            YethError::HashDirNotFound { path, app } => YethError::HashDirNotFound { path: show(path), app },
+            YethError::EmptyInclude(path) => YethError::EmptyInclude(show(path)),
+            YethError::EmptyExtensions(path) => YethError::EmptyExtensions(show(path)),
+            YethError::UnknownConfigKey { path, key, suggestion } => YethError::UnknownConfigKey {
+                path: show(path),
+                key,
+                suggestion,
+            },
+            YethError::DuplicateAppName { name, first, second } => YethError::DuplicateAppName {
+                name,
+                first: show(first),
+                second: show(second),
+            },
+            YethError::LockFileNotFound(path) => YethError::LockFileNotFound(show(path)),
+            YethError::ConfigExists(path) => YethError::ConfigExists(show(path)),
+            other => other,
+        }
+    }
+}
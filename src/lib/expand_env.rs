@@ -0,0 +1,98 @@
+use crate::error::YethError;
+
+/// Expand `$VAR`, `${VAR}` and a leading `~` using the process environment
+pub fn expand_env(value: &str) -> Result<String, YethError> {
+    expand_with(value, |name| std::env::var(name).ok())
+}
+
+/// Expand variables in `value`, resolving names through `lookup`.
+/// A `$` not followed by a variable name is kept as is.
+pub fn expand_with<F>(value: &str, lookup: F) -> Result<String, YethError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let resolve = |name: &str| {
+        lookup(name).ok_or_else(|| YethError::UndefinedEnvVar {
+            name: name.to_string(),
+            value: value.to_string(),
+        })
+    };
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    // `~` alone or followed by a separator means the home directory
+    if let Some(after) = rest.strip_prefix('~')
+        && (after.is_empty() || after.starts_with(['/', '\\']))
+    {
+        let home = lookup("HOME").or_else(|| lookup("USERPROFILE"));
+        result.push_str(&home.ok_or_else(|| YethError::UndefinedEnvVar {
+            name: "HOME".to_string(),
+            value: value.to_string(),
+        })?);
+        rest = after;
+    }
+
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(braced) = after.strip_prefix('{') {
+            let end = braced.find('}').ok_or_else(|| YethError::UndefinedEnvVar {
+                name: braced.to_string(),
+                value: value.to_string(),
+            })?;
+            result.push_str(&resolve(&braced[..end])?);
+            rest = &braced[end + 1..];
+            continue;
+        }
+
+        let name_len = after
+            .char_indices()
+            .take_while(|(i, c)| c.is_ascii_alphabetic() || *c == '_' || (*i > 0 && c.is_ascii_digit()))
+            .count();
+        if name_len == 0 {
+            result.push('$');
+            rest = after;
+        } else {
+            result.push_str(&resolve(&after[..name_len])?);
+            rest = &after[name_len..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "SDK_ROOT" => Some("/opt/sdk".to_string()),
+            "HOME" => Some("/home/dev".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_with() {
+        assert_eq!(expand_with("$SDK_ROOT/include", lookup).unwrap(), "/opt/sdk/include");
+        assert_eq!(expand_with("${SDK_ROOT}lib", lookup).unwrap(), "/opt/sdklib");
+        assert_eq!(expand_with("~/sdk", lookup).unwrap(), "/home/dev/sdk");
+        assert_eq!(expand_with("../shared", lookup).unwrap(), "../shared");
+        assert_eq!(expand_with("cost$", lookup).unwrap(), "cost$");
+        assert_eq!(expand_with("~user/sdk", lookup).unwrap(), "~user/sdk");
+    }
+
+    #[test]
+    fn test_expand_with_undefined_variable() {
+        let result = expand_with("$MISSING/include", lookup);
+        assert!(matches!(
+            result,
+            Err(YethError::UndefinedEnvVar { ref name, .. }) if name == "MISSING"
+        ));
+        assert!(expand_with("${MISSING}", lookup).is_err());
+    }
+}
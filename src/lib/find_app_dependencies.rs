@@ -2,28 +2,53 @@ use crate::cfg::{App, Dependency};
 use crate::error::YethError;
 use std::collections::HashMap;
 
-/// Find all dependencies for a specific app (including transitive dependencies)
+/// Find all dependencies for a specific app (including transitive dependencies).
+/// A cycle reachable from the app is a `CircularDependency` error.
 pub fn find_app_dependencies(
     app_name: &str,
     apps: &HashMap<String, App>,
+) -> Result<Vec<String>, YethError> {
+    collect_dependencies(app_name, apps, true)
+}
+
+/// Like `find_app_dependencies`, but a cycle is cut where it closes instead of
+/// failing, for callers that only display the graph
+pub fn find_app_dependencies_lenient(
+    app_name: &str,
+    apps: &HashMap<String, App>,
+) -> Result<Vec<String>, YethError> {
+    collect_dependencies(app_name, apps, false)
+}
+
+fn collect_dependencies(
+    app_name: &str,
+    apps: &HashMap<String, App>,
+    strict: bool,
 ) -> Result<Vec<String>, YethError> {
     if !apps.contains_key(app_name) {
-        return Err(YethError::AppNotFound(app_name.to_string()));
+        return Err(crate::suggest::app_not_found(app_name, apps));
     }
 
     let mut visited = std::collections::HashSet::new();
     let mut result = Vec::new();
-    let mut processing = std::collections::HashSet::new();
+    // Apps currently being processed, from the requested app down (cycle detection)
+    let mut processing: Vec<String> = Vec::new();
     
     fn dfs(
         current: &str,
         apps: &HashMap<String, App>,
+        strict: bool,
         visited: &mut std::collections::HashSet<String>,
-        processing: &mut std::collections::HashSet<String>,
+        processing: &mut Vec<String>,
         result: &mut Vec<String>
     ) -> Result<(), YethError> {
         // Check if we're currently processing this node (cycle detection)
-        if processing.contains(current) {
+        if let Some(pos) = processing.iter().position(|name| name == current) {
+            if strict {
+                let mut cycle = processing[pos..].to_vec();
+                cycle.push(current.to_string());
+                return Err(YethError::CircularDependency { cycle });
+            }
             return Ok(()); // Skip the rest of this branch to avoid infinite recursion
         }
         
@@ -33,13 +58,16 @@ pub fn find_app_dependencies(
         }
         
         // Mark as currently processing
-        processing.insert(current.to_string());
+        processing.push(current.to_string());
         
         if let Some(app) = apps.get(current) {
             for dep in &app.dependencies {
                 match dep {
+                    Dependency::App(dep_name) if strict && dep_name == current => {
+                        return Err(YethError::SelfDependency(current.to_string()));
+                    }
                     Dependency::App(dep_name) => {
-                        dfs(dep_name, apps, visited, processing, result)?;
+                        dfs(dep_name, apps, strict, visited, processing, result)?;
                     }
                     Dependency::Path(_) => {
                         // Path dependencies don't need to be processed recursively
@@ -49,13 +77,13 @@ pub fn find_app_dependencies(
         }
         
         // Mark as visited and add to result
-        processing.remove(current);
+        processing.pop();
         visited.insert(current.to_string());
         result.push(current.to_string());
         Ok(())
     }
     
-    dfs(app_name, apps, &mut visited, &mut processing, &mut result)?;
+    dfs(app_name, apps, strict, &mut visited, &mut processing, &mut result)?;
     
     // Result is already in correct order (dependencies first, then the app)
     Ok(result)
@@ -81,6 +109,7 @@ mod tests {
                 dir: PathBuf::from("/test/app1"),
                 dependencies: vec![],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -92,6 +121,7 @@ mod tests {
                 dir: PathBuf::from("/test/app2"),
                 dependencies: vec![Dependency::App("app1".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -103,6 +133,7 @@ mod tests {
                 dir: PathBuf::from("/test/app3"),
                 dependencies: vec![Dependency::App("app2".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -117,6 +148,7 @@ mod tests {
                     Dependency::App("app3".to_string()),
                 ],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -138,7 +170,13 @@ mod tests {
 
         // Test non-existent app
         let result = find_app_dependencies("nonexistent", &apps);
-        assert!(matches!(result, Err(YethError::AppNotFound(_))));
+        assert!(matches!(result, Err(YethError::AppNotFound { .. })));
+
+        // Test near-miss name suggests the closest apps
+        let error = find_app_dependencies("app", &apps).unwrap_err();
+        assert!(matches!(&error, YethError::AppNotFound { suggestions, .. } if suggestions.len() == 2));
+        let error = find_app_dependencies("apq4", &apps).unwrap_err();
+        assert_eq!(error.to_string(), "Application 'apq4' not found, did you mean 'app4'?");
     }
 
     #[test]
@@ -153,6 +191,7 @@ mod tests {
                 dir: PathBuf::from("/test/app1"),
                 dependencies: vec![Dependency::Path(PathBuf::from("/shared/lib"))],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -164,6 +203,7 @@ mod tests {
                 dir: PathBuf::from("/test/app2"),
                 dependencies: vec![Dependency::App("app1".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -188,6 +228,7 @@ mod tests {
                 dir: PathBuf::from("/test/app1"),
                 dependencies: vec![Dependency::App("app2".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -198,11 +239,16 @@ mod tests {
                 dir: PathBuf::from("/test/app2"),
                 dependencies: vec![Dependency::App("app1".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
-        // The function should handle circular dependencies gracefully
-        let result = find_app_dependencies("app1", &apps).unwrap();
+        // The strict version reports the cycle
+        let error = find_app_dependencies("app1", &apps).unwrap_err();
+        assert!(matches!(&error, YethError::CircularDependency { cycle } if cycle == &["app1", "app2", "app1"]));
+
+        // The lenient version handles circular dependencies gracefully
+        let result = find_app_dependencies_lenient("app1", &apps).unwrap();
         // Both apps should be in the result, but no infinite loop
         assert_eq!(result.len(), 2);
         assert!(result.contains(&"app1".to_string()));
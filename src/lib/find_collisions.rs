@@ -0,0 +1,54 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Find hash values shared by more than one app: each shared hash with the apps
+/// that have it, apps sorted by name and groups by their first app
+pub fn find_collisions(hashes: &HashMap<String, String>) -> Vec<(String, Vec<String>)> {
+    let mut by_hash: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (app_name, hash) in hashes {
+        by_hash.entry(hash.as_str()).or_default().push(app_name.clone());
+    }
+
+    let mut collisions: Vec<(String, Vec<String>)> = by_hash
+        .into_iter()
+        .filter(|(_, apps)| apps.len() > 1)
+        .map(|(hash, mut apps)| {
+            apps.sort();
+            (hash.to_string(), apps)
+        })
+        .collect();
+    collisions.sort_by(|a, b| a.1.cmp(&b.1));
+    collisions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::Config;
+    use crate::YethEngine;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_collisions() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // Two empty apps are indistinguishable, a third has a file of its own
+        for name in ["empty1", "empty2", "service"] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join("yeth.toml"), "[app]\n").unwrap();
+        }
+        fs::write(root.join("service").join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let hashes = YethEngine::new(config).run().unwrap();
+        let collisions = find_collisions(&hashes);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].0, hashes["empty1"]);
+        assert_eq!(collisions[0].1, vec!["empty1", "empty2"]);
+
+        // Distinct hashes collide with nothing
+        let unique = HashMap::from([("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())]);
+        assert!(find_collisions(&unique).is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::cfg::{App, Dependency};
+use crate::error::YethError;
+use std::collections::{HashMap, HashSet};
+
+/// Build the reverse graph: for each app, the apps that directly depend on it
+fn reverse_edges(apps: &HashMap<String, App>) -> HashMap<&str, Vec<&str>> {
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::with_capacity(apps.len());
+    for (app_name, app) in apps {
+        for dep in &app.dependencies {
+            if let Dependency::App(dep_name) = dep {
+                dependents
+                    .entry(dep_name.as_str())
+                    .or_default()
+                    .push(app_name.as_str());
+            }
+        }
+    }
+    dependents
+}
+
+/// Find all apps that depend on a specific app (including transitive dependents)
+pub fn find_dependents(
+    app_name: &str,
+    apps: &HashMap<String, App>,
+) -> Result<Vec<String>, YethError> {
+    if !apps.contains_key(app_name) {
+        return Err(crate::suggest::app_not_found(app_name, apps));
+    }
+
+    let reverse = reverse_edges(apps);
+    let mut visited = HashSet::new();
+    let mut stack = vec![app_name];
+
+    while let Some(current) = stack.pop() {
+        for dependent in reverse.get(current).into_iter().flatten() {
+            if *dependent != app_name && visited.insert(*dependent) {
+                stack.push(dependent);
+            }
+        }
+    }
+
+    let mut result: Vec<String> = visited.into_iter().map(String::from).collect();
+    result.sort();
+    Ok(result)
+}
+
+/// Find apps that no other app depends on, skipping the explicitly requested ones
+pub fn find_orphans(apps: &HashMap<String, App>, requested: &[String]) -> Vec<String> {
+    let reverse = reverse_edges(apps);
+    let mut orphans: Vec<String> = apps
+        .keys()
+        .filter(|app_name| !reverse.contains_key(app_name.as_str()))
+        .filter(|app_name| !requested.contains(app_name))
+        .cloned()
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn app(name: &str, deps: &[&str]) -> (String, App) {
+        (
+            name.to_string(),
+            App {
+                name: name.to_string(),
+                dir: PathBuf::from(format!("/test/{}", name)),
+                dependencies: deps.iter().map(|d| Dependency::App(d.to_string())).collect(),
+                exclude_patterns: vec![],
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_find_dependents() {
+        // common <- backend <- frontend, common <- admin, tools stands alone
+        let apps: HashMap<String, App> = [
+            app("common", &[]),
+            app("backend", &["common"]),
+            app("frontend", &["backend"]),
+            app("admin", &["common"]),
+            app("tools", &[]),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            find_dependents("common", &apps).unwrap(),
+            vec!["admin", "backend", "frontend"]
+        );
+        assert_eq!(find_dependents("backend", &apps).unwrap(), vec!["frontend"]);
+        assert!(find_dependents("frontend", &apps).unwrap().is_empty());
+        assert!(matches!(
+            find_dependents("missing", &apps),
+            Err(YethError::AppNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_find_orphans() {
+        let apps: HashMap<String, App> = [
+            app("common", &[]),
+            app("backend", &["common"]),
+            app("frontend", &["backend"]),
+            app("tools", &[]),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(find_orphans(&apps, &[]), vec!["frontend", "tools"]);
+        assert_eq!(find_orphans(&apps, &["tools".to_string()]), vec!["frontend"]);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::error::YethError;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Run a git command in `dir` and return its stdout
+fn run_git(dir: &Path, args: &[&str]) -> Result<String, YethError> {
+    Ok(String::from_utf8_lossy(&run_git_bytes(dir, args)?).into_owned())
+}
+
+/// Run a git command in `dir` and return its raw stdout
+fn run_git_bytes(dir: &Path, args: &[&str]) -> Result<Vec<u8>, YethError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| YethError::GitError(format!("failed to run git: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(YethError::GitError(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Find the top-level directory of the git repository containing `dir`
+pub fn repository_root(dir: &Path) -> Result<PathBuf, YethError> {
+    let output = run_git(dir, &["rev-parse", "--show-toplevel"])
+        .map_err(|_| YethError::NotAGitRepository(dir.to_path_buf()))?;
+    Ok(PathBuf::from(output.trim()))
+}
+
+/// List files changed on the current branch since it forked from `git_ref` (like
+/// `git diff <ref>...HEAD`), plus uncommitted and untracked files, so commits made
+/// on `git_ref` since don't count. Without a common ancestor, for example in a
+/// shallow clone, files are compared with `git_ref` itself. Returned paths are absolute.
+pub fn changed_files_since(dir: &Path, git_ref: &str) -> Result<Vec<PathBuf>, YethError> {
+    let repo_root = repository_root(dir)?;
+
+    let base = merge_base(&repo_root, git_ref)?.unwrap_or_else(|| git_ref.to_string());
+    // Without renames a moved file is listed under both paths; -z keeps paths unquoted
+    let diff = run_git_bytes(&repo_root, &["diff", "--name-only", "--no-renames", "-z", &base, "--"])?;
+    let untracked = run_git_bytes(&repo_root, &["ls-files", "-z", "--others", "--exclude-standard"])?;
+
+    let mut files: Vec<PathBuf> = diff
+        .split(|byte| *byte == 0)
+        .chain(untracked.split(|byte| *byte == 0))
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| repo_root.join(path_from_bytes(entry)))
+        .collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// The best common ancestor of `git_ref` and HEAD, `None` when there is none
+fn merge_base(repo_root: &Path, git_ref: &str) -> Result<Option<String>, YethError> {
+    match run_git(repo_root, &["merge-base", git_ref, "HEAD"]) {
+        Ok(output) => Ok(Some(output.trim().to_string())),
+        // git exits with 1 and says nothing when the histories don't meet
+        Err(YethError::GitError(message)) if message.is_empty() => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// List files tracked in the git index under `dir` (including newly added ones).
+/// Returned paths are joined onto `dir`.
+pub fn tracked_files(dir: &Path) -> Result<Vec<PathBuf>, YethError> {
+    repository_root(dir)?;
+
+    let output = run_git_bytes(dir, &["ls-files", "-z", "--cached"])?;
+    Ok(output
+        .split(|byte| *byte == 0)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| dir.join(path_from_bytes(entry)))
+        .collect())
+}
+
+/// List the files (blobs) under `path` in the tree of `rev`, with their blob ids and
+/// sizes. Returned paths are joined onto `path`; a committed file is listed as `path` itself.
+pub fn tree_files(path: &Path, rev: &str) -> Result<Vec<(PathBuf, String, u64)>, YethError> {
+    let dir = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+    let repo_root = repository_root(dir)?;
+
+    let canonical_root = repo_root.canonicalize()?;
+    let canonical_path = path.canonicalize()?;
+    let rel_path = canonical_path
+        .strip_prefix(&canonical_root)
+        .map_err(|_| YethError::NotAGitRepository(path.to_path_buf()))?;
+    let pathspec = if rel_path.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        rel_path.to_string_lossy().replace('\\', "/")
+    };
+
+    let output = run_git_bytes(&repo_root, &["ls-tree", "-r", "-l", "-z", rev, "--", &pathspec])?;
+    let mut files = Vec::new();
+    for entry in output.split(|byte| *byte == 0).filter(|entry| !entry.is_empty()) {
+        // `<mode> <type> <oid> <size>\t<path>`, the size padded with spaces
+        let Some(tab) = entry.iter().position(|byte| *byte == b'\t') else {
+            continue;
+        };
+        let meta = String::from_utf8_lossy(&entry[..tab]);
+        let mut fields = meta.split_whitespace();
+        let (Some(_mode), Some(kind), Some(oid), Some(size)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        // Submodules show up as commits and have no content here
+        if kind != "blob" {
+            continue;
+        }
+
+        let entry_path = path_from_bytes(&entry[tab + 1..]);
+        let file = match entry_path.strip_prefix(rel_path) {
+            Ok(rest) if rest.as_os_str().is_empty() => path.to_path_buf(),
+            Ok(rest) => path.join(rest),
+            Err(_) => continue,
+        };
+        files.push((file, oid.to_string(), size.parse().unwrap_or_default()));
+    }
+    Ok(files)
+}
+
+/// Read blob contents through a single `git cat-file --batch`, calling `on_blob`
+/// with the index into `oids` and the content, in order
+pub fn read_blobs<F>(dir: &Path, oids: &[&str], mut on_blob: F) -> Result<(), YethError>
+where
+    F: FnMut(usize, Vec<u8>),
+{
+    if oids.is_empty() {
+        return Ok(());
+    }
+
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| YethError::GitError(format!("failed to run git: {}", e)))?;
+
+    // Feed ids from another thread so a full stdout pipe can't block the writer
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input: String = oids.iter().map(|oid| format!("{}\n", oid)).collect();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let mut reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    for (index, oid) in oids.iter().enumerate() {
+        // `<oid> blob <size>\n<content>\n`, or `<oid> missing\n`
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            let mut stderr = String::new();
+            if let Some(mut child_stderr) = child.stderr.take() {
+                child_stderr.read_to_string(&mut stderr)?;
+            }
+            return Err(YethError::GitError(stderr.trim().to_string()));
+        }
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        let size = match fields.as_slice() {
+            [_, "blob", size] => size.parse::<usize>().ok(),
+            _ => None,
+        }
+        .ok_or_else(|| YethError::GitError(format!("cannot read blob '{}': {}", oid, header.trim())))?;
+
+        let mut content = vec![0; size];
+        reader.read_exact(&mut content)?;
+        let mut newline = [0; 1];
+        reader.read_exact(&mut newline)?;
+        on_blob(index, content);
+    }
+
+    drop(reader);
+    writer
+        .join()
+        .map_err(|_| YethError::GitError("failed to write to git cat-file".to_string()))??;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(YethError::GitError(format!("git cat-file exited with {}", status)));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    // git writes paths as UTF-8 outside Unix
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Run git in a test repository with a fixed identity
+    pub(crate) fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=yeth", "-c", "user.email=yeth@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_changed_files_since() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::write(root.join("committed.txt"), "v1").unwrap();
+        fs::write(root.join("untouched.txt"), "v1").unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-q", "-m", "initial"]);
+
+        fs::write(root.join("committed.txt"), "v2").unwrap();
+        fs::write(root.join("new.txt"), "new").unwrap();
+
+        let changed = changed_files_since(&root, "HEAD").unwrap();
+        assert_eq!(changed, vec![root.join("committed.txt"), root.join("new.txt")]);
+    }
+
+    #[test]
+    fn test_changed_files_since_fork_point() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::write(root.join("feature.txt"), "v1").unwrap();
+        fs::write(root.join("upstream.txt"), "v1").unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-q", "-m", "initial"]);
+        git(&root, &["branch", "upstream"]);
+
+        // The branch changes one file, then upstream moves on with another
+        fs::write(root.join("feature.txt"), "v2").unwrap();
+        git(&root, &["commit", "-q", "-am", "feature"]);
+        git(&root, &["checkout", "-q", "upstream"]);
+        fs::write(root.join("upstream.txt"), "v2").unwrap();
+        git(&root, &["commit", "-q", "-am", "upstream"]);
+        git(&root, &["checkout", "-q", "-"]);
+
+        let changed = changed_files_since(&root, "upstream").unwrap();
+        assert_eq!(changed, vec![root.join("feature.txt")]);
+
+        assert!(matches!(
+            changed_files_since(&root, "no-such-branch"),
+            Err(YethError::GitError(message)) if !message.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_changed_files_since_fork_point_rename() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("café")).unwrap();
+        fs::write(root.join("a").join("big.txt"), "content\n".repeat(100)).unwrap();
+        fs::write(root.join("upstream.txt"), "v1").unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-q", "-m", "initial"]);
+        git(&root, &["branch", "upstream"]);
+
+        // The branch moves a file into a non-ASCII directory, then upstream moves on
+        git(&root, &["mv", "a/big.txt", "café/big.txt"]);
+        git(&root, &["commit", "-q", "-m", "move"]);
+        git(&root, &["checkout", "-q", "upstream"]);
+        fs::write(root.join("upstream.txt"), "v2").unwrap();
+        git(&root, &["commit", "-q", "-am", "upstream"]);
+        git(&root, &["checkout", "-q", "-"]);
+
+        let changed = changed_files_since(&root, "upstream").unwrap();
+        assert_eq!(changed, vec![root.join("a").join("big.txt"), root.join("café").join("big.txt")]);
+    }
+
+    #[test]
+    fn test_changed_files_since_rename() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("a").join("big.txt"), "content\n".repeat(100)).unwrap();
+        fs::write(root.join("b").join("keep.txt"), "keep").unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-q", "-m", "initial"]);
+
+        // A file moved out of `a` changes both apps
+        git(&root, &["mv", "a/big.txt", "b/big.txt"]);
+
+        let changed = changed_files_since(&root, "HEAD").unwrap();
+        assert_eq!(changed, vec![root.join("a").join("big.txt"), root.join("b").join("big.txt")]);
+    }
+
+    #[test]
+    fn test_changed_files_since_non_ascii_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("café")).unwrap();
+        fs::write(root.join("café").join("menu.txt"), "v1").unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-q", "-m", "initial"]);
+
+        fs::write(root.join("café").join("menu.txt"), "v2").unwrap();
+        fs::write(root.join("café").join("crème.txt"), "new").unwrap();
+
+        let changed = changed_files_since(&root, "HEAD").unwrap();
+        assert_eq!(
+            changed,
+            vec![root.join("café").join("crème.txt"), root.join("café").join("menu.txt")]
+        );
+    }
+
+    #[test]
+    fn test_changed_files_outside_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let result = changed_files_since(temp_dir.path(), "HEAD");
+        assert!(matches!(result, Err(YethError::NotAGitRepository(_))));
+    }
+}
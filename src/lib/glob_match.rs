@@ -0,0 +1,94 @@
+/// Characters that make a pattern a glob rather than a plain name
+pub const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// Check if a pattern uses glob syntax
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(GLOB_CHARS)
+}
+
+/// Match `text` against a glob: `*` matches within one path component, `**` matches
+/// across components, `?` matches one character and `[abc]`/`[a-z]`/`[!a]` match a set
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => {
+            let crosses_separators = pattern.get(1) == Some(&'*');
+            let rest = if crosses_separators { &pattern[2..] } else { &pattern[1..] };
+            // `**/` also matches zero directories
+            if crosses_separators && rest.first() == Some(&'/') && match_from(&rest[1..], text) {
+                return true;
+            }
+            for skip in 0..=text.len() {
+                if match_from(rest, &text[skip..]) {
+                    return true;
+                }
+                if skip < text.len() && !crosses_separators && text[skip] == '/' {
+                    return false;
+                }
+            }
+            false
+        }
+        Some('?') => !text.is_empty() && text[0] != '/' && match_from(&pattern[1..], &text[1..]),
+        Some('[') => match match_class(&pattern[1..], text.first().copied()) {
+            Some((true, class_len)) => match_from(&pattern[1 + class_len..], &text[1..]),
+            Some((false, _)) => false,
+            // Unterminated class: treat `[` literally
+            None => text.first() == Some(&'[') && match_from(&pattern[1..], &text[1..]),
+        },
+        Some(c) => text.first() == Some(c) && match_from(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Match one character against a `[...]` class starting after the `[`.
+/// Returns whether it matched and the class length including the closing `]`.
+fn match_class(class: &[char], c: Option<char>) -> Option<(bool, usize)> {
+    let end = class.iter().skip(1).position(|ch| *ch == ']')? + 1;
+    let c = match c {
+        Some(c) if c != '/' => c,
+        _ => return Some((false, end + 1)),
+    };
+
+    let (negated, items) = match class[0] {
+        '!' | '^' => (true, &class[1..end]),
+        _ => (false, &class[..end]),
+    };
+    let mut matched = false;
+    let mut i = 0;
+    while i < items.len() {
+        if i + 2 < items.len() && items[i + 1] == '-' {
+            matched |= items[i] <= c && c <= items[i + 2];
+            i += 3;
+        } else {
+            matched |= items[i] == c;
+            i += 1;
+        }
+    }
+    Some((matched != negated, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.log", "debug.log"));
+        assert!(!glob_match("*.log", "logs/debug.log"));
+        assert!(glob_match("**/*.log", "logs/debug.log"));
+        assert!(glob_match("**/*.log", "debug.log"));
+        assert!(glob_match("build/**", "build/out/app.bin"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file10.txt"));
+        assert!(glob_match("[abc].rs", "b.rs"));
+        assert!(glob_match("v[0-9].txt", "v7.txt"));
+        assert!(!glob_match("[!a].rs", "a.rs"));
+        assert!(glob_match("node_modules", "node_modules"));
+        assert!(!glob_match("node_modules", "node_modules2"));
+    }
+}
@@ -1,97 +1,745 @@
-use crate::cfg::ExcludePattern;
+use crate::cfg::{hashed_config_content, Config, ExcludePattern, CONFIG_FILE, VERSION_FILE};
 use crate::error::YethError;
+use crate::glob_match::glob_match;
+use crate::hash_file::{hash_bytes, hash_file};
+use crate::run_stats::RunStats;
 use sha2::{Digest, Sha256};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+#[cfg(unix)]
+use std::time::SystemTime;
+use unicode_normalization::UnicodeNormalization;
+use walkdir::{DirEntry, WalkDir};
 
-/// Compute SHA256 hash for a directory by hashing all files in it
-pub fn hash_directory(path: &PathBuf, exclude: &[ExcludePattern]) -> Result<String, YethError> {
+/// Compute SHA256 hash for a directory from the digests of all files in it.
+/// With `normalize_eol`, CRLF is hashed as LF in files that look like text.
+pub fn hash_directory(
+    path: &Path,
+    exclude: &[ExcludePattern],
+    normalize_eol: bool,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<String, YethError> {
+    hash_directory_observed(path, exclude, normalize_eol, config, stats, |_, _| {}).map(|hashed| hashed.hash)
+}
+
+/// A directory hash with the number of files that went into it and their total size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryHash {
+    pub hash: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Like `hash_directory`, calling `on_file` with each file and its digest in the
+/// order they are fed into the hash, and counting the files and their bytes
+pub fn hash_directory_observed<F>(
+    path: &Path,
+    exclude: &[ExcludePattern],
+    normalize_eol: bool,
+    config: &Config,
+    stats: &RunStats,
+    mut on_file: F,
+) -> Result<DirectoryHash, YethError>
+where
+    F: FnMut(&Path, &str),
+{
     let mut hasher = Sha256::new();
-    let mut files: Vec<PathBuf> = WalkDir::new(path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| {
-            if !e.file_type().is_file() {
-                return false;
-            }
 
-            let entry_path = e.path();
+    if let Some(rev) = &config.git_rev {
+        let files = collect_tree_files(path, rev, exclude, config, stats)?;
+        let mut digests: Vec<Option<String>> = files
+            .iter()
+            .map(|(file, oid, _)| {
+                let key = (!is_config_file(path, file)).then(|| FileKey::blob(oid, normalize_eol))?;
+                stats.cached_digest(&key)
+            })
+            .collect();
 
-            if entry_path
-                .file_name()
-                .is_some_and(|n| n == ".git" || n == ".DS_Store" || n == "yeth.version")
-            {
-                return false;
-            }
+        // Read each blob not seen yet in this run once; identical copies share its digest
+        let mut unread_oids = HashSet::new();
+        let to_read: Vec<usize> = (0..files.len())
+            .filter(|&index| {
+                digests[index].is_none()
+                    && (is_config_file(path, &files[index].0) || unread_oids.insert(files[index].1.as_str()))
+            })
+            .collect();
+        let oids: Vec<&str> = to_read.iter().map(|&index| files[index].1.as_str()).collect();
+        crate::git::read_blobs(path, &oids, |read_index, content| {
+            let index = to_read[read_index];
+            let (file, oid, _) = &files[index];
+            let digest = if is_config_file(path, file) {
+                hash_bytes(&hashed_config_content(content), normalize_eol, stats)
+            } else {
+                let digest = hash_bytes(&content, normalize_eol, stats);
+                stats.store_digest(FileKey::blob(oid, normalize_eol), digest.clone());
+                digest
+            };
+            digests[index] = Some(digest);
+        })?;
+
+        let mut bytes = 0;
+        for ((file, oid, size), digest) in files.iter().zip(digests) {
+            // Missing when git cat-file returned fewer blobs than were requested
+            let digest = digest
+                .or_else(|| stats.cached_digest(&FileKey::blob(oid, normalize_eol)))
+                .ok_or_else(|| {
+                    YethError::GitError(format!("git cat-file returned no content for '{}' ({})", file.display(), oid))
+                })?;
+            update_with_digest(&mut hasher, path, file, &digest);
+            on_file(file, &digest);
+            bytes += size;
+        }
+        return Ok(DirectoryHash {
+            hash: format!("{:x}", hasher.finalize()),
+            files: files.len() as u64,
+            bytes,
+        });
+    }
 
-            if should_exclude(entry_path, path, exclude) {
-                return false;
+    let mut files = collect_files(path, exclude, config, stats)?;
+    files.sort_by_cached_key(|file| normalized_components(file.strip_prefix(path).unwrap_or(file)));
+    let keys: Vec<Option<FileKey>> = files
+        .iter()
+        .map(|file| FileKey::for_file(file, normalize_eol, config))
+        .collect();
+
+    // The same files, all unchanged since the last time: the hash is known already
+    let directory_key = DirectoryKey::new(path, exclude, normalize_eol);
+    if let Some(listing) = stats.cached_directory(&directory_key)
+        && listing.matches(&files, &keys)
+    {
+        stats.record_reused_files(listing.files.len() as u64);
+        for (file, _, digest) in &listing.files {
+            on_file(file, digest);
+        }
+        return Ok(listing.directory_hash());
+    }
+
+    let mut entries = Vec::with_capacity(files.len());
+    let mut complete = true;
+    let (mut count, mut bytes) = (0, 0);
+    for (file, key) in files.into_iter().zip(keys) {
+        let digest = match working_tree_digest(path, &file, key.as_ref(), normalize_eol, config, stats) {
+            Ok(digest) => digest,
+            Err(YethError::HashIoError { path: unreadable, source }) if config.skip_unreadable => {
+                // Left out of the hash entirely, as if the file weren't there
+                let shown = config.display_path(&unreadable);
+                config.warn(format_args!("skipping unreadable file '{}': {}", shown.display(), source));
+                stats.record_skipped_file();
+                complete = false;
+                continue;
             }
+            Err(error) => return Err(error),
+        };
+        update_with_digest(&mut hasher, path, &file, &digest);
+        on_file(&file, &digest);
+        count += 1;
+        bytes += file_size(&file, key.as_ref(), config);
+        match key {
+            Some(key) => entries.push((file, key, digest)),
+            None => complete = false,
+        }
+    }
+    let hashed = DirectoryHash {
+        hash: format!("{:x}", hasher.finalize()),
+        files: count,
+        bytes,
+    };
+    // Only a listing of settled, readable files can be recognized again
+    if complete {
+        stats.store_directory(
+            directory_key,
+            DirectoryListing {
+                files: entries,
+                hash: hashed.hash.clone(),
+            },
+        );
+    }
+    Ok(hashed)
+}
+
+/// Size of a hashed file, from its key when it has one
+fn file_size(file: &Path, key: Option<&FileKey>, config: &Config) -> u64 {
+    if let Some(FileKey::Inode { size, .. }) = key {
+        return *size;
+    }
+    let metadata = if config.follow_symlinks {
+        fs::metadata(file)
+    } else {
+        fs::symlink_metadata(file)
+    };
+    metadata.map(|metadata| metadata.len()).unwrap_or_default()
+}
+
+/// Digest of one file found in directory `base` of the working tree
+fn working_tree_digest(
+    base: &Path,
+    file: &Path,
+    key: Option<&FileKey>,
+    normalize_eol: bool,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<String, YethError> {
+    let io_error = |source| YethError::HashIoError {
+        path: file.to_path_buf(),
+        source,
+    };
+    if is_config_file(base, file) {
+        let content = fs::read(file).map_err(io_error)?;
+        Ok(hash_bytes(&hashed_config_content(content), normalize_eol, stats))
+    } else if config.hash_symlink_targets && fs::symlink_metadata(file).map_err(io_error)?.file_type().is_symlink() {
+        // The target path string, as git stores a symlink
+        let target = fs::read_link(file).map_err(io_error)?;
+        Ok(hash_bytes(&os_str_bytes(target.as_os_str()), false, stats))
+    } else {
+        digest_with_key(file, key, normalize_eol, config, stats)
+    }
+}
+
+/// Feed one file into a directory hash. Each file contributes its length-prefixed
+/// relative path and the digest of its content, so moving content between files or
+/// renaming a file changes the hash.
+fn update_with_digest(hasher: &mut Sha256, base: &Path, file: &Path, digest: &str) {
+    let rel_path = file.strip_prefix(base).unwrap_or(file);
+    let path_bytes = path_bytes(rel_path);
+    hasher.update((path_bytes.len() as u64).to_le_bytes());
+    hasher.update(&path_bytes);
+    hasher.update(digest.as_bytes());
+}
+
+/// The app's own yeth.toml, hashed without its metadata
+fn is_config_file(base: &Path, file: &Path) -> bool {
+    file.strip_prefix(base).is_ok_and(|rel_path| rel_path == Path::new(CONFIG_FILE))
+}
+
+/// Digest of a file's content, reused when the same unchanged file was already
+/// hashed in this run (for example a path dependency shared by several apps)
+fn cached_file_digest(
+    file: &Path,
+    normalize_eol: bool,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<String, YethError> {
+    let key = FileKey::for_file(file, normalize_eol, config);
+    digest_with_key(file, key.as_ref(), normalize_eol, config, stats)
+}
+
+/// `cached_file_digest` with the file's key already looked up
+fn digest_with_key(
+    file: &Path,
+    key: Option<&FileKey>,
+    normalize_eol: bool,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<String, YethError> {
+    if let Some(digest) = key.and_then(|key| stats.cached_digest(key)) {
+        return Ok(digest);
+    }
+    let digest = hash_file(file, normalize_eol, config, stats)?;
+    if let Some(key) = key {
+        stats.store_digest(key.clone(), digest.clone());
+    }
+    Ok(digest)
+}
+
+/// A directory hashed with given settings, see `DirectoryListing`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DirectoryKey {
+    path: PathBuf,
+    exclude: Vec<ExcludePattern>,
+    normalize_eol: bool,
+}
 
-            true
+impl DirectoryKey {
+    fn new(path: &Path, exclude: &[ExcludePattern], normalize_eol: bool) -> Self {
+        Self {
+            path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+            exclude: exclude.to_vec(),
+            normalize_eol,
+        }
+    }
+}
+
+/// The files of a directory hashed earlier in the run, each with its key and
+/// digest, and the resulting hash. When the directory is hashed again and lists
+/// the same files with the same keys, the hash is reused as is; otherwise only
+/// files whose key changed are read again and the hash is recombined.
+#[derive(Debug)]
+pub(crate) struct DirectoryListing {
+    files: Vec<(PathBuf, FileKey, String)>,
+    hash: String,
+}
+
+impl DirectoryListing {
+    fn directory_hash(&self) -> DirectoryHash {
+        let bytes = self
+            .files
+            .iter()
+            .map(|(_, key, _)| match key {
+                FileKey::Inode { size, .. } => *size,
+                FileKey::Blob { .. } => 0,
+            })
+            .sum();
+        DirectoryHash {
+            hash: self.hash.clone(),
+            files: self.files.len() as u64,
+            bytes,
+        }
+    }
+
+    fn matches(&self, files: &[PathBuf], keys: &[Option<FileKey>]) -> bool {
+        self.files.len() == files.len()
+            && self
+                .files
+                .iter()
+                .zip(files.iter().zip(keys))
+                .all(|((cached_file, cached_key, _), (file, key))| {
+                    cached_file == file && key.as_ref() == Some(cached_key)
+                })
+    }
+}
+
+/// Files modified more recently than this are never cached, see `FileKey::for_file`
+#[cfg(unix)]
+const RACY_WINDOW: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// What makes two files' digests interchangeable within a run
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum FileKey {
+    /// The same file on disk, unchanged: device, inode, size and modification time
+    Inode {
+        dev: u64,
+        ino: u64,
+        size: u64,
+        mtime: (i64, i64),
+        normalize_eol: bool,
+    },
+    /// A git blob: equal ids mean equal content
+    Blob { oid: String, normalize_eol: bool },
+}
+
+impl FileKey {
+    fn blob(oid: &str, normalize_eol: bool) -> Self {
+        FileKey::Blob {
+            oid: oid.to_string(),
+            normalize_eol,
+        }
+    }
+
+    #[cfg(unix)]
+    fn for_file(file: &Path, normalize_eol: bool, config: &Config) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = if config.follow_symlinks {
+            fs::metadata(file)
+        } else {
+            fs::symlink_metadata(file)
+        };
+        let m = metadata.ok()?;
+        // Like git's "racy" check: a file modified moments ago may change again within
+        // the same timestamp tick, so only settled files are cached
+        let age = m.modified().ok().and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        if age.is_none_or(|age| age < RACY_WINDOW) {
+            return None;
+        }
+        Some(FileKey::Inode {
+            dev: m.dev(),
+            ino: m.ino(),
+            size: m.size(),
+            mtime: (m.mtime(), m.mtime_nsec()),
+            normalize_eol,
         })
-        .map(|e| e.path().to_path_buf())
+    }
+
+    #[cfg(not(unix))]
+    fn for_file(_file: &Path, _normalize_eol: bool, _config: &Config) -> Option<Self> {
+        // Without inode numbers a file can't be recognized cheaply, so it is always read
+        None
+    }
+}
+
+/// List the files of a directory that take part in its hash, plus symlinks that
+/// aren't followed when their targets are hashed
+fn collect_files(
+    path: &Path,
+    exclude: &[ExcludePattern],
+    config: &Config,
+    stats: &RunStats,
+) -> Result<Vec<PathBuf>, YethError> {
+    let patterns = all_exclude_patterns(path, exclude, config);
+    let matcher = ExcludeMatcher::new(path, &patterns);
+    let hashes_link_targets = config.hash_symlink_targets && !config.follow_symlinks;
+
+    if config.tracked_only {
+        let files = crate::git::tracked_files(path)?
+            .into_iter()
+            .filter(|file| {
+                let is_file = if config.follow_symlinks {
+                    file.is_file()
+                } else {
+                    fs::symlink_metadata(file).is_ok_and(|m| m.is_file() || (hashes_link_targets && m.is_symlink()))
+                };
+                is_file && keep_listed_file(file, path, &matcher, config)
+            })
+            .collect();
+        stats.record_matched_excludes(path, matcher.matched_patterns());
+        return Ok(files);
+    }
+
+    let mut files = Vec::new();
+    for entry in walk(path, &matcher, config) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // Loops and dangling links met while following symlinks have nothing to hash
+            Err(error) if error.loop_ancestor().is_some() => continue,
+            Err(error) if config.follow_symlinks && error.io_error().is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) => {
+                continue;
+            }
+            Err(error) => {
+                let unreadable = error.path().unwrap_or(path).to_path_buf();
+                if config.skip_unreadable {
+                    let shown = config.display_path(&unreadable);
+                    config.warn(format_args!("skipping unreadable directory '{}': {}", shown.display(), error));
+                    continue;
+                }
+                return Err(YethError::HashWalkError {
+                    path: unreadable,
+                    source: error.into(),
+                });
+            }
+        };
+        let file_type = entry.file_type();
+        if (file_type.is_file() || (hashes_link_targets && file_type.is_symlink())) && !is_ignored_file(entry.path()) {
+            files.push(entry.into_path());
+        }
+    }
+    stats.record_matched_excludes(path, matcher.matched_patterns());
+    Ok(files)
+}
+
+/// List the files of a directory as committed in `rev`, with their blob ids, sorted by path
+fn collect_tree_files(
+    path: &Path,
+    rev: &str,
+    exclude: &[ExcludePattern],
+    config: &Config,
+    stats: &RunStats,
+) -> Result<Vec<(PathBuf, String, u64)>, YethError> {
+    let patterns = all_exclude_patterns(path, exclude, config);
+    let matcher = ExcludeMatcher::new(path, &patterns);
+
+    let mut files: Vec<(PathBuf, String, u64)> = crate::git::tree_files(path, rev)?
+        .into_iter()
+        .filter(|(file, _, _)| keep_listed_file(file, path, &matcher, config))
         .collect();
-    files.sort();
+    stats.record_matched_excludes(path, matcher.matched_patterns());
+    files.sort_by_cached_key(|(file, _, _)| normalized_components(file.strip_prefix(path).unwrap_or(file)));
+    Ok(files)
+}
 
-    for file in files {
-        let content = fs::read(&file)?;
-        hasher.update(&content);
+/// Root-level .yethignore patterns apply on top of the own excludes of anything inside the root
+fn all_exclude_patterns(path: &Path, exclude: &[ExcludePattern], config: &Config) -> Vec<ExcludePattern> {
+    // The root's .yethignore describes the root, so it doesn't reach outside it
+    let ignore_patterns: &[ExcludePattern] = if config.is_external(path) { &[] } else { &config.ignore_patterns };
+    exclude.iter().chain(ignore_patterns).cloned().collect()
+}
+
+/// Filter for files listed by git rather than found by walking the directory
+fn keep_listed_file(file: &Path, base: &Path, matcher: &ExcludeMatcher, config: &Config) -> bool {
+    let rel_path = file.strip_prefix(base).unwrap_or(file);
+    let within_depth = config
+        .max_depth
+        .is_none_or(|max_depth| rel_path.components().count() <= max_depth);
+    within_depth && !is_ignored_file(file) && !matcher.is_excluded(file)
+}
+
+/// Files never included in a hash
+fn is_ignored_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|n| n == ".git" || n == ".DS_Store" || n == VERSION_FILE)
+}
+
+/// Raw bytes of a relative path with `/` between components, without lossy UTF-8 conversion
+fn path_bytes(path: &Path) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (i, component) in normalized_components(path).iter().enumerate() {
+        if i > 0 {
+            bytes.push(b'/');
+        }
+        bytes.extend(os_str_bytes(component));
     }
-    Ok(format!("{:x}", hasher.finalize()))
+    bytes
+}
+
+/// Path components in Unicode NFC, so a name stored decomposed (as macOS does) hashes
+/// and sorts like the composed form other platforms use. Names that aren't valid
+/// Unicode are kept as they are. Also the sort key of files in a directory hash.
+fn normalized_components(path: &Path) -> Vec<OsString> {
+    path.components()
+        .map(|component| {
+            let name = component.as_os_str();
+            match name.to_str() {
+                Some(text) if !text.is_ascii() => OsString::from(text.nfc().collect::<String>()),
+                _ => name.to_os_string(),
+            }
+        })
+        .collect()
 }
 
-/// Compute hash for a path (file or directory)
-pub fn hash_path(path: &Path, exclude: &[ExcludePattern]) -> Result<String, YethError> {
-    if path.is_file() {
-        crate::hash_file::hash_file(path)
-    } else if path.is_dir() {
-        hash_directory(&path.to_path_buf(), exclude)
+#[cfg(unix)]
+fn os_str_bytes(value: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    value.as_bytes().to_vec()
+}
+
+#[cfg(windows)]
+fn os_str_bytes(value: &OsStr) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+    value.encode_wide().flat_map(|unit| unit.to_le_bytes()).collect()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn os_str_bytes(value: &OsStr) -> Vec<u8> {
+    value.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Compute hash for a path (file or directory), a dependency of `app_name`.
+/// Symlinks are followed; the path is expected to have been checked to exist.
+pub fn hash_path(
+    path: &Path,
+    app_name: &str,
+    exclude: &[ExcludePattern],
+    normalize_eol: bool,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<String, YethError> {
+    if let Some(rev) = &config.git_rev {
+        // A committed file is listed as itself; anything else is hashed as a directory
+        if let [(file, oid, _)] = crate::git::tree_files(path, rev)?.as_slice()
+            && file == path
+        {
+            let mut hash = String::new();
+            let dir = path.parent().unwrap_or(path);
+            crate::git::read_blobs(dir, &[oid.as_str()], |_, content| {
+                hash = crate::hash_file::hash_bytes(&content, normalize_eol, stats);
+            })?;
+            return Ok(hash);
+        }
+        return hash_directory(path, exclude, normalize_eol, config, stats);
+    }
+
+    let metadata = fs::metadata(path).map_err(|error| YethError::path_dependency(path, app_name, error, true))?;
+    if metadata.is_file() {
+        cached_file_digest(path, normalize_eol, config, stats)
+    } else if metadata.is_dir() {
+        hash_directory(path, exclude, normalize_eol, config, stats)
     } else {
         Err(YethError::NorFileOrDirectory(path.to_path_buf()))
     }
 }
 
+/// Walk a directory, pruning excluded subtrees instead of filtering their files one by one
+fn walk<'a>(
+    path: &Path,
+    matcher: &'a ExcludeMatcher<'a>,
+    config: &'a Config,
+) -> impl Iterator<Item = walkdir::Result<DirEntry>> + 'a {
+    let mut visited_dirs = HashSet::new();
+    // Sorted, so which of several links to one directory gets entered doesn't depend
+    // on the filesystem's directory order
+    let mut walker = WalkDir::new(path).follow_links(config.follow_symlinks).sort_by_file_name();
+    if let Some(max_depth) = config.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    walker.into_iter().filter_entry(move |e| {
+        let excluded = if e.file_type().is_dir() {
+            matcher.is_excluded_dir(e.path())
+        } else {
+            matcher.is_excluded(e.path())
+        };
+        if e.depth() > 0 && excluded {
+            return false;
+        }
+        // When following links, enter each physical directory only once
+        if !config.follow_symlinks || !e.file_type().is_dir() {
+            return true;
+        }
+        directory_id(e).is_none_or(|id| visited_dirs.insert(id))
+    })
+}
+
+/// Identify a directory by device and inode so symlink loops are detected
+#[cfg(unix)]
+fn directory_id(entry: &DirEntry) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    entry.metadata().ok().map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn directory_id(_entry: &DirEntry) -> Option<(u64, u64)> {
+    // WalkDir's own ancestor check still catches loops here
+    None
+}
+
 /// Check if a path should be excluded based on exclusion patterns
-fn should_exclude(path: &Path, base_dir: &Path, exclude_patterns: &[ExcludePattern]) -> bool {
-    if exclude_patterns.is_empty() {
-        return false;
+pub(crate) fn should_exclude(
+    path: &Path,
+    base_dir: &Path,
+    exclude_patterns: &[ExcludePattern],
+) -> bool {
+    ExcludeMatcher::new(base_dir, exclude_patterns).is_excluded(path)
+}
+
+/// Match a glob against a path: a glob with `/` is matched against the path and each
+/// of its parent directories, a glob without one against every component name
+fn glob_matches_path(glob: &str, path: &Path) -> bool {
+    if glob.contains(['/', '\\']) {
+        glob_matches_anchored(glob, path)
+    } else {
+        path.components()
+            .any(|c| glob_match(glob, &c.as_os_str().to_string_lossy()))
+    }
+}
+
+/// Match a glob relative to the base directory against a path and each of its parents,
+/// so a directory pattern covers the whole subtree
+fn glob_matches_anchored(glob: &str, path: &Path) -> bool {
+    let components: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let glob = glob.replace('\\', "/");
+    // A leading `/` only anchors the glob to the base directory, which it already is
+    let glob = glob.trim_start_matches('/');
+    (1..=components.len()).any(|len| glob_match(glob, &components[..len].join("/")))
+}
+
+/// Exclusion patterns bound to a base directory, which is canonicalized once
+/// instead of canonicalizing every checked path. Remembers which patterns
+/// excluded something.
+pub(crate) struct ExcludeMatcher<'a> {
+    base_dir: &'a Path,
+    canonical_base: PathBuf,
+    patterns: &'a [ExcludePattern],
+    matched: Vec<Cell<bool>>,
+}
+
+impl<'a> ExcludeMatcher<'a> {
+    pub(crate) fn new(base_dir: &'a Path, patterns: &'a [ExcludePattern]) -> Self {
+        let canonical_base = if patterns.is_empty() {
+            base_dir.to_path_buf()
+        } else {
+            base_dir.canonicalize().unwrap_or_else(|_| base_dir.to_path_buf())
+        };
+        Self {
+            base_dir,
+            canonical_base,
+            patterns,
+            matched: patterns.iter().map(|_| Cell::new(false)).collect(),
+        }
+    }
+
+    /// Patterns that excluded at least one checked path so far. A path is only
+    /// credited to the first pattern excluding it.
+    pub(crate) fn matched_patterns(&self) -> impl Iterator<Item = &ExcludePattern> {
+        self.patterns
+            .iter()
+            .zip(&self.matched)
+            .filter(|(_, matched)| matched.get())
+            .map(|(pattern, _)| pattern)
+    }
+
+    pub(crate) fn is_excluded(&self, path: &Path) -> bool {
+        self.matches(path, false)
     }
 
-    let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    /// Like `is_excluded` for a directory: include lists never rule out a directory,
+    /// since included files may still be found below it
+    pub(crate) fn is_excluded_dir(&self, path: &Path) -> bool {
+        self.matches(path, true)
+    }
 
-    for pattern in exclude_patterns {
-        match pattern {
-            ExcludePattern::Name(name) => {
-                let name_str = name.as_str();
-                for component in path.components() {
-                    if component.as_os_str().to_string_lossy() == name_str {
-                        return true;
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        match self.matching_pattern(path, is_dir) {
+            Some(index) => {
+                self.matched[index].set(true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Index of the first pattern excluding `path`
+    fn matching_pattern(&self, path: &Path, is_dir: bool) -> Option<usize> {
+        if self.patterns.is_empty() {
+            return None;
+        }
+
+        let rel_path = path.strip_prefix(self.base_dir).ok();
+        let canonical_path = match rel_path {
+            Some(rel_path) => self.canonical_base.join(rel_path),
+            None => path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+        };
+        // Names only match below the base dir, not in the directories containing it
+        let named_path = rel_path.unwrap_or(path);
+
+        for (index, pattern) in self.patterns.iter().enumerate() {
+            match pattern {
+                ExcludePattern::Name(name) => {
+                    if named_path.components().any(|c| c.as_os_str() == OsStr::new(name)) {
+                        return Some(index);
                     }
                 }
-            }
-            ExcludePattern::AbsolutePath(abs_path) => {
-                if canonical_path == *abs_path || canonical_path.starts_with(abs_path) {
-                    return true;
+                ExcludePattern::AbsolutePath(abs_path) => {
+                    if canonical_path.starts_with(abs_path) {
+                        return Some(index);
+                    }
+                }
+                ExcludePattern::Glob(glob) => {
+                    if glob_matches_path(glob, named_path) {
+                        return Some(index);
+                    }
+                }
+                ExcludePattern::OtherExtension(extensions) => {
+                    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+                    if !is_dir && !extension.is_some_and(|e| extensions.contains(&e)) {
+                        return Some(index);
+                    }
+                }
+                ExcludePattern::NotIncluded(include) => {
+                    if let Some(rel_path) = rel_path
+                        && !is_dir
+                        && !include.iter().any(|glob| glob_matches_anchored(glob, rel_path))
+                    {
+                        return Some(index);
+                    }
                 }
             }
         }
-    }
 
-    if let Ok(rel_path) = path.strip_prefix(base_dir) {
-        let rel_path_str = rel_path.to_string_lossy();
-        for pattern in exclude_patterns {
-            if let ExcludePattern::Name(name) = pattern {
-                let name_str = name.as_str();
-                if rel_path_str.starts_with(name_str) || rel_path_str == name_str {
-                    return true;
+        if let Some(rel_path) = rel_path {
+            // Compare with `/` separators so `src\generated` and `src/generated` match alike
+            let rel_path_str = rel_path.to_string_lossy().replace('\\', "/");
+            for (index, pattern) in self.patterns.iter().enumerate() {
+                if let ExcludePattern::Name(name) = pattern {
+                    // Whole components only, so `.env` leaves `.envrc` in
+                    let name_str = name.replace('\\', "/");
+                    let name_str = name_str.trim_end_matches('/');
+                    let below = rel_path_str
+                        .strip_prefix(name_str)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+                    if below {
+                        return Some(index);
+                    }
                 }
             }
         }
-    }
 
-    false
+        None
+    }
 }
 
 #[cfg(test)]
@@ -100,6 +748,10 @@ mod tests {
     use std::fs;
     use tempfile::tempdir;
 
+    fn config() -> Config {
+        Config::builder().build().unwrap()
+    }
+
     #[test]
     fn test_hash_directory() {
         // Create a temporary directory for testing
@@ -119,7 +771,7 @@ mod tests {
         fs::write(&file3_path, "Nested file").expect("Failed to write file3");
         
         // Hash the directory
-        let hash_result = hash_directory(&dir_path.to_path_buf(), &[]);
+        let hash_result = hash_directory(dir_path, &[], false, &config(), &RunStats::default());
         assert!(hash_result.is_ok(), "Failed to hash directory: {:?}", hash_result.err());
         
         let hash = hash_result.unwrap();
@@ -129,14 +781,14 @@ mod tests {
         assert!(hash.chars().all(|c| c.is_ascii_hexdigit()), "Hash should contain only hex characters");
         
         // Test that the same directory produces the same hash
-        let hash_result2 = hash_directory(&dir_path.to_path_buf(), &[]);
+        let hash_result2 = hash_directory(dir_path, &[], false, &config(), &RunStats::default());
         assert!(hash_result2.is_ok());
         let hash2 = hash_result2.unwrap();
         assert_eq!(hash, hash2, "Same directory should produce the same hash");
         
         // Test that modifying a file changes the hash
         fs::write(&file1_path, "Modified content").expect("Failed to modify file1");
-        let hash_result3 = hash_directory(&dir_path.to_path_buf(), &[]);
+        let hash_result3 = hash_directory(dir_path, &[], false, &config(), &RunStats::default());
         assert!(hash_result3.is_ok());
         let hash3 = hash_result3.unwrap();
         assert_ne!(hash, hash3, "Modified directory should produce different hash");
@@ -161,21 +813,631 @@ mod tests {
         fs::write(&lib_file, "Library code").expect("Failed to write lib file");
         
         // Hash without exclusions
-        let hash_all = hash_directory(&dir_path.to_path_buf(), &[]).unwrap();
+        let hash_all = hash_directory(dir_path, &[], false, &config(), &RunStats::default()).unwrap();
         
         // Hash with name exclusion
         let exclude_patterns = vec![ExcludePattern::Name("node_modules".to_string())];
-        let hash_excluded = hash_directory(&dir_path.to_path_buf(), &exclude_patterns).unwrap();
+        let hash_excluded = hash_directory(dir_path, &exclude_patterns, false, &config(), &RunStats::default()).unwrap();
         
         // Hashes should be different when excluding files
         assert_ne!(hash_all, hash_excluded, "Hashes should be different when excluding files");
         
         // Test with absolute path exclusion
         let abs_exclude_patterns = vec![ExcludePattern::AbsolutePath(node_modules.clone())];
-        let hash_abs_excluded = hash_directory(&dir_path.to_path_buf(), &abs_exclude_patterns).unwrap();
+        let hash_abs_excluded = hash_directory(dir_path, &abs_exclude_patterns, false, &config(), &RunStats::default()).unwrap();
         
         // Should be the same as name exclusion
         assert_eq!(hash_excluded, hash_abs_excluded, "Name and absolute path exclusion should produce same result");
+        // Counts cover only the files left after exclusions
+        let counted = |exclude: &[ExcludePattern]| {
+            let hashed = hash_directory_observed(dir_path, exclude, false, &config(), &RunStats::default(), |_, _| {}).unwrap();
+            (hashed.files, hashed.bytes)
+        };
+        assert_eq!(counted(&[]), (3, 37));
+        assert_eq!(counted(&exclude_patterns), (2, 25));
+    }
+
+    #[test]
+    fn test_hash_directory_unicode_normalization() {
+        // "é" composed (Linux, Windows) and decomposed (macOS)
+        let nfc = "caf\u{e9}.txt";
+        let nfd = "cafe\u{301}.txt";
+        assert_ne!(nfc, nfd);
+
+        let hash_with = |name: &str| {
+            let temp_dir = tempdir().unwrap();
+            fs::write(temp_dir.path().join(name), "bonjour").unwrap();
+            // Byte order puts this before the composed name but after the decomposed one
+            fs::write(temp_dir.path().join("cafz.txt"), "z").unwrap();
+            hash_directory(temp_dir.path(), &[], false, &config(), &RunStats::default()).unwrap()
+        };
+        assert_eq!(hash_with(nfc), hash_with(nfd));
+        #[cfg(unix)]
+        assert_eq!(path_bytes(Path::new(nfd)), nfc.as_bytes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_directory_symlink_targets() {
+        let temp_dir = tempdir().unwrap();
+        let app = temp_dir.path().join("app");
+        let shared = temp_dir.path().join("shared");
+        fs::create_dir_all(&app).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("a.toml"), "same").unwrap();
+        fs::write(shared.join("b.toml"), "same").unwrap();
+        fs::write(app.join("main.rs"), "fn main() {}").unwrap();
+        let link = app.join("config.toml");
+        std::os::unix::fs::symlink("../shared/a.toml", &link).unwrap();
+
+        let with_targets = Config::builder().hash_symlink_targets(true).build().unwrap();
+        let hash = |config: &Config| hash_directory(&app, &[], false, config, &RunStats::default()).unwrap();
+        let skipped = hash(&config());
+        let pointing_at_a = hash(&with_targets);
+        assert_ne!(skipped, pointing_at_a);
+
+        // Repointing the link to identical content changes the hash only when targets count
+        fs::remove_file(&link).unwrap();
+        std::os::unix::fs::symlink("../shared/b.toml", &link).unwrap();
+        assert_eq!(hash(&config()), skipped);
+        assert_ne!(hash(&with_targets), pointing_at_a);
+
+        // A dangling link is hashed by its target path too
+        fs::remove_file(&link).unwrap();
+        std::os::unix::fs::symlink("../shared/missing.toml", &link).unwrap();
+        assert_ne!(hash(&with_targets), pointing_at_a);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[cfg(unix)]
+    fn test_hash_directory_incremental() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let settled = SystemTime::now() - std::time::Duration::from_secs(60);
+        for name in ["src/main.rs", "src/lib.rs", "README.md"] {
+            fs::write(dir.join(name), name).unwrap();
+            fs::File::options().write(true).open(dir.join(name)).unwrap().set_modified(settled).unwrap();
+        }
+
+        let stats = RunStats::default();
+        let first = hash_directory(dir, &[], false, &config(), &stats).unwrap();
+        assert_eq!(stats.files_hashed(), 3);
+
+        // Nothing changed: the directory hash is reused without reading any file
+        assert_eq!(hash_directory(dir, &[], false, &config(), &stats).unwrap(), first);
+        assert_eq!(stats.files_hashed(), 3);
+        assert_eq!(stats.files_reused(), 3);
+
+        // One file edited: only it is read again, and the result matches a full hash
+        fs::write(dir.join("src/lib.rs"), "pub fn changed() {}").unwrap();
+        let incremental = hash_directory(dir, &[], false, &config(), &stats).unwrap();
+        assert_eq!(stats.files_hashed(), 4);
+        let full = hash_directory(dir, &[], false, &config(), &RunStats::default()).unwrap();
+        assert_eq!(incremental, full);
+        assert_ne!(incremental, first);
+
+        // A removed file changes the listing too
+        fs::remove_file(dir.join("README.md")).unwrap();
+        let incremental = hash_directory(dir, &[], false, &config(), &stats).unwrap();
+        assert_eq!(incremental, hash_directory(dir, &[], false, &config(), &RunStats::default()).unwrap());
+    }
+
+    #[test]
+    fn test_hash_directory_unreadable_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        let without_secret = hash_directory(dir, &[], false, &config(), &RunStats::default()).unwrap();
+
+        let secret = dir.join("secret.bin");
+        fs::write(&secret, "root only").unwrap();
+        fs::set_permissions(&secret, fs::Permissions::from_mode(0o000)).unwrap();
+        if fs::read(&secret).is_ok() {
+            // Running as root, permissions don't apply
+            return;
+        }
+
+        // Failing by default, naming the file
+        let err = hash_directory(dir, &[], false, &config(), &RunStats::default()).unwrap_err();
+        assert!(matches!(&err, YethError::HashIoError { path, .. } if *path == secret));
+
+        // Skipped files don't take part in the hash
+        let skipping = Config::builder().skip_unreadable(true).quiet(true).build().unwrap();
+        let stats = RunStats::default();
+        assert_eq!(hash_directory(dir, &[], false, &skipping, &stats).unwrap(), without_secret);
+        assert_eq!(stats.files_skipped(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_directory_unreadable_subdirectory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        let without_private = hash_directory(dir, &[], false, &config(), &RunStats::default()).unwrap();
+
+        let private = dir.join("private");
+        fs::create_dir(&private).unwrap();
+        fs::write(private.join("key.pem"), "secret").unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o000)).unwrap();
+        let readable = fs::read_dir(&private).is_ok();
+        let result = hash_directory(dir, &[], false, &config(), &RunStats::default());
+        let skipping = Config::builder().skip_unreadable(true).quiet(true).build().unwrap();
+        let skipped = hash_directory(dir, &[], false, &skipping, &RunStats::default());
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o755)).unwrap();
+        if readable {
+            // Running as root, permissions don't apply
+            return;
+        }
+
+        // The directory is named instead of being left out of the hash
+        assert!(matches!(result, Err(YethError::HashWalkError { path, .. }) if path == private));
+        assert_eq!(skipped.unwrap(), without_private);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_path_unreadable() {
+        let temp_dir = tempdir().unwrap();
+        let link = temp_dir.path().join("shared");
+        let target = temp_dir.path().join("deleted");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        // A dangling link names its missing target and the app
+        let err = hash_path(&link, "api", &[], false, &config(), &RunStats::default()).unwrap_err();
+        assert!(matches!(
+            &err,
+            YethError::DanglingSymlink { link: l, target: t, app } if *l == link && *t == target && app == "api"
+        ));
+
+        // A plain path gone by hashing time was deleted during the run
+        let err = hash_path(&target, "api", &[], false, &config(), &RunStats::default()).unwrap_err();
+        assert!(matches!(&err, YethError::PathVanished { app, .. } if app == "api"));
+
+        // Once the target exists, the link is hashed as the target
+        fs::write(&target, "content").unwrap();
+        assert_eq!(
+            hash_path(&link, "api", &[], false, &config(), &RunStats::default()).unwrap(),
+            hash_path(&target, "api", &[], false, &config(), &RunStats::default()).unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_directory_symlink_loop() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path().to_path_buf();
+        fs::write(dir_path.join("file1.txt"), "Hello, World!").expect("Failed to write file1");
+
+        let hash_plain = hash_directory(&dir_path, &[], false, &config(), &RunStats::default()).unwrap();
+
+        // A link back to the directory itself must not be walked again
+        std::os::unix::fs::symlink(&dir_path, dir_path.join("loop")).expect("Failed to create symlink");
+        let follow = Config::builder().follow_symlinks(true).build().unwrap();
+        let hash_loop = hash_directory(&dir_path, &[], false, &follow, &RunStats::default()).unwrap();
+
+        assert_eq!(hash_plain, hash_loop, "Symlink loop should not add files to the hash");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_directory_links_to_one_directory() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let follow = Config::builder().follow_symlinks(true).build().unwrap();
+
+        // The same tree created in opposite orders, so directory order may differ
+        let tree = |name: &str, links_first: bool| {
+            let dir = temp_dir.path().join(name);
+            fs::create_dir_all(&dir).unwrap();
+            let make_real = || {
+                fs::create_dir_all(dir.join("real")).unwrap();
+                fs::write(dir.join("real").join("f"), "content").unwrap();
+            };
+            if !links_first {
+                make_real();
+            }
+            for link in ["z", "a"] {
+                std::os::unix::fs::symlink("real", dir.join(link)).unwrap();
+            }
+            if links_first {
+                make_real();
+            }
+            hash_directory(&dir, &[], false, &follow, &RunStats::default()).unwrap()
+        };
+        let hash = tree("one", false);
+        assert_eq!(hash, tree("two", true));
+
+        // The first link by name is the one entered
+        let plain = temp_dir.path().join("plain");
+        fs::create_dir_all(plain.join("a")).unwrap();
+        fs::write(plain.join("a").join("f"), "content").unwrap();
+        assert_eq!(hash, hash_directory(&plain, &[], false, &follow, &RunStats::default()).unwrap());
+    }
+
+    #[test]
+    fn test_hash_directory_includes_file_names() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path().to_path_buf();
+        let file_path = dir_path.join("a.txt");
+        fs::write(&file_path, "content").expect("Failed to write file");
+        let hash_before = hash_directory(&dir_path, &[], false, &config(), &RunStats::default()).unwrap();
+
+        // Same content under a different name is a different tree
+        fs::rename(&file_path, dir_path.join("b.txt")).expect("Failed to rename file");
+        let hash_after = hash_directory(&dir_path, &[], false, &config(), &RunStats::default()).unwrap();
+        assert_ne!(hash_before, hash_after, "Renaming a file should change the hash");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_directory_non_utf8_file_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        // Both names would collapse to "a\u{FFFD}" with a lossy conversion
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir1 = temp_dir.path().join("dir1");
+        let dir2 = temp_dir.path().join("dir2");
+        fs::create_dir(&dir1).expect("Failed to create dir1");
+        fs::create_dir(&dir2).expect("Failed to create dir2");
+        fs::write(dir1.join(OsStr::from_bytes(b"a\xff")), "same").expect("Failed to write file");
+        fs::write(dir2.join(OsStr::from_bytes(b"a\xfe")), "same").expect("Failed to write file");
+
+        let hash1 = hash_directory(&dir1, &[], false, &config(), &RunStats::default()).unwrap();
+        let hash2 = hash_directory(&dir2, &[], false, &config(), &RunStats::default()).unwrap();
+        assert_ne!(hash1, hash2, "Distinct non-UTF-8 names should hash differently");
+    }
+
+    #[test]
+    fn test_hash_directory_normalize_eol() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let lf_dir = temp_dir.path().join("lf");
+        let crlf_dir = temp_dir.path().join("crlf");
+        fs::create_dir(&lf_dir).expect("Failed to create lf dir");
+        fs::create_dir(&crlf_dir).expect("Failed to create crlf dir");
+        fs::write(lf_dir.join("main.rs"), "fn main() {}\n").expect("Failed to write file");
+        fs::write(crlf_dir.join("main.rs"), "fn main() {}\r\n").expect("Failed to write file");
+
+        // Different bytes by default, same logical content with normalization
+        assert_ne!(
+            hash_directory(&lf_dir, &[], false, &config(), &RunStats::default()).unwrap(),
+            hash_directory(&crlf_dir, &[], false, &config(), &RunStats::default()).unwrap()
+        );
+        assert_eq!(
+            hash_directory(&lf_dir, &[], true, &config(), &RunStats::default()).unwrap(),
+            hash_directory(&crlf_dir, &[], true, &config(), &RunStats::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hash_directory_include() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path().to_path_buf();
+        fs::create_dir_all(dir_path.join("src").join("generated")).expect("Failed to create src");
+        fs::create_dir_all(dir_path.join("docs")).expect("Failed to create docs");
+        fs::write(dir_path.join("src").join("main.rs"), "fn main() {}").expect("Failed to write main.rs");
+        fs::write(dir_path.join("src").join("generated").join("api.rs"), "gen").expect("Failed to write api.rs");
+        fs::write(dir_path.join("Dockerfile"), "FROM scratch").expect("Failed to write Dockerfile");
+        fs::write(dir_path.join("Cargo.toml"), "[package]").expect("Failed to write Cargo.toml");
+        fs::write(dir_path.join("docs").join("guide.md"), "guide").expect("Failed to write guide");
+
+        let stats = RunStats::default();
+        let include = ExcludePattern::NotIncluded(vec![
+            "src".to_string(),
+            "Dockerfile".to_string(),
+            "*.toml".to_string(),
+        ]);
+        let generated = ExcludePattern::Name("generated".to_string());
+        let only_included = vec![include.clone()];
+        let included_and_excluded = vec![include, generated];
+        let hash_included = hash_directory(&dir_path, &only_included, false, &config(), &stats).unwrap();
+        let hash_both = hash_directory(&dir_path, &included_and_excluded, false, &config(), &stats).unwrap();
+
+        // Files outside the include list don't count
+        fs::write(dir_path.join("docs").join("guide.md"), "edited").expect("Failed to modify guide");
+        fs::write(dir_path.join("README.md"), "readme").expect("Failed to write README");
+        assert_eq!(hash_included, hash_directory(&dir_path, &only_included, false, &config(), &stats).unwrap());
+
+        // Excludes still remove files from the included set
+        assert_ne!(hash_included, hash_both);
+        fs::write(dir_path.join("src").join("generated").join("api.rs"), "regen").expect("Failed to modify api.rs");
+        assert_eq!(hash_both, hash_directory(&dir_path, &included_and_excluded, false, &config(), &stats).unwrap());
+        assert_ne!(hash_included, hash_directory(&dir_path, &only_included, false, &config(), &stats).unwrap());
+
+        // Nothing matching the include list hashes like an empty directory
+        let nothing = ExcludePattern::NotIncluded(vec!["missing".to_string()]);
+        let empty_dir = dir_path.join("empty");
+        fs::create_dir(&empty_dir).expect("Failed to create empty dir");
+        assert_eq!(
+            hash_directory(&dir_path, &[nothing], false, &config(), &stats).unwrap(),
+            hash_directory(&empty_dir, &[], false, &config(), &stats).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hash_directory_extensions() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path().join("docs");
+        fs::create_dir_all(dir_path.join("guide")).expect("Failed to create guide");
+        fs::write(dir_path.join("index.md"), "index").expect("Failed to write index.md");
+        fs::write(dir_path.join("guide").join("intro.MDX"), "intro").expect("Failed to write intro.MDX");
+        fs::write(dir_path.join("guide").join("draft.md"), "draft").expect("Failed to write draft.md");
+        fs::write(dir_path.join("logo.png"), "png").expect("Failed to write logo.png");
+
+        let stats = RunStats::default();
+        let docs_only = vec![ExcludePattern::OtherExtension(vec!["md".to_string(), "mdx".to_string()])];
+        let without_drafts = vec![docs_only[0].clone(), ExcludePattern::Glob("draft.*".to_string())];
+        let hash = hash_directory(&dir_path, &docs_only, false, &config(), &stats).unwrap();
+
+        // Other extensions don't count, matching ignores case
+        fs::write(dir_path.join("logo.png"), "new png").expect("Failed to modify logo.png");
+        fs::write(dir_path.join("Makefile"), "all:").expect("Failed to write Makefile");
+        assert_eq!(hash, hash_directory(&dir_path, &docs_only, false, &config(), &stats).unwrap());
+        fs::write(dir_path.join("guide").join("intro.MDX"), "new intro").expect("Failed to modify intro.MDX");
+        let hash_after_edit = hash_directory(&dir_path, &docs_only, false, &config(), &stats).unwrap();
+        assert_ne!(hash, hash_after_edit);
+
+        // Excludes still remove matching files
+        let hash_without_drafts = hash_directory(&dir_path, &without_drafts, false, &config(), &stats).unwrap();
+        fs::write(dir_path.join("guide").join("draft.md"), "new draft").expect("Failed to modify draft.md");
+        assert_ne!(hash_after_edit, hash_directory(&dir_path, &docs_only, false, &config(), &stats).unwrap());
+        assert_eq!(hash_without_drafts, hash_directory(&dir_path, &without_drafts, false, &config(), &stats).unwrap());
+    }
+
+    #[test]
+    fn test_hash_directory_max_depth() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path().to_path_buf();
+        let deep_dir = dir_path.join("a").join("b");
+        fs::create_dir_all(&deep_dir).expect("Failed to create nested directories");
+        fs::write(dir_path.join("top.txt"), "top").expect("Failed to write top file");
+        fs::write(dir_path.join("a").join("mid.txt"), "mid").expect("Failed to write mid file");
+        let deep_file = deep_dir.join("deep.txt");
+        fs::write(&deep_file, "deep").expect("Failed to write deep file");
+
+        let limited = Config::builder().max_depth(Some(2)).build().unwrap();
+        let hash_before = hash_directory(&dir_path, &[], false, &limited, &RunStats::default()).unwrap();
+
+        // Files below the limit don't contribute
+        fs::write(&deep_file, "changed").expect("Failed to modify deep file");
+        assert_eq!(hash_before, hash_directory(&dir_path, &[], false, &limited, &RunStats::default()).unwrap());
+
+        // Files within the limit still do
+        fs::write(dir_path.join("a").join("mid.txt"), "changed").expect("Failed to modify mid file");
+        assert_ne!(hash_before, hash_directory(&dir_path, &[], false, &limited, &RunStats::default()).unwrap());
+    }
+
+    #[test]
+    fn test_hash_directory_prunes_excluded_directories() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path().to_path_buf();
+        fs::write(dir_path.join("index.js"), "app").expect("Failed to write index.js");
+        let hash_without = hash_directory(&dir_path, &[], false, &config(), &RunStats::default()).unwrap();
+
+        // A large excluded tree
+        let node_modules = dir_path.join("node_modules");
+        for i in 0..50 {
+            let package = node_modules.join(format!("package{}", i));
+            fs::create_dir_all(&package).expect("Failed to create package directory");
+            for j in 0..20 {
+                fs::write(package.join(format!("file{}.js", j)), "lib").expect("Failed to write file");
+            }
+        }
+
+        let exclude = vec![ExcludePattern::Name("node_modules".to_string())];
+        let matcher = ExcludeMatcher::new(&dir_path, &exclude);
+
+        // Only the root and index.js are yielded, node_modules is pruned before descending
+        let visited = walk(&dir_path, &matcher, &config()).count();
+        assert_eq!(visited, 2, "Excluded directory should not be traversed");
+        assert_eq!(hash_without, hash_directory(&dir_path, &exclude, false, &config(), &RunStats::default()).unwrap());
+    }
+
+    #[test]
+    fn test_hash_directory_tracked_only() {
+        use crate::git::tests::git;
+
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path().to_path_buf();
+        fs::write(dir_path.join("committed.txt"), "v1").expect("Failed to write committed file");
+        git(&dir_path, &["init", "-q"]);
+        git(&dir_path, &["add", "."]);
+        git(&dir_path, &["commit", "-q", "-m", "initial"]);
+
+        let tracked = Config::builder().tracked_only(true).build().unwrap();
+        let hash_committed = hash_directory(&dir_path, &[], false, &tracked, &RunStats::default()).unwrap();
+
+        // Untracked clutter is ignored
+        fs::write(dir_path.join("scratch.tmp"), "junk").expect("Failed to write scratch file");
+        assert_eq!(hash_committed, hash_directory(&dir_path, &[], false, &tracked, &RunStats::default()).unwrap());
+        assert_ne!(hash_committed, hash_directory(&dir_path, &[], false, &config(), &RunStats::default()).unwrap());
+
+        // Files added to the index count, and unstaged edits are read from the working tree
+        fs::write(dir_path.join("added.txt"), "new").expect("Failed to write added file");
+        git(&dir_path, &["add", "added.txt"]);
+        let hash_added = hash_directory(&dir_path, &[], false, &tracked, &RunStats::default()).unwrap();
+        assert_ne!(hash_committed, hash_added);
+
+        fs::write(dir_path.join("committed.txt"), "v2").expect("Failed to modify committed file");
+        assert_ne!(hash_added, hash_directory(&dir_path, &[], false, &tracked, &RunStats::default()).unwrap());
+    }
+
+    /// Backdate a file so it is old enough for its digest to be cached
+    #[cfg(unix)]
+    fn settle(path: &Path) {
+        let an_hour_ago = SystemTime::now() - std::time::Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(an_hour_ago))
+            .expect("Failed to set modification time");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_directory_reuses_file_digests() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let (first, second) = (temp_dir.path().join("first"), temp_dir.path().join("second"));
+        fs::create_dir_all(&first).expect("Failed to create first dir");
+        fs::create_dir_all(&second).expect("Failed to create second dir");
+        fs::write(first.join("api.pb.go"), "generated").expect("Failed to write file");
+        fs::write(first.join("main.go"), "package main").expect("Failed to write file");
+        settle(&first.join("api.pb.go"));
+        settle(&first.join("main.go"));
+        // The same file linked into a second tree, next to a file of its own
+        fs::hard_link(first.join("api.pb.go"), second.join("api.pb.go")).expect("Failed to link file");
+        fs::write(second.join("main.go"), "package other").expect("Failed to write file");
+
+        let stats = RunStats::default();
+        let first_hash = hash_directory(&first, &[], false, &config(), &stats).unwrap();
+        assert_eq!((stats.files_hashed(), stats.files_reused()), (2, 0));
+
+        // Known files cost no read, and the result is the same as without the cache
+        assert_eq!(hash_directory(&first, &[], false, &config(), &stats).unwrap(), first_hash);
+        assert_eq!((stats.files_hashed(), stats.files_reused()), (2, 2));
+        let second_hash = hash_directory(&second, &[], false, &config(), &stats).unwrap();
+        assert_eq!((stats.files_hashed(), stats.files_reused()), (3, 3));
+        assert_eq!(second_hash, hash_directory(&second, &[], false, &config(), &RunStats::default()).unwrap());
+        assert_ne!(first_hash, second_hash);
+
+        // A modified file is read again
+        fs::write(first.join("main.go"), "package main // edited").expect("Failed to modify file");
+        assert_ne!(hash_directory(&first, &[], false, &config(), &stats).unwrap(), first_hash);
+    }
+
+    #[test]
+    fn test_hash_directory_git_rev_identical_copies() {
+        use crate::git::tests::git;
+
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let repo = temp_dir.path().to_path_buf();
+        for (app, schema) in [("billing", "message A {}"), ("orders", "message A {}"), ("users", "message B {}")] {
+            fs::create_dir_all(repo.join(app)).expect("Failed to create app dir");
+            fs::write(repo.join(app).join("api.proto"), schema).expect("Failed to write schema");
+        }
+        git(&repo, &["init", "-q"]);
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "initial"]);
+
+        let at_head = Config::builder().git_rev(Some("HEAD".to_string())).build().unwrap();
+        let stats = RunStats::default();
+        let hash = |app: &str| hash_directory(&repo.join(app), &[], false, &at_head, &stats).unwrap();
+        let (billing, orders, users) = (hash("billing"), hash("orders"), hash("users"));
+
+        // The second identical copy is not read; a differing copy still changes the hash
+        assert_eq!(billing, orders);
+        assert_ne!(billing, users);
+        assert_eq!((stats.files_hashed(), stats.files_reused()), (2, 1));
+        assert_eq!(billing, hash_directory(&repo.join("billing"), &[], false, &config(), &RunStats::default()).unwrap());
+    }
+
+    #[test]
+    fn test_hash_directory_git_rev() {
+        use crate::git::tests::git;
+
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let repo = temp_dir.path().to_path_buf();
+        let app_dir = repo.join("app");
+        fs::create_dir_all(app_dir.join("dist")).expect("Failed to create app dir");
+        fs::write(app_dir.join("main.rs"), "fn main() {}").expect("Failed to write main.rs");
+        fs::write(app_dir.join("dist").join("bundle.js"), "built").expect("Failed to write bundle");
+        fs::write(repo.join("shared.txt"), "shared").expect("Failed to write shared file");
+        git(&repo, &["init", "-q"]);
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "initial"]);
+
+        let exclude = vec![ExcludePattern::Name("dist".to_string())];
+        let stats = RunStats::default();
+        let at_head = Config::builder().git_rev(Some("HEAD".to_string())).build().unwrap();
+        let committed = hash_directory(&app_dir, &exclude, false, &config(), &stats).unwrap();
+        let committed_file = hash_path(&repo.join("shared.txt"), "app", &[], false, &config(), &stats).unwrap();
+
+        // The committed tree hashes like the clean working tree, excludes included
+        assert_eq!(committed, hash_directory(&app_dir, &exclude, false, &at_head, &stats).unwrap());
+        assert_eq!(committed_file, hash_path(&repo.join("shared.txt"), "app", &[], false, &at_head, &stats).unwrap());
+
+        // Local edits and untracked files don't matter
+        fs::write(app_dir.join("main.rs"), "fn main() { dirty() }").expect("Failed to modify main.rs");
+        fs::write(app_dir.join("scratch.tmp"), "junk").expect("Failed to write scratch file");
+        fs::write(repo.join("shared.txt"), "edited").expect("Failed to modify shared file");
+        assert_eq!(committed, hash_directory(&app_dir, &exclude, false, &at_head, &stats).unwrap());
+        assert_eq!(committed_file, hash_path(&repo.join("shared.txt"), "app", &[], false, &at_head, &stats).unwrap());
+
+        // Counted with the committed sizes
+        let counted = hash_directory_observed(&app_dir, &exclude, false, &at_head, &stats, |_, _| {}).unwrap();
+        assert_eq!((counted.files, counted.bytes), (1, 12));
+
+        // Unknown revisions are reported by git
+        let unknown = Config::builder().git_rev(Some("no-such-ref".to_string())).build().unwrap();
+        let result = hash_directory(&app_dir, &exclude, false, &unknown, &stats);
+        assert!(matches!(result, Err(YethError::GitError(_))));
+    }
+
+    #[test]
+    fn test_hash_directory_tracked_only_outside_repository() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let tracked = Config::builder().tracked_only(true).build().unwrap();
+        let result = hash_directory(temp_dir.path(), &[], false, &tracked, &RunStats::default());
+        assert!(matches!(result, Err(YethError::NotAGitRepository(_))));
+    }
+
+    #[test]
+    fn test_should_exclude_matches_names_below_base_only() {
+        let base_dir = Path::new("/tmp/work/app");
+        let exclude = vec![ExcludePattern::Name("tmp".to_string())];
+
+        assert!(!should_exclude(&base_dir.join("main.rs"), base_dir, &exclude));
+        assert!(should_exclude(&base_dir.join("tmp").join("cache"), base_dir, &exclude));
+    }
+
+    #[test]
+    fn test_should_exclude_normalizes_separators() {
+        let base_dir = Path::new("/repo/app");
+        let file = base_dir.join("src").join("generated").join("api.rs");
+
+        let backslash = vec![ExcludePattern::Name("src\\generated".to_string())];
+        let forward = vec![ExcludePattern::Name("src/generated".to_string())];
+
+        assert!(should_exclude(&file, base_dir, &backslash));
+        assert!(should_exclude(&file, base_dir, &forward));
+        assert!(!should_exclude(&base_dir.join("src").join("main.rs"), base_dir, &backslash));
+    }
+
+    #[test]
+    fn test_should_exclude_matches_whole_components() {
+        let base_dir = Path::new("/repo/app");
+        let exclude = vec![
+            ExcludePattern::Name(".env".to_string()),
+            ExcludePattern::Name("src/gen".to_string()),
+        ];
+
+        assert!(should_exclude(&base_dir.join(".env"), base_dir, &exclude));
+        assert!(!should_exclude(&base_dir.join(".envrc"), base_dir, &exclude));
+        assert!(!should_exclude(&base_dir.join(".env.example"), base_dir, &exclude));
+        assert!(should_exclude(&base_dir.join("src").join("gen").join("api.rs"), base_dir, &exclude));
+        assert!(!should_exclude(&base_dir.join("src").join("generated.rs"), base_dir, &exclude));
+    }
+
+    #[test]
+    fn test_hash_directory_excluded_dotfile_keeps_similar_names() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let dir = temp_dir.path();
+        fs::write(dir.join(".env"), "SECRET=1").unwrap();
+        fs::write(dir.join(".envrc"), "use flake").unwrap();
+        let exclude = vec![ExcludePattern::Name(".env".to_string())];
+        let config = Config::builder().build().unwrap();
+
+        let hash = hash_directory(dir, &exclude, false, &config, &RunStats::default()).unwrap();
+        fs::write(dir.join(".env"), "SECRET=2").unwrap();
+        assert_eq!(hash_directory(dir, &exclude, false, &config, &RunStats::default()).unwrap(), hash);
+
+        fs::write(dir.join(".envrc"), "use nix").unwrap();
+        assert_ne!(hash_directory(dir, &exclude, false, &config, &RunStats::default()).unwrap(), hash);
     }
 
     #[test]
@@ -197,7 +1459,7 @@ mod tests {
         fs::write(&version_file, "1.0.0").expect("Failed to write version file");
         
         // Hash the directory
-        let hash_result = hash_directory(&dir_path.to_path_buf(), &[]);
+        let hash_result = hash_directory(dir_path, &[], false, &config(), &RunStats::default());
         assert!(hash_result.is_ok());
         
         // Now delete the special files and hash again
@@ -205,7 +1467,7 @@ mod tests {
         fs::remove_file(&ds_store).expect("Failed to remove DS Store");
         fs::remove_file(&version_file).expect("Failed to remove version file");
         
-        let hash_result2 = hash_directory(&dir_path.to_path_buf(), &[]);
+        let hash_result2 = hash_directory(dir_path, &[], false, &config(), &RunStats::default());
         assert!(hash_result2.is_ok());
         
         // Hashes should be the same since special files are ignored
@@ -1,27 +1,155 @@
+use crate::cfg::Config;
 use crate::error::YethError;
+use crate::run_stats::RunStats;
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::{BufReader, Read};
 use std::path::Path;
 
-/// Compute SHA256 hash for a file using buffered reading
-pub fn hash_file(path: &Path) -> Result<String, YethError> {
-    let mut hasher = Sha256::new();
+/// Number of leading bytes inspected to decide whether a file is text
+pub(crate) const TEXT_SNIFF_LEN: usize = 8192;
+/// Files smaller than this are always read, mapping them costs more than it saves
+pub(crate) const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// Compute SHA256 hash for a file, reading it `config.read_buffer_size` bytes at a time,
+/// or memory-mapping it with `config.use_mmap` when it is large enough.
+/// With `normalize_eol`, CRLF is hashed as LF in files that look like text.
+pub fn hash_file(
+    path: &Path,
+    normalize_eol: bool,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<String, YethError> {
+    read_and_hash(path, normalize_eol, config, stats).map_err(|source| YethError::HashIoError {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn read_and_hash(
+    path: &Path,
+    normalize_eol: bool,
+    config: &Config,
+    stats: &RunStats,
+) -> std::io::Result<String> {
     let file = fs::File::open(path)?;
-    let mut reader = BufReader::new(file);
-    
-    let mut buffer = [0; 8192];
+    if config.use_mmap && file.metadata()?.len() >= MMAP_THRESHOLD {
+        // SAFETY: the map is only read; a file modified while mapped yields a wrong
+        // hash, the same as when it is modified between buffered reads.
+        if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+            let mut hasher = Sha256::new();
+            let mut normalizer = (normalize_eol && looks_like_text(&map[..map.len().min(TEXT_SNIFF_LEN)]))
+                .then(EolNormalizer::default);
+            for chunk in map.chunks(config.read_buffer_size) {
+                match normalizer.as_mut() {
+                    Some(normalizer) => normalizer.feed(chunk, |bytes| hasher.update(bytes)),
+                    None => hasher.update(chunk),
+                }
+            }
+            if let Some(normalizer) = normalizer {
+                normalizer.finish(|bytes| hasher.update(bytes));
+            }
+            stats.record_file(map.len() as u64);
+            return Ok(format!("{:x}", hasher.finalize()));
+        }
+    }
+
+    let buffer_size = config.read_buffer_size;
+    let mut hasher = Sha256::new();
+    let mut reader = BufReader::with_capacity(buffer_size, file);
+
+    // The text check always looks at the same prefix, so the buffer size never changes the hash
+    let mut head = Vec::with_capacity(TEXT_SNIFF_LEN);
+    reader.by_ref().take(TEXT_SNIFF_LEN as u64).read_to_end(&mut head)?;
+    let mut normalizer = (normalize_eol && looks_like_text(&head)).then(EolNormalizer::default);
+    let mut total_bytes = head.len() as u64;
+    let mut update = |chunk: &[u8], normalizer: &mut Option<EolNormalizer>| match normalizer.as_mut() {
+        Some(normalizer) => normalizer.feed(chunk, |bytes| hasher.update(bytes)),
+        None => hasher.update(chunk),
+    };
+    update(&head, &mut normalizer);
+
+    let mut buffer = vec![0; buffer_size];
     loop {
         let bytes_read = reader.read(&mut buffer)?;
         if bytes_read == 0 {
             break;
         }
-        hasher.update(&buffer[..bytes_read]);
+        total_bytes += bytes_read as u64;
+        update(&buffer[..bytes_read], &mut normalizer);
     }
+    if let Some(normalizer) = normalizer {
+        normalizer.finish(|bytes| hasher.update(bytes));
+    }
+    stats.record_file(total_bytes);
     
     Ok(format!("{:x}", hasher.finalize()))
 }
 
+/// Compute SHA256 hash for file content already in memory, same as `hash_file` would
+pub fn hash_bytes(content: &[u8], normalize_eol: bool, stats: &RunStats) -> String {
+    let mut hasher = Sha256::new();
+    if normalize_eol && looks_like_text(&content[..content.len().min(TEXT_SNIFF_LEN)]) {
+        let mut normalizer = EolNormalizer::default();
+        normalizer.feed(content, |bytes| hasher.update(bytes));
+        normalizer.finish(|bytes| hasher.update(bytes));
+    } else {
+        hasher.update(content);
+    }
+    stats.record_file(content.len() as u64);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Text heuristic used by line-ending normalization: no NUL byte in the first chunk
+pub(crate) fn looks_like_text(first_chunk: &[u8]) -> bool {
+    !first_chunk.contains(&0)
+}
+
+/// Streaming CRLF to LF conversion. A CR at the end of a chunk is held back
+/// until the next chunk shows whether it starts a CRLF pair.
+#[derive(Default)]
+pub(crate) struct EolNormalizer {
+    pending_cr: bool,
+}
+
+impl EolNormalizer {
+    pub(crate) fn feed(&mut self, chunk: &[u8], mut emit: impl FnMut(&[u8])) {
+        if chunk.is_empty() {
+            return;
+        }
+        if self.pending_cr {
+            self.pending_cr = false;
+            if chunk[0] != b'\n' {
+                emit(b"\r");
+            }
+        }
+
+        let mut rest = chunk;
+        while let Some(pos) = rest.iter().position(|byte| *byte == b'\r') {
+            emit(&rest[..pos]);
+            match rest.get(pos + 1) {
+                // Drop the CR, the LF is emitted with the rest
+                Some(b'\n') => rest = &rest[pos + 1..],
+                Some(_) => {
+                    emit(b"\r");
+                    rest = &rest[pos + 1..];
+                }
+                None => {
+                    self.pending_cr = true;
+                    rest = &[];
+                }
+            }
+        }
+        emit(rest);
+    }
+
+    pub(crate) fn finish(self, mut emit: impl FnMut(&[u8])) {
+        if self.pending_cr {
+            emit(b"\r");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -29,6 +157,10 @@ mod tests {
     use std::io::Write;
     use tempfile::tempdir;
 
+    fn config() -> Config {
+        Config::builder().build().unwrap()
+    }
+
     #[test]
     fn test_hash_file() {
         // Create a temporary directory and file for testing
@@ -41,7 +173,7 @@ mod tests {
         file.sync_all().expect("Failed to sync file");
         
         // Calculate the hash
-        let hash_result = hash_file(&file_path);
+        let hash_result = hash_file(&file_path, false, &config(), &RunStats::default());
         assert!(hash_result.is_ok(), "Failed to hash file: {:?}", hash_result.err());
         
         let hash = hash_result.unwrap();
@@ -51,7 +183,7 @@ mod tests {
         assert!(hash.chars().all(|c| c.is_ascii_hexdigit()), "Hash should contain only hex characters");
         
         // Test that the same file produces the same hash
-        let hash_result2 = hash_file(&file_path);
+        let hash_result2 = hash_file(&file_path, false, &config(), &RunStats::default());
         assert!(hash_result2.is_ok());
         let hash2 = hash_result2.unwrap();
         assert_eq!(hash, hash2, "Same file should produce the same hash");
@@ -61,7 +193,7 @@ mod tests {
         file2.write_all(b"Hello, Different World!").expect("Failed to write to test file");
         file2.sync_all().expect("Failed to sync file");
         
-        let hash_result3 = hash_file(&file_path);
+        let hash_result3 = hash_file(&file_path, false, &config(), &RunStats::default());
         assert!(hash_result3.is_ok());
         let hash3 = hash_result3.unwrap();
         assert_ne!(hash, hash3, "Different content should produce different hashes");
@@ -72,9 +204,77 @@ mod tests {
         file3.write_all(&large_content).expect("Failed to write to test file");
         file3.sync_all().expect("Failed to sync file");
         
-        let hash_result4 = hash_file(&file_path);
+        let hash_result4 = hash_file(&file_path, false, &config(), &RunStats::default());
         assert!(hash_result4.is_ok(), "Failed to hash large file: {:?}", hash_result4.err());
         let hash4 = hash_result4.unwrap();
         assert_eq!(hash4.len(), 64, "Hash of large file should be 64 characters long");
     }
+
+    #[test]
+    fn test_hash_file_normalize_eol() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let lf_path = temp_dir.path().join("lf.txt");
+        let crlf_path = temp_dir.path().join("crlf.txt");
+        fs::write(&lf_path, "line 1\nline 2\n").unwrap();
+        fs::write(&crlf_path, "line 1\r\nline 2\r\n").unwrap();
+
+        // Byte-exact by default, equal with normalization on
+        assert_ne!(hash_file(&lf_path, false, &config(), &RunStats::default()).unwrap(), hash_file(&crlf_path, false, &config(), &RunStats::default()).unwrap());
+        assert_eq!(hash_file(&lf_path, true, &config(), &RunStats::default()).unwrap(), hash_file(&crlf_path, true, &config(), &RunStats::default()).unwrap());
+
+        // Binary files (NUL in the first chunk) are never rewritten
+        let binary_lf = temp_dir.path().join("lf.bin");
+        let binary_crlf = temp_dir.path().join("crlf.bin");
+        fs::write(&binary_lf, b"\0data\n").unwrap();
+        fs::write(&binary_crlf, b"\0data\r\n").unwrap();
+        assert_ne!(hash_file(&binary_lf, true, &config(), &RunStats::default()).unwrap(), hash_file(&binary_crlf, true, &config(), &RunStats::default()).unwrap());
+    }
+
+    #[test]
+    fn test_eol_normalizer_across_chunks() {
+        let mut output = Vec::new();
+        let mut normalizer = EolNormalizer::default();
+        // CRLF split between chunks, a lone CR, and a trailing CR
+        for chunk in [&b"a\r"[..], b"\nb\rc\r"] {
+            normalizer.feed(chunk, |bytes| output.extend_from_slice(bytes));
+        }
+        normalizer.finish(|bytes| output.extend_from_slice(bytes));
+        assert_eq!(output, b"a\nb\rc\r");
+    }
+
+    #[test]
+    fn test_hash_file_buffer_size_does_not_change_hash() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let file_path = temp_dir.path().join("big.txt");
+        // CRLF pairs straddle every small buffer boundary
+        fs::write(&file_path, "line\r\n".repeat(5000)).unwrap();
+
+        for normalize_eol in [false, true] {
+            let expected = hash_file(&file_path, normalize_eol, &config(), &RunStats::default()).unwrap();
+            for buffer_size in [1, 7, 4096, 1 << 20] {
+                let config = Config::builder().read_buffer_size(buffer_size).build().unwrap();
+                let hash = hash_file(&file_path, normalize_eol, &config, &RunStats::default()).unwrap();
+                assert_eq!(hash, expected, "buffer size {}", buffer_size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_file_mmap_matches_buffered() {
+        let temp_dir = tempdir().expect("Failed to create temp directory");
+        let file_path = temp_dir.path().join("asset.txt");
+        // A few megabytes, well above the mmap threshold, with CRLF line endings
+        let content = "0123456789abcdef\r\n".repeat(200_000);
+        assert!(content.len() as u64 > MMAP_THRESHOLD);
+        fs::write(&file_path, content).unwrap();
+
+        let mmap_config = Config::builder().use_mmap(true).build().unwrap();
+        for normalize_eol in [false, true] {
+            let stats = RunStats::default();
+            let buffered = hash_file(&file_path, normalize_eol, &config(), &RunStats::default()).unwrap();
+            let mapped = hash_file(&file_path, normalize_eol, &mmap_config, &stats).unwrap();
+            assert_eq!(mapped, buffered);
+            assert_eq!(stats.bytes_hashed(), 3_600_000);
+        }
+    }
 }
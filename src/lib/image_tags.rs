@@ -0,0 +1,171 @@
+use crate::error::YethError;
+use std::collections::{BTreeMap, HashMap};
+
+/// How app hashes are turned into image references
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageTagOptions {
+    /// Registry and namespace put before the image name, such as `registry.example.com/team`
+    pub registry: Option<String>,
+    /// Text put before the hash in the tag
+    pub tag_prefix: String,
+    /// Number of hash characters in the tag
+    pub hash_length: usize,
+}
+
+impl Default for ImageTagOptions {
+    fn default() -> Self {
+        Self {
+            registry: None,
+            tag_prefix: "yeth-".to_string(),
+            hash_length: 10,
+        }
+    }
+}
+
+/// One image reference per app, `<registry>/<image name>:<tag prefix><short hash>`.
+/// Fails when an app name has nothing left after `image_name`, when two apps end
+/// up with the same image name, or when different hashes look the same once shortened.
+pub fn image_refs(
+    hashes: &HashMap<String, String>,
+    options: &ImageTagOptions,
+) -> Result<BTreeMap<String, String>, YethError> {
+    let apps: BTreeMap<&String, &String> = hashes.iter().collect();
+
+    let mut by_short_hash: BTreeMap<String, Vec<(&String, &String)>> = BTreeMap::new();
+    for (app, hash) in &apps {
+        let short_hash: String = hash.chars().take(options.hash_length).collect();
+        by_short_hash.entry(short_hash).or_default().push((app, hash));
+    }
+    for (short_hash, apps) in &by_short_hash {
+        if apps.iter().any(|(_, hash)| *hash != apps[0].1) {
+            return Err(YethError::ShortHashCollision {
+                short_hash: short_hash.clone(),
+                apps: apps.iter().map(|(app, _)| app.to_string()).collect(),
+            });
+        }
+    }
+
+    let registry = options.registry.as_deref().map(|registry| registry.trim_end_matches('/'));
+    let mut images: HashMap<String, &String> = HashMap::new();
+    let mut refs = BTreeMap::new();
+    for (app, hash) in apps {
+        let image = image_name(app).ok_or_else(|| YethError::EmptyImageName(app.clone()))?;
+        if let Some(first) = images.insert(image.clone(), app) {
+            return Err(YethError::DuplicateImageName {
+                image,
+                first: first.clone(),
+                second: app.clone(),
+            });
+        }
+        let tag = format!("{}{}", options.tag_prefix, hash.chars().take(options.hash_length).collect::<String>());
+        if !is_valid_tag(&tag) {
+            return Err(YethError::InvalidImageTag(tag));
+        }
+        let image_ref = match registry {
+            Some(registry) if !registry.is_empty() => format!("{}/{}:{}", registry, image, tag),
+            _ => format!("{}:{}", image, tag),
+        };
+        refs.insert(app.clone(), image_ref);
+    }
+    Ok(refs)
+}
+
+/// An app name as an image name component: lowercase, `/` as `-`, and only letters,
+/// digits and the separators `.`, `_` and `-` between them. `None` when nothing is left.
+pub fn image_name(app: &str) -> Option<String> {
+    let mut name = String::new();
+    let mut separators = String::new();
+    for c in app.chars().flat_map(char::to_lowercase) {
+        match c {
+            'a'..='z' | '0'..='9' => {
+                if !name.is_empty() && !separators.is_empty() {
+                    name.push_str(valid_separator(&separators));
+                }
+                separators.clear();
+                name.push(c);
+            }
+            '/' => separators.push('-'),
+            '.' | '_' | '-' => separators.push(c),
+            _ => {}
+        }
+    }
+    (!name.is_empty()).then_some(name)
+}
+
+/// A run of separators as the registry grammar allows it: `.`, `_`, `__` or dashes
+fn valid_separator(separators: &str) -> &str {
+    match separators {
+        "." | "_" | "__" => separators,
+        _ if separators.chars().all(|c| c == '-') => separators,
+        _ => "-",
+    }
+}
+
+fn is_valid_tag(tag: &str) -> bool {
+    tag.len() <= 128
+        && !tag.starts_with(['.', '-'])
+        && !tag.is_empty()
+        && tag.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_name() {
+        assert_eq!(image_name("Backend").as_deref(), Some("backend"));
+        assert_eq!(image_name("team/api").as_deref(), Some("team-api"));
+        assert_eq!(image_name("my_app.v2").as_deref(), Some("my_app.v2"));
+        assert_eq!(image_name("-web app+ui-").as_deref(), Some("webappui"));
+        assert_eq!(image_name("a._b").as_deref(), Some("a-b"));
+        assert_eq!(image_name("ünïcode").as_deref(), Some("ncode"));
+        assert_eq!(image_name("+++"), None);
+    }
+
+    #[test]
+    fn test_image_refs() {
+        let hashes = HashMap::from([
+            ("Backend".to_string(), "abc1234def".to_string()),
+            ("web".to_string(), "0123456789".to_string()),
+        ]);
+        let options = ImageTagOptions {
+            registry: Some("registry.example.com/team/".to_string()),
+            hash_length: 7,
+            ..Default::default()
+        };
+        let refs = image_refs(&hashes, &options).unwrap();
+        assert_eq!(refs["Backend"], "registry.example.com/team/backend:yeth-abc1234");
+        assert_eq!(refs["web"], "registry.example.com/team/web:yeth-0123456");
+
+        let options = ImageTagOptions {
+            tag_prefix: String::new(),
+            ..Default::default()
+        };
+        assert_eq!(image_refs(&hashes, &options).unwrap()["web"], "web:0123456789");
+
+        // Names that vanish or clash, hashes that only differ after the cut, bad tags
+        let empty = HashMap::from([("+++".to_string(), "abc".to_string())]);
+        assert!(matches!(image_refs(&empty, &options), Err(YethError::EmptyImageName(app)) if app == "+++"));
+
+        let clash = HashMap::from([("API".to_string(), "abc".to_string()), ("api".to_string(), "abc".to_string())]);
+        assert!(matches!(
+            image_refs(&clash, &options),
+            Err(YethError::DuplicateImageName { image, .. }) if image == "api"
+        ));
+
+        let colliding = HashMap::from([("api".to_string(), "abc1".to_string()), ("web".to_string(), "abc2".to_string())]);
+        let short = ImageTagOptions { hash_length: 3, ..Default::default() };
+        assert!(matches!(
+            image_refs(&colliding, &short),
+            Err(YethError::ShortHashCollision { short_hash, apps }) if short_hash == "abc" && apps == ["api", "web"]
+        ));
+        assert!(image_refs(&colliding, &ImageTagOptions::default()).is_ok());
+
+        let bad_prefix = ImageTagOptions {
+            tag_prefix: "-release/".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(image_refs(&hashes, &bad_prefix), Err(YethError::InvalidImageTag(_))));
+    }
+}
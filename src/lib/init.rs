@@ -0,0 +1,152 @@
+use crate::cfg::{is_path_like, join_config_path, lexically_normalized, App, CONFIG_FILE};
+use crate::error::YethError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What `init_app` wrote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitOutcome {
+    /// The yeth.toml written
+    pub path: PathBuf,
+    /// An existing app whose directory encloses the new one: its own hash will no
+    /// longer cover the new app's files
+    pub enclosing_app: Option<String>,
+}
+
+/// Write a commented yeth.toml template into `dir`, creating the directory if needed.
+/// `dependencies` are written as given: app names must name one of `apps`, paths
+/// are relative to `dir` and must exist. An existing yeth.toml is only replaced with
+/// `force`.
+pub fn init_app(
+    dir: &Path,
+    dependencies: &[String],
+    force: bool,
+    apps: &HashMap<String, App>,
+) -> Result<InitOutcome, YethError> {
+    let path = dir.join(CONFIG_FILE);
+    if !force && fs::symlink_metadata(&path).is_ok() {
+        return Err(YethError::ConfigExists(path));
+    }
+
+    let app_name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .or_else(|| Some(dir.canonicalize().ok()?.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| dir.to_string_lossy().into_owned());
+    for dependency in dependencies {
+        if is_path_like(dependency) {
+            // `dir` may not exist yet, so `..` can't be resolved through it
+            let dependency_path = join_config_path(dir, dependency);
+            if !lexically_normalized(&dependency_path).exists() {
+                return Err(YethError::PathDependencyNotFound(dependency_path, app_name));
+            }
+        } else if !apps.contains_key(dependency) {
+            return Err(crate::suggest::app_not_found(dependency, apps));
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+    let enclosing_app = enclosing_app(dir, apps);
+    fs::write(&path, config_template(dependencies))?;
+    Ok(InitOutcome { path, enclosing_app })
+}
+
+/// The innermost app whose directory contains `dir`, other than `dir` itself
+fn enclosing_app(dir: &Path, apps: &HashMap<String, App>) -> Option<String> {
+    let dir = dir.canonicalize().ok()?;
+    apps.values()
+        .filter_map(|app| Some((app, app.dir.canonicalize().ok()?)))
+        .filter(|(_, app_dir)| *app_dir != dir && dir.starts_with(app_dir))
+        .max_by_key(|(_, app_dir)| app_dir.components().count())
+        .map(|(app, _)| app.name.clone())
+}
+
+/// Content of a new yeth.toml listing `dependencies`, with the common options
+/// present but commented out
+pub fn config_template(dependencies: &[String]) -> String {
+    let dependencies = dependencies
+        .iter()
+        .map(|dependency| toml::Value::String(dependency.clone()).to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        r#"# The application is named after this directory.
+[app]
+# Applications (by name) and paths (relative to this directory) whose changes
+# should change this application's hash
+dependencies = [{}]
+
+# Files and directories left out of the hash: names match anywhere, globs and
+# paths with '/' match relative to this directory
+# exclude = ["node_modules", "*.log"]
+
+# Hash only these files instead of the whole directory
+# include = ["src", "Cargo.toml"]
+
+# Categories to select applications with --tag
+# tags = ["backend"]
+"#,
+        dependencies
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_config_template() {
+        let content = config_template(&["common".to_string(), "../shared".to_string()]);
+        assert!(content.contains("dependencies = [\"common\", \"../shared\"]\n"));
+        assert!(content.contains("# exclude = "));
+
+        // The template is a valid config on its own
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("api")).unwrap();
+        fs::write(root.join("api").join(CONFIG_FILE), config_template(&[])).unwrap();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let apps = crate::discover_apps::discover_apps(&config).unwrap();
+        assert!(apps["api"].dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_init_app() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("shared")).unwrap();
+        fs::create_dir_all(root.join("common")).unwrap();
+        let common = App {
+            name: "common".to_string(),
+            dir: root.join("common"),
+            ..Default::default()
+        };
+        let apps = HashMap::from([("common".to_string(), common)]);
+        let dependencies = vec!["common".to_string(), "../shared".to_string()];
+
+        let outcome = init_app(&root.join("api"), &dependencies, false, &apps).unwrap();
+        assert_eq!(outcome.path, root.join("api").join(CONFIG_FILE));
+        assert_eq!(outcome.enclosing_app, None);
+        assert_eq!(fs::read_to_string(&outcome.path).unwrap(), config_template(&dependencies));
+
+        // Existing configs are kept unless forced
+        let err = init_app(&root.join("api"), &[], false, &apps).unwrap_err();
+        assert!(matches!(err, YethError::ConfigExists(path) if path == outcome.path));
+        init_app(&root.join("api"), &[], true, &apps).unwrap();
+        assert_eq!(fs::read_to_string(&outcome.path).unwrap(), config_template(&[]));
+
+        // Dependencies must exist, and nothing is written otherwise
+        let err = init_app(&root.join("web"), &["comon".to_string()], false, &apps).unwrap_err();
+        assert!(matches!(&err, YethError::AppNotFound { suggestions, .. } if suggestions == &["common"]));
+        let err = init_app(&root.join("web"), &["../missing".to_string()], false, &apps).unwrap_err();
+        assert!(matches!(err, YethError::PathDependencyNotFound(..)));
+        assert!(!root.join("web").exists());
+
+        // Nested inside an existing app
+        let outcome = init_app(&root.join("common").join("tools"), &[], false, &apps).unwrap();
+        assert_eq!(outcome.enclosing_app.as_deref(), Some("common"));
+    }
+}
@@ -1,29 +1,98 @@
 pub mod cfg;
+pub mod dependency_graph;
+pub mod diff;
 pub mod error;
+pub mod image_tags;
+pub mod init;
+pub mod lockfile;
+pub mod observer;
+pub mod run_stats;
+pub mod state;
+pub mod unused_excludes;
+pub mod verify;
+pub mod version_writes;
+pub mod why;
 mod find_app_dependencies;
+mod find_dependents;
+mod find_collisions;
 mod hash_file;
 mod hash_directory;
 mod topological_sort;
 mod compute_final_hash;
 mod discover_apps;
 mod calculate_hashes;
+mod changed_apps;
+mod apps_affected_by;
+mod git;
+mod suggest;
+mod expand_env;
+mod glob_match;
+mod select_apps;
+#[cfg(feature = "async")]
+mod engine_async;
 
 use cfg::App;
 use error::YethError;
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
 
 use crate::cfg::Config;
+use crate::dependency_graph::DependencyGraph;
+use crate::run_stats::{Phase, RunStats};
+use crate::state::State;
+use crate::lockfile::{LockMismatch, Lockfile};
+use crate::version_writes::{DependencyHash, VersionFormat, VersionWriteAction};
 use crate::discover_apps::discover_apps;
-use crate::calculate_hashes::{calculate_hashes, calculate_hashes_for_app};
+use crate::calculate_hashes::{
+    calculate_hashes, calculate_hashes_for_app, calculate_hashes_for_apps, calculate_hashes_keep_going, calculate_hashes_streaming,
+    calculate_impact_hashes, dependency_hashes,
+};
 
 pub struct YethEngine {
     config: Config,
+    /// Shared with the blocking tasks of the async methods
+    stats: Arc<RunStats>,
 }
 
 impl YethEngine {
     pub fn new(config: Config) -> YethEngine {
-        Self { config }
+        Self {
+            config,
+            stats: Arc::default(),
+        }
+    }
+
+    /// Also record how long each app takes to hash, see `RunStats::slowest_apps`
+    pub fn with_app_timings(mut self) -> Self {
+        self.stats_mut().enable_app_timings();
+        self
+    }
+
+    /// Report every app, file and path dependency that goes into the hashes to `observer`
+    pub fn with_observer(mut self, observer: impl observer::HashObserver + 'static) -> Self {
+        self.stats_mut().set_observer(Arc::new(observer));
+        self
+    }
+
+    /// The stats to change an option on. While a streaming task still holds them they
+    /// are copied first, so the task keeps reporting to the stats it started with.
+    fn stats_mut(&mut self) -> &mut RunStats {
+        Arc::make_mut(&mut self.stats)
+    }
+
+    /// File and byte counters and phase timings accumulated by every call on this engine
+    pub fn stats(&self) -> &RunStats {
+        &self.stats
+    }
+
+    fn timed<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.stats.record_phase(phase, start.elapsed());
+        result
     }
 
     /// Find all dependencies for a specific app (including transitive dependencies)
@@ -31,12 +100,142 @@ impl YethEngine {
       find_app_dependencies::find_app_dependencies(app_name, apps)
     }
 
+    /// Find all dependencies for a specific app, cutting cycles instead of failing
+    pub fn find_app_dependencies_lenient(&self, app_name: &str, apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
+      find_app_dependencies::find_app_dependencies_lenient(app_name, apps)
+    }
+
+    /// Discover, sort, and hash all applications
+    pub fn run(&self) -> Result<HashMap<String, String>, YethError> {
+        let apps = self.discover_apps()?;
+        self.run_with_apps(&apps)
+    }
+
+    /// Run discovery, sorting and hashing twice from scratch and compare every app's hash
+    pub fn verify(&self) -> Result<verify::VerifyReport, YethError> {
+        verify::verify(&self.config)
+    }
+
+    /// Sort and hash an already discovered set of applications
+    pub fn run_with_apps(&self, apps: &HashMap<String, App>) -> Result<HashMap<String, String>, YethError> {
+        let ordered_apps = self.topological_sort(apps)?;
+        self.calculate_hashes(ordered_apps, apps)
+    }
+
+    /// Sort and hash applications, calling `on_hash` as each app's hash is computed
+    pub fn run_streaming<F>(&self, apps: &HashMap<String, App>, on_hash: F) -> Result<HashMap<String, String>, YethError>
+    where
+        F: FnMut(&str, &str),
+    {
+        let ordered_apps = self.topological_sort(apps)?;
+        self.timed(Phase::Hashing, || {
+            calculate_hashes_streaming(ordered_apps, apps, &self.config, &self.stats, on_hash)
+        })
+    }
+
+    /// Find all apps that depend on a specific app (including transitive dependents)
+    pub fn find_dependents(&self, app_name: &str, apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
+      find_dependents::find_dependents(app_name, apps)
+    }
+
+    /// Find apps that no other app depends on, skipping the explicitly requested ones
+    pub fn find_orphans(&self, apps: &HashMap<String, App>, requested: &[String]) -> Vec<String> {
+      find_dependents::find_orphans(apps, requested)
+    }
+
+    /// Hash values shared by more than one app, each with the apps sharing it
+    pub fn find_collisions(&self, hashes: &HashMap<String, String>) -> Vec<(String, Vec<String>)> {
+        find_collisions::find_collisions(hashes)
+    }
+
+    /// An image reference for each app in `hashes`, see `image_tags::image_refs`
+    pub fn image_refs(
+        &self,
+        hashes: &HashMap<String, String>,
+        options: &image_tags::ImageTagOptions,
+    ) -> Result<BTreeMap<String, String>, YethError> {
+        image_tags::image_refs(hashes, options)
+    }
+
+    /// Warn about every hash value shared by more than one app
+    pub fn warn_collisions(&self, hashes: &HashMap<String, String>) {
+        for (hash, apps) in self.find_collisions(hashes) {
+            self.config.warn(format_args!("applications {} share the hash {}", apps.join(", "), hash));
+        }
+    }
+
+    /// Exclude patterns of the hashed apps that excluded no file in this engine's runs
+    pub fn find_unused_excludes(
+        &self,
+        hashes: &HashMap<String, String>,
+        apps: &HashMap<String, App>,
+    ) -> Vec<unused_excludes::UnusedExclude> {
+        unused_excludes::find_unused_excludes(hashes, apps, &self.stats)
+    }
+
+    /// Warn about every exclude pattern of the hashed apps that excluded no file
+    pub fn warn_unused_excludes(&self, hashes: &HashMap<String, String>, apps: &HashMap<String, App>) {
+        for unused in self.find_unused_excludes(hashes, apps) {
+            let pattern = match &unused.pattern {
+                cfg::ExcludePattern::AbsolutePath(path) => self.config.display_path(path).display().to_string(),
+                cfg::ExcludePattern::Name(pattern) | cfg::ExcludePattern::Glob(pattern) => pattern.clone(),
+                pattern => format!("{:?}", pattern),
+            };
+            match &unused.dependency {
+                Some(path) => self.config.warn(format_args!(
+                    "exclude pattern '{}' of application '{}' matched nothing in '{}'",
+                    pattern,
+                    unused.app,
+                    self.config.display_path(path).display()
+                )),
+                None => self.config.warn(format_args!(
+                    "exclude pattern '{}' of application '{}' matched nothing",
+                    pattern, unused.app
+                )),
+            }
+        }
+    }
+
+    /// Build a serializable dependency graph with reverse edges
+    pub fn dependency_graph(&self, apps: &HashMap<String, App>) -> DependencyGraph {
+        DependencyGraph::from_apps(apps)
+    }
+
+    /// Render the dependency graph as text trees, see `dependency_graph::render_tree`
+    pub fn render_dependency_tree(
+        &self,
+        apps: &HashMap<String, App>,
+        root_app: Option<&str>,
+        max_depth: Option<usize>,
+    ) -> Result<Vec<Vec<String>>, YethError> {
+        dependency_graph::render_tree(&self.config, apps, root_app, max_depth)
+    }
+
+    /// `render_dependency_tree` with app names and path dependencies styled for a terminal
+    pub fn render_dependency_tree_styled(
+        &self,
+        apps: &HashMap<String, App>,
+        root_app: Option<&str>,
+        max_depth: Option<usize>,
+        style: dependency_graph::TreeStyle,
+    ) -> Result<Vec<Vec<String>>, YethError> {
+        dependency_graph::render_tree_styled(&self.config, apps, root_app, max_depth, style)
+    }
+
+    /// Snapshot discovered apps with their hashing order
+    pub fn state(&self, apps: &HashMap<String, App>) -> Result<State, YethError> {
+        Ok(State::new(apps, self.topological_sort(apps)?))
+    }
+
     pub fn discover_apps(&self) -> Result<HashMap<String, App>, YethError> {
-        discover_apps(&self.config)
+        let apps = self.timed(Phase::Discovery, || discover_apps(&self.config))?;
+        // Every discovered app comes from one yeth.toml
+        self.stats.record_config_files(apps.len() as u64);
+        Ok(apps)
     }
 
     pub fn topological_sort(&self, apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
-      topological_sort::topological_sort(apps)
+      self.timed(Phase::Sort, || topological_sort::topological_sort(apps))
     }
 
     pub fn calculate_hashes(
@@ -44,7 +243,24 @@ impl YethEngine {
         ordered_apps: Vec<String>,
         apps: &HashMap<String, App>,
     ) -> Result<HashMap<String, String>, YethError> {
-        calculate_hashes(ordered_apps, apps)
+        self.timed(Phase::Hashing, || calculate_hashes(ordered_apps, apps, &self.config, &self.stats))
+    }
+
+    /// Calculate hashes without stopping at the first failure: the successful hashes,
+    /// plus each failed app with its error
+    pub fn calculate_hashes_keep_going(
+        &self,
+        ordered_apps: Vec<String>,
+        apps: &HashMap<String, App>,
+    ) -> (HashMap<String, String>, Vec<(String, YethError)>) {
+        self.timed(Phase::Hashing, || {
+            calculate_hashes_keep_going(ordered_apps, apps, &self.config, &self.stats)
+        })
+    }
+
+    /// Explain an app's hash: its transitive dependencies with own and final hashes, as a tree
+    pub fn explain(&self, app_name: &str, apps: &HashMap<String, App>) -> Result<why::WhyNode, YethError> {
+        why::explain(app_name, apps, &self.config, &self.stats)
     }
 
     /// Calculate hashes for a specific app and its dependencies
@@ -53,6 +269,306 @@ impl YethEngine {
         app_name: &str,
         apps: &HashMap<String, App>,
     ) -> Result<HashMap<String, String>, YethError> {
-        calculate_hashes_for_app(app_name, apps)
+        self.timed(Phase::Hashing, || calculate_hashes_for_app(app_name, apps, &self.config, &self.stats))
+    }
+
+    /// Calculate hashes for several apps and their dependencies
+    pub fn calculate_hashes_for_apps(
+        &self,
+        app_names: &[String],
+        apps: &HashMap<String, App>,
+    ) -> Result<HashMap<String, String>, YethError> {
+        self.timed(Phase::Hashing, || calculate_hashes_for_apps(app_names, apps, &self.config, &self.stats))
+    }
+
+    /// Resolve an app name or glob (`worker-*`) to the matching app names, sorted
+    pub fn select_apps(&self, pattern: &str, apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
+        select_apps::select_apps(pattern, apps)
+    }
+
+    /// Remove the apps matching any of the names or globs, see `select_apps::exclude_apps`
+    pub fn exclude_apps(&self, patterns: &[String], apps: &mut HashMap<String, App>) -> Result<Vec<String>, YethError> {
+        select_apps::exclude_apps(patterns, apps)
+    }
+
+    /// Names of the apps carrying any of `tags`, see `select_apps::select_tagged_apps`
+    pub fn select_tagged_apps(&self, tags: &[String], apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
+        select_apps::select_tagged_apps(tags, apps)
+    }
+
+    /// Order in which several apps and their dependencies are hashed
+    pub fn combined_dependency_order(
+        &self,
+        app_names: &[String],
+        apps: &HashMap<String, App>,
+    ) -> Result<Vec<String>, YethError> {
+        calculate_hashes::combined_dependency_order(app_names, apps)
+    }
+
+    /// Calculate hashes for an app and its transitive dependents, returning only those apps
+    pub fn calculate_impact_hashes(
+        &self,
+        app_name: &str,
+        apps: &HashMap<String, App>,
+    ) -> Result<HashMap<String, String>, YethError> {
+        self.timed(Phase::Hashing, || calculate_impact_hashes(app_name, apps, &self.config, &self.stats))
+    }
+
+    /// List files changed under the root since the current branch forked from a git ref
+    pub fn changed_files_since(&self, git_ref: &str) -> Result<Vec<PathBuf>, YethError> {
+        git::changed_files_since(&self.config.root, git_ref)
+    }
+
+    /// Map changed files to the apps they affect, including transitive dependents
+    pub fn apps_affected_by(&self, changed_files: &[PathBuf], apps: &HashMap<String, App>) -> Vec<String> {
+        apps_affected_by::apps_affected_by(changed_files, apps, &self.config)
+    }
+
+    /// Plan saving hashes to yeth.version files: which would be created, updated or left alone
+    pub fn plan_version_writes(
+        &self,
+        apps: &HashMap<String, App>,
+        hashes: &HashMap<String, String>,
+        format: VersionFormat,
+    ) -> Result<Vec<VersionWriteAction>, YethError> {
+        version_writes::plan_version_writes(apps, hashes, format)
+    }
+
+    /// The hash each dependency of an app contributes, given the hashes of a run
+    pub fn dependency_hashes(
+        &self,
+        app_name: &str,
+        apps: &HashMap<String, App>,
+        hashes: &HashMap<String, String>,
+    ) -> Result<Vec<DependencyHash>, YethError> {
+        dependency_hashes(app_name, apps, hashes, &self.config, &self.stats)
+    }
+
+    /// Save every app's hash to yeth.lock at the root, returning its path
+    pub fn write_lock(&self, hashes: &HashMap<String, String>) -> Result<PathBuf, YethError> {
+        let path = Lockfile::path(&self.config.root);
+        Lockfile::from_hashes(hashes).write(&path)?;
+        Ok(path)
+    }
+
+    /// Compare hashes with yeth.lock at the root; a missing lockfile is an error
+    pub fn check_lock(&self, hashes: &HashMap<String, String>) -> Result<Vec<LockMismatch>, YethError> {
+        let path = Lockfile::path(&self.config.root);
+        let lockfile = Lockfile::read(&path)?.ok_or(YethError::LockFileNotFound(path))?;
+        Ok(lockfile.compare(hashes))
+    }
+
+    /// Find applications whose hash differs from their saved yeth.version file
+    pub fn changed_apps(
+        &self,
+        apps: &HashMap<String, App>,
+        hashes: &HashMap<String, String>,
+    ) -> Result<Vec<String>, YethError> {
+        changed_apps::changed_apps(apps, hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let app1_dir = root.join("app1");
+        let app2_dir = root.join("app2");
+        fs::create_dir_all(&app1_dir).unwrap();
+        fs::create_dir_all(&app2_dir).unwrap();
+        fs::write(app1_dir.join("yeth.toml"), "[app]\n").unwrap();
+        fs::write(app1_dir.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(app2_dir.join("yeth.toml"), "[app]\ndependencies = [\"app1\"]\n").unwrap();
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let engine = YethEngine::new(config);
+
+        let hashes = engine.run().unwrap();
+        assert_eq!(hashes.len(), 2);
+
+        // Same result as running each step by hand, and stable across repeated runs
+        let apps = engine.discover_apps().unwrap();
+        let ordered_apps = engine.topological_sort(&apps).unwrap();
+        assert_eq!(hashes, engine.calculate_hashes(ordered_apps, &apps).unwrap());
+        assert_eq!(hashes, engine.run_with_apps(&apps).unwrap());
+    }
+
+    #[test]
+    fn test_run_stats_timings() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for (name, files) in [("small", 1), ("large", 3)] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("yeth.toml"), "[app]\n").unwrap();
+            for i in 1..files {
+                fs::write(dir.join(format!("{}.rs", i)), "fn main() {}").unwrap();
+            }
+        }
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+
+        // Without app timings only the counters and phases are kept
+        let engine = YethEngine::new(config.clone());
+        engine.run().unwrap();
+        assert_eq!(engine.stats().config_files_parsed(), 2);
+        assert!(engine.stats().phase_duration(Phase::Hashing) > std::time::Duration::ZERO);
+        assert!(engine.stats().slowest_apps(5).is_empty());
+
+        let engine = YethEngine::new(config).with_app_timings();
+        engine.run().unwrap();
+        let mut timings = engine.stats().slowest_apps(5);
+        timings.sort_by(|a, b| a.app.cmp(&b.app));
+        let files: Vec<(&str, u64, u64)> = timings.iter().map(|t| (t.app.as_str(), t.files, t.bytes)).collect();
+        assert_eq!(files, vec![("large", 3, 30), ("small", 1, 6)]);
+        assert_eq!(engine.stats().slowest_apps(1).len(), 1);
+    }
+
+    #[test]
+    fn test_yethignore() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        for app in ["app1", "app2"] {
+            let app_dir = root.join(app);
+            fs::create_dir_all(app_dir.join("logs")).unwrap();
+            fs::write(app_dir.join("yeth.toml"), "[app]\nexclude = [\"scratch.txt\"]\n").unwrap();
+            fs::write(app_dir.join("main.rs"), "fn main() {}").unwrap();
+        }
+        fs::write(root.join(".yethignore"), "# build noise\n\n*.log\ntmp/\n").unwrap();
+
+        let run = || {
+            let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+            YethEngine::new(config).run().unwrap()
+        };
+        let before = run();
+
+        // Ignored in both apps by .yethignore, and by each app's own exclude
+        for app in ["app1", "app2"] {
+            let app_dir = root.join(app);
+            fs::write(app_dir.join("logs").join("debug.log"), "noise").unwrap();
+            fs::create_dir_all(app_dir.join("tmp")).unwrap();
+            fs::write(app_dir.join("tmp").join("cache"), "noise").unwrap();
+            fs::write(app_dir.join("scratch.txt"), "noise").unwrap();
+        }
+        assert_eq!(before, run());
+
+        fs::write(root.join("app2").join("notes.txt"), "real change").unwrap();
+        let after = run();
+        assert_eq!(before.get("app1"), after.get("app1"));
+        assert_ne!(before.get("app2"), after.get("app2"));
+    }
+
+    #[test]
+    fn test_yethignore_unreadable() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".yethignore")).unwrap();
+
+        let result = Config::builder().root(root.to_path_buf()).build();
+        assert!(matches!(result, Err(YethError::ConfigReadError { ref path, .. }) if path == &root.join(".yethignore")));
+    }
+
+    #[test]
+    fn test_app_meta_is_not_hashed() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let app_dir = root.join("app1");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let engine = YethEngine::new(config);
+
+        fs::write(app_dir.join("yeth.toml"), "[app]\n").unwrap();
+        let without_meta = engine.run().unwrap();
+
+        fs::write(
+            app_dir.join("yeth.toml"),
+            "[app]\n[app.meta]\nowner = \"team-payments\"\ntier = 1\n",
+        )
+        .unwrap();
+        let apps = engine.discover_apps().unwrap();
+
+        // Metadata round-trips to JSON values
+        let meta = serde_json::to_value(&apps.get("app1").unwrap().meta).unwrap();
+        assert_eq!(meta, serde_json::json!({ "owner": "team-payments", "tier": 1 }));
+
+        // but doesn't change the hash
+        assert_eq!(without_meta, engine.run_with_apps(&apps).unwrap());
+
+        // nor does it in a config with comments and its own formatting
+        fs::write(app_dir.join("yeth.toml"), "[app]\n# owner: payments\ndependencies = []\n").unwrap();
+        let commented = engine.run().unwrap();
+        fs::write(
+            app_dir.join("yeth.toml"),
+            "[app]\n# owner: payments\ndependencies = []\n\n[app.meta]\nowner = \"team-payments\"\n",
+        )
+        .unwrap();
+        assert_eq!(commented, engine.run().unwrap());
+    }
+
+    #[test]
+    fn test_salt_changes_every_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for (name, config) in [("lib", "[app]\n"), ("web", "[app]\ndependencies = [\"lib\"]\n")] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join("yeth.toml"), config).unwrap();
+        }
+        let run = |salt: Option<&str>| {
+            let config = Config::builder()
+                .root(root.to_path_buf())
+                .salt(salt.map(String::from))
+                .build()
+                .unwrap();
+            YethEngine::new(config).run().unwrap()
+        };
+
+        let unsalted = run(None);
+        let v1 = run(Some("toolchain-1"));
+        let v2 = run(Some("toolchain-2"));
+        for app in ["lib", "web"] {
+            assert_ne!(unsalted[app], v1[app]);
+            assert_ne!(v1[app], v2[app]);
+        }
+        assert_eq!(v1, run(Some("toolchain-1")));
+        assert_eq!(unsalted, run(None));
+    }
+
+    #[test]
+    fn test_app_tags_are_not_hashed() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let app_dir = root.join("app1");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let engine = YethEngine::new(config);
+
+        fs::write(app_dir.join("yeth.toml"), "[app]\n").unwrap();
+        let without_tags = engine.run().unwrap();
+
+        fs::write(app_dir.join("yeth.toml"), "[app]\ntags = [\"backend\", \"tier1\"]\n").unwrap();
+        let apps = engine.discover_apps().unwrap();
+        assert_eq!(apps["app1"].tags, vec!["backend", "tier1"]);
+        assert_eq!(without_tags, engine.run_with_apps(&apps).unwrap());
+
+        // Comments and formatting around them don't make tags count either
+        fs::write(app_dir.join("yeth.toml"), "[app]\n# deployed by ci\nexclude = [ \"*.log\" ]\n").unwrap();
+        let commented = engine.run().unwrap();
+        fs::write(
+            app_dir.join("yeth.toml"),
+            "[app]\n# deployed by ci\nexclude = [ \"*.log\" ]\ntags = [\"backend\"]  # for --tag\n",
+        )
+        .unwrap();
+        assert_eq!(commented, engine.run().unwrap());
     }
 }
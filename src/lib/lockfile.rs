@@ -0,0 +1,166 @@
+use crate::cfg::LOCK_FILE;
+use crate::error::YethError;
+use crate::version_writes::HASH_SCHEME;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "# Generated by `yeth --write-lock`. Do not edit by hand.\n\n";
+
+/// Every application's hash, saved as yeth.lock at the root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// How the hashes were computed; a different scheme makes every entry stale
+    pub scheme: String,
+    /// App name → hash, sorted by name
+    pub apps: BTreeMap<String, String>,
+}
+
+/// One way a lockfile disagrees with freshly computed hashes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockMismatch {
+    /// The lockfile was written with another hash scheme
+    Scheme { locked: String, current: String },
+    /// The app's hash differs from the locked one
+    Changed { app: String, locked: String, current: String },
+    /// The app is not in the lockfile
+    Added { app: String, current: String },
+    /// The lockfile lists an app that no longer exists
+    Removed { app: String, locked: String },
+}
+
+impl Lockfile {
+    pub fn from_hashes(hashes: &HashMap<String, String>) -> Self {
+        Self {
+            scheme: HASH_SCHEME.to_string(),
+            apps: hashes.iter().map(|(app, hash)| (app.clone(), hash.clone())).collect(),
+        }
+    }
+
+    /// Path of the lockfile for a root directory
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(LOCK_FILE)
+    }
+
+    /// Read a lockfile; a missing file is `Ok(None)`
+    pub fn read(path: &Path) -> Result<Option<Self>, YethError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => {
+                return Err(YethError::ConfigReadError {
+                    path: path.to_path_buf(),
+                    source,
+                });
+            }
+        };
+        toml::from_str(&content)
+            .map(Some)
+            .map_err(|source| YethError::TomlParseError {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    /// Lockfile content: a header comment, then the scheme and sorted `[apps]` table
+    pub fn render(&self) -> String {
+        let body = toml::to_string(self).expect("lockfile always serializes");
+        format!("{}{}", HEADER, body)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), YethError> {
+        fs::write(path, self.render())?;
+        Ok(())
+    }
+
+    /// Differences between this lockfile and `hashes`, sorted by app name
+    pub fn compare(&self, hashes: &HashMap<String, String>) -> Vec<LockMismatch> {
+        let mut mismatches = Vec::new();
+        if self.scheme != HASH_SCHEME {
+            mismatches.push(LockMismatch::Scheme {
+                locked: self.scheme.clone(),
+                current: HASH_SCHEME.to_string(),
+            });
+        }
+
+        let current = Lockfile::from_hashes(hashes).apps;
+        for (app, hash) in &current {
+            match self.apps.get(app) {
+                Some(locked) if locked == hash => {}
+                Some(locked) => mismatches.push(LockMismatch::Changed {
+                    app: app.clone(),
+                    locked: locked.clone(),
+                    current: hash.clone(),
+                }),
+                None => mismatches.push(LockMismatch::Added {
+                    app: app.clone(),
+                    current: hash.clone(),
+                }),
+            }
+        }
+        for (app, locked) in &self.apps {
+            if !current.contains_key(app) {
+                mismatches.push(LockMismatch::Removed {
+                    app: app.clone(),
+                    locked: locked.clone(),
+                });
+            }
+        }
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn hashes(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(app, hash)| (app.to_string(), hash.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lockfile_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = Lockfile::path(temp_dir.path());
+        assert_eq!(Lockfile::read(&path).unwrap(), None);
+
+        let lockfile = Lockfile::from_hashes(&hashes(&[("zeta", "222"), ("alpha", "111")]));
+        lockfile.write(&path).unwrap();
+
+        // Stable, sorted output that reads back unchanged
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.find("alpha").unwrap() < content.find("zeta").unwrap());
+        assert!(content.contains(HASH_SCHEME));
+        let read = Lockfile::read(&path).unwrap().unwrap();
+        assert_eq!(read, lockfile);
+        assert_eq!(read.render(), content);
+    }
+
+    #[test]
+    fn test_lockfile_compare() {
+        let lockfile = Lockfile::from_hashes(&hashes(&[("same", "1"), ("changed", "2"), ("removed", "3")]));
+        let current = hashes(&[("same", "1"), ("changed", "20"), ("added", "4")]);
+
+        assert_eq!(
+            lockfile.compare(&current),
+            vec![
+                LockMismatch::Added { app: "added".into(), current: "4".into() },
+                LockMismatch::Changed { app: "changed".into(), locked: "2".into(), current: "20".into() },
+                LockMismatch::Removed { app: "removed".into(), locked: "3".into() },
+            ]
+        );
+        assert!(Lockfile::from_hashes(&current).compare(&current).is_empty());
+
+        // Another scheme is reported on its own
+        let old_scheme = Lockfile {
+            scheme: "other".to_string(),
+            ..Lockfile::from_hashes(&current)
+        };
+        assert!(matches!(old_scheme.compare(&current)[..], [LockMismatch::Scheme { .. }]));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::cfg::App;
+use std::path::Path;
+
+/// Callbacks invoked while an engine hashes applications, to see exactly which
+/// files and path dependencies went into each hash. Every method does nothing by
+/// default. Set one with `YethEngine::with_observer`.
+///
+/// For one app the calls come in order: `on_app_start`, `on_file_hashed` for each
+/// file of its own directory (sorted like the hash sees them), `on_path_dependency`
+/// for each path dependency, then `on_app_finished`. Files inside a path dependency
+/// are not reported one by one, as the dependency's digest is shared between apps.
+pub trait HashObserver: Send + Sync {
+    fn on_app_start(&self, _app: &App) {}
+
+    /// A file of the app's own directory and the digest it contributed
+    fn on_file_hashed(&self, _app: &App, _file: &Path, _digest: &str) {}
+
+    /// A path dependency of the app and the hash it contributed
+    fn on_path_dependency(&self, _app: &App, _path: &Path, _digest: &str) {}
+
+    fn on_app_finished(&self, _app: &App, _final_hash: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::Config;
+    use crate::YethEngine;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl HashObserver for Arc<Recorder> {
+        fn on_app_start(&self, app: &App) {
+            self.events.lock().unwrap().push(format!("start {}", app.name));
+        }
+
+        fn on_file_hashed(&self, app: &App, file: &Path, _digest: &str) {
+            let file = file.strip_prefix(&app.dir).unwrap();
+            self.events.lock().unwrap().push(format!("file {} {}", app.name, file.display()));
+        }
+
+        fn on_path_dependency(&self, app: &App, path: &Path, _digest: &str) {
+            let path = path.file_name().unwrap().to_string_lossy();
+            self.events.lock().unwrap().push(format!("path {} {}", app.name, path));
+        }
+
+        fn on_app_finished(&self, app: &App, final_hash: &str) {
+            assert_eq!(final_hash.len(), 64);
+            self.events.lock().unwrap().push(format!("finish {}", app.name));
+        }
+    }
+
+    #[test]
+    fn test_observer_callbacks() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("shared")).unwrap();
+        fs::write(root.join("shared").join("schema.sql"), "create table").unwrap();
+        fs::create_dir_all(root.join("common")).unwrap();
+        fs::write(root.join("common").join("yeth.toml"), "[app]\n").unwrap();
+        fs::write(root.join("common").join("lib.rs"), "pub fn f() {}").unwrap();
+        fs::create_dir_all(root.join("api").join("src")).unwrap();
+        fs::write(
+            root.join("api").join("yeth.toml"),
+            "[app]\ndependencies = [\"common\", \"../shared\"]\n",
+        )
+        .unwrap();
+        fs::write(root.join("api").join("src").join("main.rs"), "fn main() {}").unwrap();
+
+        let recorder = Arc::new(Recorder::default());
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let engine = YethEngine::new(config).with_observer(recorder.clone());
+        let observed = engine.run().unwrap();
+
+        let events = recorder.events.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            vec![
+                "start common",
+                "file common lib.rs",
+                "file common yeth.toml",
+                "finish common",
+                "start api",
+                "file api src/main.rs",
+                "file api yeth.toml",
+                "path api shared",
+                "finish api",
+            ]
+        );
+
+        // Observing doesn't change the hashes
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        assert_eq!(YethEngine::new(config).run().unwrap(), observed);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::cfg::ExcludePattern;
+use crate::hash_directory::{DirectoryKey, DirectoryListing, FileKey};
+use crate::observer::HashObserver;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters accumulated while hashing, plus the digests of files and directories
+/// already hashed in this run. Atomic or locked so they can be shared by reference;
+/// a clone is a snapshot that shares only the observer.
+#[derive(Debug, Default)]
+pub struct RunStats {
+    files_hashed: AtomicU64,
+    bytes_hashed: AtomicU64,
+    files_reused: AtomicU64,
+    files_skipped: AtomicU64,
+    file_digests: Mutex<HashMap<FileKey, String>>,
+    directory_listings: Mutex<HashMap<DirectoryKey, Arc<DirectoryListing>>>,
+    /// Exclude patterns that left out something, with the directory they were applied to
+    matched_excludes: Mutex<HashSet<(PathBuf, ExcludePattern)>>,
+    config_files_parsed: AtomicU64,
+    /// Nanoseconds spent in each `Phase`, indexed by the phase
+    phase_nanos: [AtomicU64; 3],
+    /// Per-app timings, collected only when enabled
+    app_timings: Option<Mutex<Vec<AppTiming>>>,
+    observer: Observer,
+}
+
+/// The optional observer, a separate type so `RunStats` can stay `Debug`
+#[derive(Default, Clone)]
+struct Observer(Option<Arc<dyn HashObserver>>);
+
+impl std::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0.is_some() { "Some(..)" } else { "None" })
+    }
+}
+
+/// A stage of a run, timed separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Discovery,
+    Sort,
+    Hashing,
+}
+
+/// How long one app took to hash, and how many files of its own directory went
+/// into its hash after exclusions, with their total size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppTiming {
+    pub app: String,
+    pub duration: Duration,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl Clone for RunStats {
+    fn clone(&self) -> Self {
+        let load = |counter: &AtomicU64| AtomicU64::new(counter.load(Ordering::Relaxed));
+        Self {
+            files_hashed: load(&self.files_hashed),
+            bytes_hashed: load(&self.bytes_hashed),
+            files_reused: load(&self.files_reused),
+            files_skipped: load(&self.files_skipped),
+            file_digests: Mutex::new(self.file_digests.lock().unwrap().clone()),
+            directory_listings: Mutex::new(self.directory_listings.lock().unwrap().clone()),
+            matched_excludes: Mutex::new(self.matched_excludes.lock().unwrap().clone()),
+            config_files_parsed: load(&self.config_files_parsed),
+            phase_nanos: self.phase_nanos.each_ref().map(load),
+            app_timings: self.app_timings.as_ref().map(|timings| Mutex::new(timings.lock().unwrap().clone())),
+            observer: self.observer.clone(),
+        }
+    }
+}
+
+impl RunStats {
+    /// Counters that also keep the hashing time of every app, see `slowest_apps`
+    pub fn with_app_timings() -> Self {
+        Self {
+            app_timings: Some(Mutex::default()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn enable_app_timings(&mut self) {
+        self.app_timings.get_or_insert_with(Mutex::default);
+    }
+
+    pub(crate) fn set_observer(&mut self, observer: Arc<dyn HashObserver>) {
+        self.observer = Observer(Some(observer));
+    }
+
+    /// The observer to notify while hashing, if any
+    pub(crate) fn observer(&self) -> Option<&dyn HashObserver> {
+        self.observer.0.as_deref()
+    }
+
+    /// Record one file read in full, `bytes` being its size on disk
+    pub fn record_file(&self, bytes: u64) {
+        self.files_hashed.fetch_add(1, Ordering::Relaxed);
+        self.bytes_hashed.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Number of files read, counting a file once per time it was hashed
+    pub fn files_hashed(&self) -> u64 {
+        self.files_hashed.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes read from files
+    pub fn bytes_hashed(&self) -> u64 {
+        self.bytes_hashed.load(Ordering::Relaxed)
+    }
+
+    /// Number of files whose digest was reused instead of reading them again
+    pub fn files_reused(&self) -> u64 {
+        self.files_reused.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_skipped_file(&self) {
+        self.files_skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of unreadable files left out of hashes (only with `skip_unreadable`)
+    pub fn files_skipped(&self) -> u64 {
+        self.files_skipped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn cached_digest(&self, key: &FileKey) -> Option<String> {
+        let digest = self.file_digests.lock().unwrap().get(key).cloned();
+        if digest.is_some() {
+            self.files_reused.fetch_add(1, Ordering::Relaxed);
+        }
+        digest
+    }
+
+    pub(crate) fn store_digest(&self, key: FileKey, digest: String) {
+        self.file_digests.lock().unwrap().insert(key, digest);
+    }
+
+    pub(crate) fn cached_directory(&self, key: &DirectoryKey) -> Option<Arc<DirectoryListing>> {
+        self.directory_listings.lock().unwrap().get(key).cloned()
+    }
+
+    pub(crate) fn store_directory(&self, key: DirectoryKey, listing: DirectoryListing) {
+        self.directory_listings.lock().unwrap().insert(key, Arc::new(listing));
+    }
+
+    /// Remember which of the patterns applied to directory `dir` excluded something
+    pub(crate) fn record_matched_excludes<'a>(&self, dir: &Path, patterns: impl Iterator<Item = &'a ExcludePattern>) {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        let mut matched = self.matched_excludes.lock().unwrap();
+        for pattern in patterns {
+            matched.insert((dir.clone(), pattern.clone()));
+        }
+    }
+
+    /// Whether `pattern` excluded anything when directory `dir` was hashed in this run
+    pub fn exclude_matched(&self, dir: &Path, pattern: &ExcludePattern) -> bool {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        self.matched_excludes.lock().unwrap().contains(&(dir, pattern.clone()))
+    }
+
+    /// Count files whose digests came with a reused directory hash
+    pub(crate) fn record_reused_files(&self, count: u64) {
+        self.files_reused.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_config_files(&self, count: u64) {
+        self.config_files_parsed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Number of yeth.toml files parsed during discovery
+    pub fn config_files_parsed(&self) -> u64 {
+        self.config_files_parsed.load(Ordering::Relaxed)
+    }
+
+    pub fn record_phase(&self, phase: Phase, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.phase_nanos[phase as usize].fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Total time spent in a phase, over every call
+    pub fn phase_duration(&self, phase: Phase) -> Duration {
+        Duration::from_nanos(self.phase_nanos[phase as usize].load(Ordering::Relaxed))
+    }
+
+    /// Whether per-app timings are collected
+    pub fn app_timings_enabled(&self) -> bool {
+        self.app_timings.is_some()
+    }
+
+    pub fn record_app(&self, app: &str, duration: Duration, files: u64, bytes: u64) {
+        if let Some(timings) = &self.app_timings {
+            timings.lock().unwrap().push(AppTiming {
+                app: app.to_string(),
+                duration,
+                files,
+                bytes,
+            });
+        }
+    }
+
+    /// Up to `count` apps that took longest to hash, slowest first
+    pub fn slowest_apps(&self, count: usize) -> Vec<AppTiming> {
+        let Some(timings) = &self.app_timings else {
+            return Vec::new();
+        };
+        let mut timings = timings.lock().unwrap().clone();
+        timings.sort_by(|a, b| b.duration.cmp(&a.duration).then_with(|| a.app.cmp(&b.app)));
+        timings.truncate(count);
+        timings
+    }
+}
@@ -0,0 +1,180 @@
+use crate::cfg::{App, Dependency, ExcludePattern};
+use crate::error::YethError;
+use crate::glob_match::{glob_match, is_glob};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Resolve an `--app` argument to app names. A glob (`worker-*`) selects every app
+/// whose name matches, sorted by name, and must match at least one; a plain name
+/// must name an existing app.
+pub fn select_apps(pattern: &str, apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
+    if !is_glob(pattern) {
+        if !apps.contains_key(pattern) {
+            return Err(crate::suggest::app_not_found(pattern, apps));
+        }
+        return Ok(vec![pattern.to_string()]);
+    }
+
+    let mut selected: Vec<String> = apps
+        .keys()
+        .filter(|app_name| glob_match(pattern, app_name))
+        .cloned()
+        .collect();
+    if selected.is_empty() {
+        return Err(YethError::NoAppMatchesPattern(pattern.to_string()));
+    }
+    selected.sort();
+    Ok(selected)
+}
+
+/// Names of the apps carrying at least one of `tags`, sorted. Every tag must be
+/// carried by some app, so a typo is an error rather than an empty selection.
+pub fn select_tagged_apps(tags: &[String], apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
+    if let Some(unknown) = tags
+        .iter()
+        .find(|tag| !apps.values().any(|app| app.tags.contains(tag)))
+    {
+        return Err(YethError::NoAppWithTag(unknown.clone()));
+    }
+
+    let mut selected: Vec<String> = apps
+        .iter()
+        .filter(|(_, app)| app.tags.iter().any(|tag| tags.contains(tag)))
+        .map(|(app_name, _)| app_name.clone())
+        .collect();
+    selected.sort();
+    Ok(selected)
+}
+
+/// Remove the apps matching any of `patterns` (names or globs, resolved like
+/// `select_apps`) and return their names, sorted. An excluded app that a remaining
+/// app depends on is an error, since the dependent's hash can't be computed
+/// without it. An excluded app nested in a remaining one stays out of that app's
+/// hash, as it would be if it were hashed on its own.
+pub fn exclude_apps(patterns: &[String], apps: &mut HashMap<String, App>) -> Result<Vec<String>, YethError> {
+    let mut excluded = Vec::new();
+    for pattern in patterns {
+        excluded.extend(select_apps(pattern, apps)?);
+    }
+    excluded.sort();
+    excluded.dedup();
+
+    let mut dependents: Vec<&App> = apps
+        .values()
+        .filter(|app| !excluded.contains(&app.name))
+        .collect();
+    dependents.sort_by(|a, b| a.name.cmp(&b.name));
+    for dependent in dependents {
+        for dependency in &dependent.dependencies {
+            if let Dependency::App(app) = dependency
+                && excluded.contains(app)
+            {
+                return Err(YethError::ExcludedAppIsDependency {
+                    app: app.clone(),
+                    dependent: dependent.name.clone(),
+                });
+            }
+        }
+    }
+
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let excluded_dirs: Vec<PathBuf> = excluded
+        .iter()
+        .filter_map(|app_name| apps.remove(app_name))
+        .map(|app| canonical(&app.dir))
+        .collect();
+    for app in apps.values_mut() {
+        let app_dir = canonical(&app.dir);
+        for dir in excluded_dirs.iter().filter(|dir| **dir != app_dir && dir.starts_with(&app_dir)) {
+            app.exclude_patterns.push(ExcludePattern::AbsolutePath(dir.clone()));
+        }
+    }
+    Ok(excluded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_apps() {
+        let apps: HashMap<String, App> = ["worker-email", "worker-sms", "web", "[legacy]"]
+            .into_iter()
+            .map(|name| (name.to_string(), App::default()))
+            .collect();
+
+        assert_eq!(select_apps("worker-*", &apps).unwrap(), vec!["worker-email", "worker-sms"]);
+        assert_eq!(select_apps("w?b", &apps).unwrap(), vec!["web"]);
+        assert_eq!(select_apps("web", &apps).unwrap(), vec!["web"]);
+
+        // No match for a glob, and the usual suggestion for a plain name
+        assert!(matches!(select_apps("api-*", &apps), Err(YethError::NoAppMatchesPattern(p)) if p == "api-*"));
+        assert!(matches!(select_apps("wbe", &apps), Err(YethError::AppNotFound { .. })));
+    }
+
+    #[test]
+    fn test_select_tagged_apps() {
+        let app = |name: &str, tags: &[&str]| {
+            let app = App {
+                name: name.to_string(),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..Default::default()
+            };
+            (name.to_string(), app)
+        };
+        let apps = HashMap::from([
+            app("web", &["frontend"]),
+            app("api", &["backend", "tier1"]),
+            app("billing", &["backend"]),
+            app("cron", &["infra"]),
+            app("shared", &[]),
+        ]);
+        let tags = |tags: &[&str]| -> Vec<String> { tags.iter().map(|tag| tag.to_string()).collect() };
+
+        assert_eq!(select_tagged_apps(&tags(&["backend"]), &apps).unwrap(), vec!["api", "billing"]);
+        assert_eq!(
+            select_tagged_apps(&tags(&["tier1", "frontend"]), &apps).unwrap(),
+            vec!["api", "web"]
+        );
+        assert!(matches!(
+            select_tagged_apps(&tags(&["backend", "mobile"]), &apps),
+            Err(YethError::NoAppWithTag(tag)) if tag == "mobile"
+        ));
+    }
+
+    #[test]
+    fn test_exclude_apps() {
+        let app = |name: &str, dependencies: &[&str]| {
+            let app = App {
+                name: name.to_string(),
+                dependencies: dependencies.iter().map(|dep| Dependency::App(dep.to_string())).collect(),
+                ..Default::default()
+            };
+            (name.to_string(), app)
+        };
+        let all = HashMap::from([
+            app("web", &["common"]),
+            app("common", &[]),
+            app("legacy-a", &[]),
+            app("legacy-b", &[]),
+        ]);
+
+        let mut apps = all.clone();
+        let excluded = exclude_apps(&["legacy-*".to_string(), "legacy-a".to_string()], &mut apps).unwrap();
+        assert_eq!(excluded, vec!["legacy-a", "legacy-b"]);
+        let mut remaining: Vec<_> = apps.keys().cloned().collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["common", "web"]);
+
+        // A dependency of a remaining app can't be excluded, and nothing is removed
+        let mut apps = all.clone();
+        let err = exclude_apps(&["common".to_string()], &mut apps).unwrap_err();
+        assert!(matches!(&err, YethError::ExcludedAppIsDependency { app, dependent } if app == "common" && dependent == "web"));
+        assert_eq!(apps.len(), 4);
+
+        // Excluding the dependent too is fine
+        let mut apps = all.clone();
+        exclude_apps(&["common".to_string(), "web".to_string()], &mut apps).unwrap();
+        assert_eq!(apps.len(), 2);
+    }
+}
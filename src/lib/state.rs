@@ -0,0 +1,84 @@
+use crate::cfg::App;
+use crate::error::YethError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Everything discovery resolved: the apps with their parsed dependencies and
+/// exclude patterns, and the order they are hashed in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    /// App name → app, sorted by name
+    pub apps: BTreeMap<String, App>,
+    /// Dependencies before the apps that use them
+    pub topological_order: Vec<String>,
+}
+
+impl State {
+    pub fn new(apps: &HashMap<String, App>, topological_order: Vec<String>) -> Self {
+        Self {
+            apps: apps.iter().map(|(name, app)| (name.clone(), app.clone())).collect(),
+            topological_order,
+        }
+    }
+
+    /// The apps in the form discovery returns them
+    pub fn into_apps(self) -> HashMap<String, App> {
+        self.apps.into_iter().collect()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), YethError> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, YethError> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::{Dependency, ExcludePattern};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_state_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let state_path = temp_dir.path().join("state.json");
+
+        let lib = App {
+            name: "lib".to_string(),
+            dir: PathBuf::from("/repo/lib"),
+            exclude_patterns: vec![
+                ExcludePattern::Name("target".to_string()),
+                ExcludePattern::Glob("*.log".to_string()),
+            ],
+            path_dependency_excludes: HashMap::from([(
+                PathBuf::from("/repo/proto"),
+                vec![ExcludePattern::Glob("*.pb.go".to_string())],
+            )]),
+            extensions: Some(vec!["rs".to_string()]),
+            dependencies: vec![Dependency::Path(PathBuf::from("/repo/proto"))],
+            ..Default::default()
+        };
+        let web = App {
+            name: "web".to_string(),
+            dir: PathBuf::from("/repo/web"),
+            dependencies: vec![Dependency::App("lib".to_string())],
+            meta: HashMap::from([("owner".to_string(), toml::Value::from("team-web"))]),
+            ..Default::default()
+        };
+        let apps = HashMap::from([("lib".to_string(), lib), ("web".to_string(), web)]);
+        let state = State::new(&apps, vec!["lib".to_string(), "web".to_string()]);
+
+        state.save(&state_path).unwrap();
+        let loaded = State::load(&state_path).unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded.into_apps(), apps);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::cfg::App;
+use crate::error::YethError;
+use std::collections::HashMap;
+
+/// `AppNotFound` for `app_name`, suggesting up to two similarly named apps
+pub(crate) fn app_not_found(app_name: &str, apps: &HashMap<String, App>) -> YethError {
+    YethError::AppNotFound {
+        name: app_name.to_string(),
+        suggestions: closest_matches(app_name, apps.keys().map(String::as_str), 2),
+    }
+}
+
+/// Compute the Levenshtein edit distance between two strings
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+    let mut current = vec![0; b_chars.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b_chars.len()]
+}
+
+/// Find up to `limit` candidates close enough to `target` to be a likely typo, closest first
+pub fn closest_matches<'a, I>(target: &str, candidates: I, limit: usize) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    // Allow roughly one typo per three characters
+    let max_distance = (target.chars().count() / 3).max(1);
+
+    let mut matches: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(target, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort();
+
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("exclude", "exclude"), 0);
+        assert_eq!(edit_distance("exlude", "exclude"), 1);
+        assert_eq!(edit_distance("backnd", "backend"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_closest_matches() {
+        let candidates = ["backend", "frontend", "admin", "shared"];
+
+        assert_eq!(closest_matches("backnd", candidates, 2), vec!["backend"]);
+        assert_eq!(closest_matches("frontent", candidates, 2), vec!["frontend"]);
+        assert!(closest_matches("database", candidates, 2).is_empty());
+    }
+}
@@ -1,8 +1,11 @@
 use crate::cfg::{App, Dependency};
 use crate::error::YethError;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
 
-/// Perform topological sort on applications based on their dependencies
+/// Perform topological sort on applications based on their dependencies.
+/// Among apps whose dependencies are all sorted, names come alphabetically,
+/// so the order is the same on every run.
 pub fn topological_sort(apps: &HashMap<String, App>) -> Result<Vec<String>, YethError> {
     let mut graph: HashMap<String, Vec<String>> = HashMap::with_capacity(apps.len());
     let mut in_degree: HashMap<String, usize> = HashMap::with_capacity(apps.len());
@@ -13,6 +16,9 @@ pub fn topological_sort(apps: &HashMap<String, App>) -> Result<Vec<String>, Yeth
         for dep in &app.dependencies {
             match dep {
                 Dependency::App(dep_name) => {
+                    if dep_name == app_name {
+                        return Err(YethError::SelfDependency(app_name.to_string()));
+                    }
                     if !apps.contains_key(dep_name) {
                         return Err(YethError::DependencyNotFound(
                             dep_name.to_string(),
@@ -26,8 +32,11 @@ pub fn topological_sort(apps: &HashMap<String, App>) -> Result<Vec<String>, Yeth
                     valid_app_deps += 1;
                 }
                 Dependency::Path(path) => {
-                    if !path.exists() {
-                        return Err(YethError::PathDependencyNotFound(
+                    if let Err(error) = path.metadata() {
+                        return Err(YethError::path_dependency(path, app_name, error, false));
+                    }
+                    if paths_overlap(path, &app.dir) {
+                        return Err(YethError::PathDependencyOverlapsApp(
                             path.to_path_buf(),
                             app_name.to_string(),
                         ));
@@ -39,34 +48,69 @@ pub fn topological_sort(apps: &HashMap<String, App>) -> Result<Vec<String>, Yeth
         in_degree.insert(app_name.clone(), valid_app_deps);
     }
 
-    let mut queue = VecDeque::with_capacity(in_degree.len());
-    for (app, &deg) in &in_degree {
-        if deg == 0 {
-            queue.push_back(app.clone());
-        }
-    }
+    let mut ready: BTreeSet<String> = in_degree
+        .iter()
+        .filter(|(_, deg)| **deg == 0)
+        .map(|(app, _)| app.clone())
+        .collect();
 
     let mut topo_order = Vec::with_capacity(in_degree.len());
-    while let Some(app) = queue.pop_front() {
-        topo_order.push(app.clone());
+    while let Some(app) = ready.pop_first() {
         if let Some(neighbors) = graph.get(&app) {
             for neighbor in neighbors {
                 let deg = in_degree.get_mut(neighbor).unwrap();
                 *deg -= 1;
                 if *deg == 0 {
-                    queue.push_back(neighbor.clone());
+                    ready.insert(neighbor.clone());
                 }
             }
         }
+        topo_order.push(app);
     }
 
     if topo_order.len() != apps.len() {
-        return Err(YethError::CircularDependency);
+        return Err(YethError::CircularDependency {
+            cycle: find_cycle(apps, &in_degree),
+        });
     }
 
     Ok(topo_order)
 }
 
+/// Walk dependencies among the apps left unsorted (each has an unsorted dependency)
+/// until one repeats, and return that cycle with its first app repeated at the end
+fn find_cycle(apps: &HashMap<String, App>, in_degree: &HashMap<String, usize>) -> Vec<String> {
+    let is_left = |name: &str| in_degree.get(name).is_some_and(|&deg| deg > 0);
+    let Some(start) = in_degree.keys().filter(|name| is_left(name)).min() else {
+        return Vec::new();
+    };
+
+    let mut path = vec![start.clone()];
+    loop {
+        let current = &apps[path.last().unwrap()];
+        let next = current.dependencies.iter().find_map(|dep| match dep {
+            Dependency::App(dep_name) if is_left(dep_name) => Some(dep_name.clone()),
+            _ => None,
+        });
+        let Some(next) = next else {
+            return path;
+        };
+        if let Some(pos) = path.iter().position(|name| *name == next) {
+            let mut cycle = path.split_off(pos);
+            cycle.push(next);
+            return cycle;
+        }
+        path.push(next);
+    }
+}
+
+/// Check if one path encloses the other, comparing canonical forms when possible
+fn paths_overlap(a: &Path, b: &Path) -> bool {
+    let a = a.canonicalize().unwrap_or_else(|_| a.to_path_buf());
+    let b = b.canonicalize().unwrap_or_else(|_| b.to_path_buf());
+    a.starts_with(&b) || b.starts_with(&a)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,6 +131,7 @@ mod tests {
                 dir: PathBuf::from("/test/app1"),
                 dependencies: vec![],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -98,6 +143,7 @@ mod tests {
                 dir: PathBuf::from("/test/app2"),
                 dependencies: vec![Dependency::App("app1".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -109,6 +155,7 @@ mod tests {
                 dir: PathBuf::from("/test/app3"),
                 dependencies: vec![Dependency::App("app2".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -123,6 +170,7 @@ mod tests {
                     Dependency::App("app3".to_string()),
                 ],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
 
@@ -153,6 +201,31 @@ mod tests {
         assert!(result.contains(&"app4".to_string()));
     }
 
+    #[test]
+    fn test_topological_sort_is_deterministic() {
+        let app = |name: &str, dependencies: &[&str]| {
+            let app = App {
+                name: name.to_string(),
+                dir: PathBuf::from("/test").join(name),
+                dependencies: dependencies.iter().map(|dep| Dependency::App(dep.to_string())).collect(),
+                ..Default::default()
+            };
+            (name.to_string(), app)
+        };
+        let apps = HashMap::from([
+            app("zeta", &[]),
+            app("mid", &["zeta"]),
+            app("alpha", &[]),
+            app("beta", &["alpha"]),
+        ]);
+
+        // Ready apps are taken by name, whatever the map's iteration order
+        let expected = vec!["alpha", "beta", "zeta", "mid"];
+        for _ in 0..10 {
+            assert_eq!(topological_sort(&apps.clone().into_iter().collect()).unwrap(), expected);
+        }
+    }
+
     #[test]
     fn test_topological_sort_with_path_dependencies() {
         let mut apps = HashMap::new();
@@ -169,6 +242,7 @@ mod tests {
                 dir: PathBuf::from("/test/app1"),
                 dependencies: vec![Dependency::Path(shared_lib.clone())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
         
@@ -180,6 +254,7 @@ mod tests {
                 dir: PathBuf::from("/test/app2"),
                 dependencies: vec![Dependency::App("app1".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
         
@@ -209,6 +284,7 @@ mod tests {
                 dir: PathBuf::from("/test/app1"),
                 dependencies: vec![Dependency::App("app2".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
         
@@ -219,12 +295,37 @@ mod tests {
                 dir: PathBuf::from("/test/app2"),
                 dependencies: vec![Dependency::App("app1".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
         
         // Should return an error for circular dependencies
         let result = topological_sort(&apps);
-        assert!(matches!(result, Err(YethError::CircularDependency)));
+        assert!(matches!(result, Err(YethError::CircularDependency { .. })));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Circular dependency detected: app1 -> app2 -> app1"
+        );
+    }
+
+    #[test]
+    fn test_topological_sort_with_self_dependency() {
+        let mut apps = HashMap::new();
+        apps.insert(
+            "app1".to_string(),
+            App {
+                name: "app1".to_string(),
+                dir: PathBuf::from("/test/app1"),
+                dependencies: vec![Dependency::App("app1".to_string())],
+                ..Default::default()
+            },
+        );
+
+        // Reported as such rather than as a one-app cycle
+        let result = topological_sort(&apps);
+        assert!(matches!(&result, Err(YethError::SelfDependency(app)) if app == "app1"));
+        let result = crate::find_app_dependencies::find_app_dependencies("app1", &apps);
+        assert!(matches!(&result, Err(YethError::SelfDependency(app)) if app == "app1"));
     }
 
     #[test]
@@ -239,6 +340,7 @@ mod tests {
                 dir: PathBuf::from("/test/app1"),
                 dependencies: vec![Dependency::App("nonexistent".to_string())],
                 exclude_patterns: vec![],
+                ..Default::default()
             },
         );
         
@@ -246,4 +348,54 @@ mod tests {
         let result = topological_sort(&apps);
         assert!(matches!(result, Err(YethError::DependencyNotFound(_, _))));
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_topological_sort_with_dangling_symlink_dependency() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let link = temp_dir.path().join("shared");
+        std::os::unix::fs::symlink(temp_dir.path().join("deleted"), &link).unwrap();
+
+        let mut apps = HashMap::new();
+        apps.insert(
+            "app1".to_string(),
+            App {
+                name: "app1".to_string(),
+                dir: temp_dir.path().join("app1"),
+                dependencies: vec![Dependency::Path(link)],
+                ..Default::default()
+            },
+        );
+        let result = topological_sort(&apps);
+        assert!(matches!(result, Err(YethError::DanglingSymlink { .. })));
+    }
+
+    #[test]
+    fn test_topological_sort_with_self_enclosing_path_dependency() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app_dir = temp_dir.path().join("app1");
+        let inner_dir = app_dir.join("config");
+        std::fs::create_dir_all(&inner_dir).unwrap();
+
+        // Path dependency inside the app directory
+        let mut apps = HashMap::new();
+        apps.insert(
+            "app1".to_string(),
+            App {
+                name: "app1".to_string(),
+                dir: app_dir.clone(),
+                dependencies: vec![Dependency::Path(inner_dir.clone())],
+                exclude_patterns: vec![],
+                ..Default::default()
+            },
+        );
+        let result = topological_sort(&apps);
+        assert!(matches!(result, Err(YethError::PathDependencyOverlapsApp(_, _))));
+
+        // Path dependency on an ancestor of the app directory
+        apps.get_mut("app1").unwrap().dependencies =
+            vec![Dependency::Path(app_dir.join(".."))];
+        let result = topological_sort(&apps);
+        assert!(matches!(result, Err(YethError::PathDependencyOverlapsApp(_, _))));
+    }
 }
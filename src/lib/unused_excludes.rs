@@ -0,0 +1,119 @@
+use crate::cfg::{App, Dependency, ExcludePattern};
+use crate::run_stats::RunStats;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// An exclude pattern from an app's yeth.toml that left out no file, most likely a typo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedExclude {
+    pub app: String,
+    pub pattern: ExcludePattern,
+    /// The path dependency the pattern was given for, `None` for the app's own `exclude`
+    pub dependency: Option<PathBuf>,
+}
+
+/// Exclude patterns of the hashed apps that excluded nothing in this run, sorted by
+/// app. An app's own patterns also apply to its path dependencies, so they count as
+/// used when they exclude something in either.
+pub fn find_unused_excludes(
+    hashes: &HashMap<String, String>,
+    apps: &HashMap<String, App>,
+    stats: &RunStats,
+) -> Vec<UnusedExclude> {
+    let mut app_names: Vec<&String> = hashes.keys().filter(|name| apps.contains_key(*name)).collect();
+    app_names.sort();
+
+    let mut unused = Vec::new();
+    for app_name in app_names {
+        let app = &apps[app_name];
+        let paths: Vec<&PathBuf> = app
+            .dependencies
+            .iter()
+            .filter_map(|dependency| match dependency {
+                Dependency::Path(path) => Some(path),
+                Dependency::App(_) => None,
+            })
+            .collect();
+
+        for pattern in &app.exclude_patterns {
+            let matched = stats.exclude_matched(app.hashed_dir(), pattern)
+                || paths.iter().any(|path| stats.exclude_matched(path, pattern));
+            if !matched {
+                unused.push(UnusedExclude {
+                    app: app_name.clone(),
+                    pattern: pattern.clone(),
+                    dependency: None,
+                });
+            }
+        }
+        for path in paths {
+            let Some(patterns) = app.path_dependency_excludes.get(path) else {
+                continue;
+            };
+            for pattern in patterns {
+                if !stats.exclude_matched(path, pattern) {
+                    unused.push(UnusedExclude {
+                        app: app_name.clone(),
+                        pattern: pattern.clone(),
+                        dependency: Some(path.clone()),
+                    });
+                }
+            }
+        }
+    }
+    unused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::Config;
+    use crate::YethEngine;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_unused_excludes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("proto")).unwrap();
+        fs::write(root.join("proto").join("api.pb.go"), "package api").unwrap();
+        fs::create_dir_all(root.join("web").join("node_modules")).unwrap();
+        fs::write(root.join("web").join("node_modules").join("lib.js"), "lib").unwrap();
+        fs::write(
+            root.join("web").join("yeth.toml"),
+            r#"[app]
+exclude = ["node_modules", "node_module", "*.log"]
+dependencies = [{ path = "../proto", exclude = ["*.pb.go", "*.pb.rs"] }]
+"#,
+        )
+        .unwrap();
+        fs::write(root.join("proto").join("debug.log"), "log").unwrap();
+
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let engine = YethEngine::new(config);
+        let apps = engine.discover_apps().unwrap();
+        let hashes = engine.run_with_apps(&apps).unwrap();
+
+        // "*.log" only matches in the path dependency, which it applies to as well
+        let unused = engine.find_unused_excludes(&hashes, &apps);
+        assert_eq!(
+            unused,
+            vec![
+                UnusedExclude {
+                    app: "web".to_string(),
+                    pattern: ExcludePattern::Name("node_module".to_string()),
+                    dependency: None,
+                },
+                UnusedExclude {
+                    app: "web".to_string(),
+                    pattern: ExcludePattern::Glob("*.pb.rs".to_string()),
+                    dependency: Some(apps["web"].dir.join("../proto")),
+                },
+            ]
+        );
+
+        // Apps that weren't hashed aren't reported
+        assert!(engine.find_unused_excludes(&HashMap::new(), &apps).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use crate::cfg::Config;
+use crate::error::YethError;
+use crate::YethEngine;
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+/// Outcome of running the whole pipeline twice and comparing the hashes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// How long the first and the second run took
+    pub durations: [Duration; 2],
+    /// Number of applications hashed by the first run
+    pub apps: usize,
+    /// Applications whose hash differs between the runs, sorted by name
+    pub mismatches: Vec<HashMismatch>,
+}
+
+/// One application hashed differently by the two runs. A missing hash means
+/// the application was only discovered by the other run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashMismatch {
+    pub app: String,
+    pub first: Option<String>,
+    pub second: Option<String>,
+}
+
+impl VerifyReport {
+    /// Whether both runs produced the same hash for every application
+    pub fn is_deterministic(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Discover, sort and hash everything twice, each run with its own engine so
+/// no cached digest carries over, and compare the results
+pub fn verify(config: &Config) -> Result<VerifyReport, YethError> {
+    let (first, first_duration) = timed_run(config)?;
+    let (second, second_duration) = timed_run(config)?;
+
+    let names: BTreeSet<&String> = first.keys().chain(second.keys()).collect();
+    let mismatches = names
+        .into_iter()
+        .filter(|name| first.get(*name) != second.get(*name))
+        .map(|name| HashMismatch {
+            app: name.clone(),
+            first: first.get(name).cloned(),
+            second: second.get(name).cloned(),
+        })
+        .collect();
+
+    Ok(VerifyReport {
+        durations: [first_duration, second_duration],
+        apps: first.len(),
+        mismatches,
+    })
+}
+
+fn timed_run(config: &Config) -> Result<(HashMap<String, String>, Duration), YethError> {
+    let start = Instant::now();
+    let hashes = YethEngine::new(config.clone()).run()?;
+    Ok((hashes, start.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for (name, config) in [
+            ("common", ""),
+            ("backend", "[app]\ndependencies = [\"common\"]\n"),
+        ] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("yeth.toml"), config).unwrap();
+            fs::write(dir.join("main.rs"), name).unwrap();
+        }
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+
+        let report = verify(&config).unwrap();
+        assert!(report.is_deterministic());
+        assert_eq!(report.apps, 2);
+
+        let report = VerifyReport {
+            mismatches: vec![HashMismatch {
+                app: "backend".to_string(),
+                first: Some("a".to_string()),
+                second: None,
+            }],
+            ..report
+        };
+        assert!(!report.is_deterministic());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::cfg::{App, VERSION_FILE};
+use crate::error::YethError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Identifies how hashes are computed, recorded in json version files
+pub const HASH_SCHEME: &str = "yeth-sha256-v3";
+
+/// Layout of a yeth.version file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VersionFormat {
+    /// Just the hash
+    #[default]
+    Plain,
+    /// A `VersionDocument`
+    Json,
+}
+
+/// Content of a json yeth.version file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionDocument {
+    pub hash: String,
+    pub short_hash: String,
+    pub scheme: String,
+    /// Seconds since the Unix epoch when the hash was saved
+    pub timestamp: u64,
+    pub dependencies: Vec<DependencyHash>,
+}
+
+/// The hash one dependency contributed to an app's final hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyHash {
+    /// `app:<name>`, or `path:<path>` with the path relative to the root
+    pub dependency: String,
+    pub hash: String,
+}
+
+impl VersionDocument {
+    pub fn new(hash: &str, short_hash_length: usize, dependencies: Vec<DependencyHash>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            hash: hash.to_string(),
+            short_hash: hash.chars().take(short_hash_length).collect(),
+            scheme: HASH_SCHEME.to_string(),
+            timestamp,
+            dependencies,
+        }
+    }
+}
+
+/// Read the saved hash from yeth.version content in either format
+pub fn parse_version_file(content: &str) -> (String, VersionFormat) {
+    let content = content.trim();
+    if content.starts_with('{')
+        && let Ok(document) = serde_json::from_str::<VersionDocument>(content)
+    {
+        return (document.hash, VersionFormat::Json);
+    }
+    (content.to_string(), VersionFormat::Plain)
+}
+
+/// What saving an application's hash would do to its yeth.version file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionWriteAction {
+    /// The file doesn't exist yet
+    Create { app: String, path: PathBuf, hash: String },
+    /// The file holds a different hash, or is in another format
+    Update {
+        app: String,
+        path: PathBuf,
+        old_hash: String,
+        new_hash: String,
+    },
+    /// The file already holds this hash
+    Unchanged { app: String, path: PathBuf, hash: String },
+}
+
+impl VersionWriteAction {
+    /// Whether applying the action touches the file
+    pub fn is_change(&self) -> bool {
+        !matches!(self, VersionWriteAction::Unchanged { .. })
+    }
+}
+
+/// Plan writing each hash to its app's yeth.version, without touching any file.
+/// `hashes` holds the values to write (already shortened when short hashes are wanted).
+pub fn plan_version_writes(
+    apps: &HashMap<String, App>,
+    hashes: &HashMap<String, String>,
+    format: VersionFormat,
+) -> Result<Vec<VersionWriteAction>, YethError> {
+    let mut app_names: Vec<&String> = hashes.keys().collect();
+    app_names.sort();
+
+    let mut actions = Vec::with_capacity(app_names.len());
+    for app_name in app_names {
+        let app = apps
+            .get(app_name)
+            .ok_or_else(|| crate::suggest::app_not_found(app_name, apps))?;
+        let path = app.dir.join(VERSION_FILE);
+        let hash = hashes[app_name].clone();
+        let app = app_name.clone();
+
+        let action = match fs::read_to_string(&path).map(|content| parse_version_file(&content)) {
+            Ok((saved, saved_format)) if saved == hash && saved_format == format => {
+                VersionWriteAction::Unchanged { app, path, hash }
+            }
+            Ok((saved, _)) => VersionWriteAction::Update {
+                app,
+                path,
+                old_hash: saved,
+                new_hash: hash,
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                VersionWriteAction::Create { app, path, hash }
+            }
+            Err(e) => return Err(e.into()),
+        };
+        actions.push(action);
+    }
+    Ok(actions)
+}
+
+/// Write the files a plan creates or updates, each holding just the hash
+pub fn apply_version_writes(actions: &[VersionWriteAction]) -> Result<(), YethError> {
+    apply_version_writes_with(actions, |_, hash| Ok(hash.to_string()))
+}
+
+/// Write the files a plan creates or updates, with `render(app, hash)` as content
+pub fn apply_version_writes_with<F>(actions: &[VersionWriteAction], mut render: F) -> Result<(), YethError>
+where
+    F: FnMut(&str, &str) -> Result<String, YethError>,
+{
+    for action in actions {
+        match action {
+            VersionWriteAction::Create { app, path, hash } => fs::write(path, render(app, hash)?)?,
+            VersionWriteAction::Update { app, path, new_hash, .. } => {
+                fs::write(path, render(app, new_hash)?)?
+            }
+            VersionWriteAction::Unchanged { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_version_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let mut apps = HashMap::new();
+        let mut hashes = HashMap::new();
+        for name in ["created", "changed", "unchanged"] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    ..Default::default()
+                },
+            );
+            hashes.insert(name.to_string(), format!("{}-hash", name));
+        }
+        fs::write(root.join("changed").join(VERSION_FILE), "old-hash").unwrap();
+        fs::write(root.join("unchanged").join(VERSION_FILE), "unchanged-hash\n").unwrap();
+
+        let actions = plan_version_writes(&apps, &hashes, VersionFormat::Plain).unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                VersionWriteAction::Update {
+                    app: "changed".to_string(),
+                    path: root.join("changed").join(VERSION_FILE),
+                    old_hash: "old-hash".to_string(),
+                    new_hash: "changed-hash".to_string(),
+                },
+                VersionWriteAction::Create {
+                    app: "created".to_string(),
+                    path: root.join("created").join(VERSION_FILE),
+                    hash: "created-hash".to_string(),
+                },
+                VersionWriteAction::Unchanged {
+                    app: "unchanged".to_string(),
+                    path: root.join("unchanged").join(VERSION_FILE),
+                    hash: "unchanged-hash".to_string(),
+                },
+            ]
+        );
+
+        // Planning writes nothing; applying leaves nothing left to change
+        assert!(!root.join("created").join(VERSION_FILE).exists());
+        apply_version_writes(&actions).unwrap();
+        let replanned = plan_version_writes(&apps, &hashes, VersionFormat::Plain).unwrap();
+        assert!(replanned.iter().all(|action| !action.is_change()));
+    }
+
+    #[test]
+    fn test_json_version_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("app");
+        fs::create_dir_all(&dir).unwrap();
+        let apps = HashMap::from([(
+            "app".to_string(),
+            App {
+                name: "app".to_string(),
+                dir: dir.clone(),
+                ..Default::default()
+            },
+        )]);
+        let hash = "a".repeat(64);
+        let hashes = HashMap::from([("app".to_string(), hash.clone())]);
+        fs::write(dir.join(VERSION_FILE), &hash).unwrap();
+
+        // Same hash in another format is rewritten
+        let actions = plan_version_writes(&apps, &hashes, VersionFormat::Json).unwrap();
+        assert!(matches!(&actions[0], VersionWriteAction::Update { old_hash, .. } if *old_hash == hash));
+
+        let dependencies = vec![DependencyHash {
+            dependency: "lib".to_string(),
+            hash: "b".repeat(64),
+        }];
+        apply_version_writes_with(&actions, |_, hash| {
+            let document = VersionDocument::new(hash, 10, dependencies.clone());
+            Ok(serde_json::to_string_pretty(&document)?)
+        })
+        .unwrap();
+
+        // The json document parses back to the same hash
+        let content = fs::read_to_string(dir.join(VERSION_FILE)).unwrap();
+        assert_eq!(parse_version_file(&content), (hash.clone(), VersionFormat::Json));
+        let document: VersionDocument = serde_json::from_str(&content).unwrap();
+        assert_eq!(document.short_hash, "a".repeat(10));
+        assert_eq!(document.dependencies, dependencies);
+        let replanned = plan_version_writes(&apps, &hashes, VersionFormat::Json).unwrap();
+        assert!(!replanned[0].is_change());
+    }
+}
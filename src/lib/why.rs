@@ -0,0 +1,170 @@
+use crate::calculate_hashes::{calculate_hash_details, AppHashDetail};
+use crate::cfg::{App, Config, Dependency};
+use crate::error::YethError;
+use crate::run_stats::RunStats;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// An app in the dependency closure with its own directory hash and final hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WhyNode {
+    pub app: String,
+    pub own_hash: String,
+    pub final_hash: String,
+    pub dependencies: Vec<WhyDependency>,
+}
+
+/// One dependency edge and the hash it carried into the dependent app
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WhyDependency {
+    App(WhyNode),
+    /// An app already expanded elsewhere in the tree
+    Repeated { app: String, final_hash: String },
+    Path { path: PathBuf, hash: String },
+}
+
+/// Hash `app_name` and its transitive dependencies and explain how its hash was
+/// built: every app in the closure with its own and final hash, as a tree
+pub fn explain(
+    app_name: &str,
+    apps: &HashMap<String, App>,
+    config: &Config,
+    stats: &RunStats,
+) -> Result<WhyNode, YethError> {
+    let order = crate::find_app_dependencies::find_app_dependencies(app_name, apps)?;
+    let details = calculate_hash_details(order, apps, config, stats)?;
+    let mut expanded = HashSet::new();
+    Ok(build_node(app_name, &details, &mut expanded))
+}
+
+fn build_node(app_name: &str, details: &HashMap<String, AppHashDetail>, expanded: &mut HashSet<String>) -> WhyNode {
+    expanded.insert(app_name.to_string());
+    let detail = &details[app_name];
+    let dependencies = detail
+        .dependencies
+        .iter()
+        .map(|(dep, hash)| match dep {
+            Dependency::App(dep_name) if expanded.contains(dep_name) => WhyDependency::Repeated {
+                app: dep_name.clone(),
+                final_hash: hash.clone(),
+            },
+            Dependency::App(dep_name) => WhyDependency::App(build_node(dep_name, details, expanded)),
+            Dependency::Path(path) => WhyDependency::Path {
+                path: path.clone(),
+                hash: hash.clone(),
+            },
+        })
+        .collect();
+
+    WhyNode {
+        app: app_name.to_string(),
+        own_hash: detail.own_hash.clone(),
+        final_hash: detail.final_hash.clone(),
+        dependencies,
+    }
+}
+
+impl WhyNode {
+    /// Render as a text tree, hashes shortened to `hash_length` characters
+    pub fn render(&self, hash_length: usize) -> Vec<String> {
+        let short = |hash: &str| -> String { hash.chars().take(hash_length).collect() };
+        let mut lines = vec![format!(
+            "{} own={} final={}",
+            self.app,
+            short(&self.own_hash),
+            short(&self.final_hash)
+        )];
+        self.render_dependencies("  ", &short, &mut lines);
+        lines
+    }
+
+    fn render_dependencies(&self, indent: &str, short: &dyn Fn(&str) -> String, lines: &mut Vec<String>) {
+        for (i, dep) in self.dependencies.iter().enumerate() {
+            let is_last = i == self.dependencies.len() - 1;
+            let prefix = if is_last { "└─" } else { "├─" };
+            match dep {
+                WhyDependency::App(node) => {
+                    lines.push(format!(
+                        "{}{} {} (app) own={} final={}",
+                        indent,
+                        prefix,
+                        node.app,
+                        short(&node.own_hash),
+                        short(&node.final_hash)
+                    ));
+                    let child_indent = format!("{}{}", indent, if is_last { "   " } else { "│  " });
+                    node.render_dependencies(&child_indent, short, lines);
+                }
+                WhyDependency::Repeated { app, final_hash } => {
+                    lines.push(format!("{}{} {} (…) final={}", indent, prefix, app, short(final_hash)));
+                }
+                WhyDependency::Path { path, hash } => {
+                    let kind = if path.is_file() { "file" } else { "dir" };
+                    lines.push(format!("{}{} {} ({}) hash={}", indent, prefix, path.display(), kind, short(hash)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_explain() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().root(root.to_path_buf()).build().unwrap();
+        let migrations = root.join("migrations");
+        fs::create_dir_all(&migrations).unwrap();
+        fs::write(migrations.join("001.sql"), "create table").unwrap();
+
+        let mut apps = HashMap::new();
+        for (name, dependencies) in [
+            ("common", vec![]),
+            ("backend", vec![Dependency::App("common".to_string()), Dependency::Path(migrations.clone())]),
+            ("frontend", vec![Dependency::App("backend".to_string()), Dependency::App("common".to_string())]),
+        ] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.rs"), name).unwrap();
+            apps.insert(
+                name.to_string(),
+                App {
+                    name: name.to_string(),
+                    dir,
+                    dependencies,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let before = explain("frontend", &apps, &config, &RunStats::default()).unwrap();
+        fs::write(migrations.join("001.sql"), "create table users").unwrap();
+        let after = explain("frontend", &apps, &config, &RunStats::default()).unwrap();
+
+        // The change travels through backend's path dependency; common and own hashes stay
+        assert_eq!(after.own_hash, before.own_hash);
+        assert_ne!(after.final_hash, before.final_hash);
+        let [WhyDependency::App(backend_before), WhyDependency::Repeated { .. }] = &before.dependencies[..] else {
+            panic!("unexpected tree: {:?}", before.dependencies);
+        };
+        let WhyDependency::App(backend_after) = &after.dependencies[0] else {
+            panic!("unexpected tree: {:?}", after.dependencies);
+        };
+        assert_eq!(backend_after.own_hash, backend_before.own_hash);
+        assert_eq!(backend_after.dependencies[0], backend_before.dependencies[0]);
+        assert_ne!(backend_after.dependencies[1], backend_before.dependencies[1]);
+
+        let lines = after.render(6);
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with("  ├─ backend (app) own="));
+        assert!(lines[2].starts_with("  │  ├─ common (app) own="));
+        assert!(lines[4].starts_with("  └─ common (…) final="));
+    }
+}
@@ -1,15 +1,32 @@
 mod cli;
+mod color;
+mod template;
 
 use anyhow::Result;
 use clap::Parser;
-use yeth::{cfg::{App, Config, Dependency}, error::YethError, YethEngine};
-use std::{collections::HashMap, time::Instant};
+use yeth::{cfg::{App, Config}, diff::{self, ManifestStatus}, error::YethError, image_tags::ImageTagOptions, lockfile::LockMismatch, run_stats::{Phase, RunStats}, version_writes::{self, VersionDocument, VersionFormat, VersionWriteAction}, YethEngine};
+use std::{collections::{BTreeMap, HashMap}, io::Write, path::{Path, PathBuf}, time::Instant};
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
 
-use cli::Cli;
+use cli::{Cli, Command, OutputFormat, SortBy};
+use color::Palette;
 
-fn main() -> Result<()> {
-    let args = Cli::parse().validate()?;
+fn main() {
+    let args = Cli::parse().validate().unwrap_or_else(|error| error.exit());
+    let errors = Palette::new(args.color, &std::io::stderr());
+    if let Err(error) = try_main(args) {
+        // What returning the error from main prints, with the label colored
+        eprintln!("{} {:?}", errors.error("Error:"), error);
+        std::process::exit(1);
+    }
+}
+
+fn try_main(args: Cli) -> Result<()> {
+    if let Some(shell) = args.completions {
+        cli::write_completions(shell, &mut std::io::stdout());
+        return Ok(());
+    }
     
     // Check if benchmarking mode is enabled
     if let Some(iterations) = args.bench {
@@ -18,11 +35,80 @@ fn main() -> Result<()> {
     
     let start_time = Instant::now();
 
-    let config = Config::builder().root(args.root).build()?;
+    let config = build_config(&args)?;
+    // Library errors show paths relative to the root, like the rest of the output
+    let paths = config.clone();
+    run(args, config, start_time).map_err(|err| match err.downcast::<YethError>() {
+        Ok(err) => err.with_display_paths(&paths).into(),
+        Err(err) => err,
+    })
+}
 
-    let engine = YethEngine::new(config);
+fn run(args: Cli, config: Config, start_time: Instant) -> Result<()> {
+    let root = config.root.clone();
+
+    if let Some(Command::Init { path, dependencies, force }) = &args.command {
+        let apps = YethEngine::new(config.clone()).discover_apps()?;
+        let dir = path.clone().unwrap_or_else(|| PathBuf::from("."));
+        let outcome = yeth::init::init_app(&dir, dependencies, *force, &apps)?;
+        if let Some(owner) = &outcome.enclosing_app {
+            config.warn(format_args!(
+                "'{}' is inside application '{}', whose hash will no longer include its files",
+                config.display_path(&dir).display(),
+                owner
+            ));
+        }
+        if !args.quiet {
+            println!("Created {}", config.display_path(&outcome.path).display());
+        }
+        return Ok(());
+    }
+
+    // Colors only for text meant to be read, and never in a file
+    let output_file = args.output.as_ref().filter(|path| path.as_os_str() != "-");
+    let palette = if args.format != OutputFormat::Text || args.template.is_some() || args.print0 || output_file.is_some() {
+        Palette::plain()
+    } else {
+        Palette::new(args.color, &std::io::stdout())
+    };
+    let errors = Palette::new(args.color, &std::io::stderr());
+
+    let mut engine = YethEngine::new(config);
+    if args.verbose {
+        engine = engine.with_app_timings();
+    }
+
+    // Determinism self-check, each run discovers and hashes from scratch
+    if args.verify {
+        let report = engine.verify()?;
+        if report.apps == 0 {
+            return Err(YethError::NoApplicationsFound.into());
+        }
+        if !args.quiet {
+            println!("First run: {:.2?}", report.durations[0]);
+            println!("Second run: {:.2?}", report.durations[1]);
+        }
+        if !report.is_deterministic() {
+            eprintln!("Hashes differ between runs:");
+            for mismatch in &report.mismatches {
+                let missing = "(missing)".to_string();
+                eprintln!(
+                    "  {}: {} -> {}",
+                    mismatch.app,
+                    mismatch.first.as_ref().unwrap_or(&missing),
+                    mismatch.second.as_ref().unwrap_or(&missing)
+                );
+            }
+            std::process::exit(1);
+        }
+        if !args.quiet {
+            println!("All {} application hashes match", report.apps);
+        }
+        return Ok(());
+    }
 
-    let apps = engine.discover_apps()?;
+    let mut apps = engine.discover_apps()?;
+    engine.exclude_apps(&args.exclude_app, &mut apps)?;
 
     if apps.is_empty() {
         return Err(YethError::NoApplicationsFound.into());
@@ -30,17 +116,99 @@ fn main() -> Result<()> {
 
     // If dependency graph requested
     if args.show_graph {
-        print_dependency_graph(apps);
+        if args.format == OutputFormat::Json {
+            let graph = engine.dependency_graph(&apps);
+            println!("{}", serde_json::to_string_pretty(&graph)?);
+        } else if args.format == OutputFormat::Dot {
+            print!("{}", engine.dependency_graph(&apps).to_dot());
+        } else {
+            let trees = engine.render_dependency_tree_styled(
+                &apps,
+                args.graph_app.as_deref(),
+                args.graph_depth,
+                palette.tree_style(),
+            )?;
+            print_dependency_graph(trees, args.print0, args.quiet);
+        }
         return Ok(());
     }
 
-    let ordered_apps = engine.topological_sort(&apps)?;
-    let hashes = if let Some(app_name) = &args.app {
-        engine.calculate_hashes_for_app(app_name, &apps)?
-    } else {
-        engine.calculate_hashes(ordered_apps, &apps)?
+    // --app may be a glob selecting several applications, --tag selects by category
+    let selected_apps = select_apps(&engine, &args, &apps)?;
+    if args.hash_only
+        && let Some(selected) = &selected_apps
+        && selected.len() > 1
+    {
+        anyhow::bail!(
+            "--hash-only needs a single application, but '{}' matches {}",
+            args.app.as_deref().unwrap_or_default(),
+            selected.join(", ")
+        );
+    }
+    let is_requested = |app_name: &str| -> bool {
+        selected_apps
+            .as_ref()
+            .is_none_or(|selected| selected.iter().any(|s| s == app_name))
     };
 
+    // If a hash explanation requested
+    if args.why && let Some(selected) = &selected_apps {
+        let trees = selected
+            .iter()
+            .map(|app_name| engine.explain(app_name, &apps))
+            .collect::<Result<Vec<_>, _>>()?;
+        if args.format == OutputFormat::Json {
+            match trees.as_slice() {
+                [tree] => println!("{}", serde_json::to_string_pretty(tree)?),
+                trees => println!("{}", serde_json::to_string_pretty(trees)?),
+            }
+        } else {
+            for tree in &trees {
+                for line in tree.render(args.short_hash_length) {
+                    print_record(&line, args.print0);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // If a state dump requested
+    if let Some(path) = &args.dump_state {
+        engine.state(&apps)?.save(path)?;
+        return Ok(());
+    }
+
+    // If only the list of applications requested
+    if args.list_apps {
+        let listing: BTreeMap<&String, ListedApp> = apps
+            .iter()
+            .filter(|(app_name, _)| is_requested(app_name))
+            .map(|(app_name, app)| (app_name, ListedApp { dir: app.relative_dir(&root), tags: &app.tags }))
+            .collect();
+        if args.format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&listing)?);
+        } else {
+            for (app_name, listed) in &listing {
+                let dir = listed.dir.display().to_string();
+                let mut record = format!("{} {}", palette.app(app_name), palette.path(&dir));
+                if !listed.tags.is_empty() {
+                    record = format!("{} {}", record, listed.tags.join(","));
+                }
+                print_record(&record, args.print0);
+            }
+        }
+        return Ok(());
+    }
+
+    // If orphan report requested
+    if args.orphans {
+        let requested = selected_apps.clone().unwrap_or_default();
+        for orphan in engine.find_orphans(&apps, &requested) {
+            print_record(&orphan, args.print0);
+        }
+        return Ok(());
+    }
+
     let format_hash = |hash: &str| -> String {
         if args.short_hash {
             hash.chars().take(args.short_hash_length).collect()
@@ -48,84 +216,581 @@ fn main() -> Result<()> {
             hash.to_string()
         }
     };
+    // A `<hash> <app>` output line, with the app directory when requested
+    let hash_record = |hash: &str, app_name: &str| -> String {
+        if let Some(template) = &args.template {
+            let dir = apps[app_name].relative_dir(&root);
+            template.render(app_name, hash, &dir.to_string_lossy())
+        } else if args.show_dir {
+            let dir = apps[app_name].relative_dir(&root).display().to_string();
+            format!("{} {} {}", palette.hash(hash), palette.app(app_name), palette.path(&dir))
+        } else {
+            format!("{} {}", palette.hash(hash), palette.app(app_name))
+        }
+    };
+
+    let mut failures = Vec::new();
+    // The order of every application, kept when hashing sorts them so that
+    // --sort-by topo doesn't sort again
+    let mut ordered_apps = None;
+    let hashes = if args.keep_going {
+        let order = match &selected_apps {
+            Some(selected) => engine.combined_dependency_order(selected, &apps)?,
+            None => ordered_apps.insert(engine.topological_sort(&apps)?).clone(),
+        };
+        let (hashes, failed) = engine.calculate_hashes_keep_going(order, &apps);
+        failures = failed;
+        hashes
+    } else if let Some(selected) = &selected_apps {
+        engine.calculate_hashes_for_apps(selected, &apps)?
+    } else if let Some(app_name) = &args.impact {
+        engine.calculate_impact_hashes(app_name, &apps)?
+    } else if args.stream {
+        engine.run_streaming(&apps, |app_name, hash| {
+            print_record(&hash_record(&format_hash(hash), app_name), args.print0);
+            let _ = std::io::stdout().flush();
+        })?
+    } else {
+        let order = ordered_apps.insert(engine.topological_sort(&apps)?).clone();
+        engine.calculate_hashes(order, &apps)?
+    };
+
+    if args.warn_collisions {
+        engine.warn_collisions(&hashes);
+    }
+    if args.warn_unused_excludes {
+        engine.warn_unused_excludes(&hashes, &apps);
+    }
+
+    // Detect changes before version files get overwritten
+    let changed = if let Some(path) = args.input_file.as_ref().filter(|_| args.changed) {
+        let hash_diff = diff::diff_with_saved(&diff::load_hashes(path)?, &hashes);
+        Some(hash_diff.added.into_iter().chain(hash_diff.changed).collect())
+    } else if args.changed {
+        Some(engine.changed_apps(&apps, &hashes)?)
+    } else if let Some(git_ref) = &args.since {
+        let changed_files = engine.changed_files_since(git_ref)?;
+        Some(engine.apps_affected_by(&changed_files, &apps))
+    } else {
+        None
+    };
 
     // Save hashes to files if needed
     if args.write_versions {
-        for (app_name, hash) in &hashes {
-            let app = apps.get(app_name).unwrap();
-            let version_file = app.dir.join("yeth.version");
-            let formatted_hash = format_hash(hash);
-            std::fs::write(&version_file, formatted_hash)?;
+        // A json document carries the full hash next to the short one
+        let version_format = VersionFormat::from(args.version_format);
+        let formatted: HashMap<String, String> = hashes
+            .iter()
+            .map(|(app_name, hash)| match version_format {
+                VersionFormat::Plain => (app_name.clone(), format_hash(hash)),
+                VersionFormat::Json => (app_name.clone(), hash.clone()),
+            })
+            .collect();
+        let actions = engine.plan_version_writes(&apps, &formatted, version_format)?;
+
+        if args.dry_run {
+            for action in actions.iter().filter(|_| !args.quiet) {
+                match action {
+                    VersionWriteAction::Create { path, hash, .. } => {
+                        println!("{} {} ({})", palette.changed("create"), path.display(), hash)
+                    }
+                    VersionWriteAction::Update { path, old_hash, new_hash, .. } => {
+                        println!("{} {} ({} -> {})", palette.changed("update"), path.display(), old_hash, new_hash)
+                    }
+                    VersionWriteAction::Unchanged { path, .. } => {
+                        println!("unchanged {}", path.display())
+                    }
+                }
+            }
+            if actions.iter().any(|action| action.is_change()) {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+
+        match version_format {
+            VersionFormat::Plain => version_writes::apply_version_writes(&actions)?,
+            VersionFormat::Json => version_writes::apply_version_writes_with(&actions, |app_name, hash| {
+                let dependencies = engine.dependency_hashes(app_name, &apps, &hashes)?;
+                let document = VersionDocument::new(hash, args.short_hash_length, dependencies);
+                Ok(serde_json::to_string_pretty(&document)? + "\n")
+            })?,
         }
     }
 
-    // Output results
-    if let Some(app_name) = &args.app {
-        // Output for specific application
-        if let Some(hash) = hashes.get(app_name) {
-            let formatted_hash = format_hash(hash);
+    if args.write_lock {
+        engine.write_lock(&hashes)?;
+    }
+
+    if let Some(path) = &args.output_file {
+        diff::save_hashes(path, &hashes)?;
+    }
+
+    if args.frozen {
+        let mismatches = engine.check_lock(&hashes)?;
+        if !mismatches.is_empty() {
+            eprintln!("yeth.lock is out of date:");
+            for mismatch in &mismatches {
+                match mismatch {
+                    LockMismatch::Scheme { locked, current } => {
+                        eprintln!("  scheme {} -> {}", locked, current)
+                    }
+                    LockMismatch::Changed { app, locked, current } => {
+                        eprintln!("  {} {}: {} -> {}", errors.changed("changed"), app, locked, current)
+                    }
+                    LockMismatch::Added { app, current } => eprintln!("  added {}: {}", app, current),
+                    LockMismatch::Removed { app, locked } => eprintln!("  removed {}: {}", app, locked),
+                }
+            }
+            std::process::exit(1);
+        }
+    }
+
+    let is_selected = |app_name: &str| -> bool {
+        changed
+            .as_ref()
+            .is_none_or(|changed| changed.iter().any(|c| c == app_name))
+    };
+
+    // Compare with the hashes of an earlier run, as far as the selected apps go
+    let hash_diff = match &args.diff {
+        Some(path) => {
+            let before: HashMap<String, String> = diff::load_hashes(path)?
+                .into_iter()
+                .filter(|(app_name, _)| is_requested(app_name))
+                .collect();
+            let after: HashMap<String, String> = hashes
+                .iter()
+                .filter(|(app_name, _)| is_requested(app_name))
+                .map(|(app_name, hash)| (app_name.clone(), hash.clone()))
+                .collect();
+            Some(diff::diff_with_saved(&before, &after))
+        }
+        None => None,
+    };
+
+    // Compare with a baseline manifest, which always answers through the exit code too
+    let manifest_diff = match &args.baseline {
+        Some(path) => {
+            let baseline: HashMap<String, String> = diff::load_hashes(path)?
+                .into_iter()
+                .filter(|(app_name, _)| is_requested(app_name))
+                .collect();
+            let current: HashMap<String, String> = hashes
+                .iter()
+                .filter(|(app_name, _)| is_requested(app_name))
+                .map(|(app_name, hash)| (app_name.clone(), hash.clone()))
+                .collect();
+            Some(diff::compare_manifests(&baseline, &current))
+        }
+        None => None,
+    };
+    let baseline_differs = manifest_diff.as_ref().is_some_and(|manifest_diff| manifest_diff.has_changes());
+
+    // With --quiet, --changed, --since and --diff answer through the exit code only
+    let changes_found = (changed.is_some()
+        && hashes
+            .keys()
+            .filter(|app_name| is_requested(app_name))
+            .any(|app_name| is_selected(app_name)))
+        || hash_diff.as_ref().is_some_and(|hash_diff| !hash_diff.is_empty());
+
+    // Output results, collected so --output can save them in one go
+    let mut results: Vec<u8> = Vec::new();
+    if args.stream {
+        // Already printed while hashing
+    } else if args.quiet && (changed.is_some() || hash_diff.is_some() || manifest_diff.is_some()) {
+        // Reported through the exit code below
+    } else if let Some(manifest_diff) = &manifest_diff {
+        if args.format == OutputFormat::Json {
+            writeln!(results, "{}", serde_json::to_string_pretty(manifest_diff)?)?;
+        } else {
+            for (app_name, status) in &manifest_diff.apps {
+                let label = status.to_string();
+                let label = if *status == ManifestStatus::Unchanged { label } else { palette.changed(&label) };
+                write_record(&mut results, &format!("{} {}", label, palette.app(app_name)), args.print0)?;
+            }
+        }
+    } else if let Some(hash_diff) = &hash_diff {
+        if args.format == OutputFormat::Json {
+            writeln!(results, "{}", serde_json::to_string_pretty(hash_diff)?)?;
+        } else {
+            let labeled = [("added", &hash_diff.added), ("removed", &hash_diff.removed), ("changed", &hash_diff.changed)];
+            for (label, app_names) in labeled {
+                for app_name in app_names {
+                    write_record(&mut results, &format!("{} {}", palette.changed(label), palette.app(app_name)), args.print0)?;
+                }
+            }
+        }
+    } else if args.image_tag {
+        let output_apps: Vec<&String> = match &selected_apps {
+            Some(selected) => selected.iter().filter(|app_name| hashes.contains_key(*app_name)).collect(),
+            None => sort_apps(&engine, &apps, &hashes, args.sort_by, &mut ordered_apps)?,
+        };
+        let output_apps: Vec<&String> = output_apps
+            .into_iter()
+            .filter(|app_name| is_selected(app_name))
+            .collect();
+        let shown: HashMap<String, String> = output_apps
+            .iter()
+            .map(|app_name| (app_name.to_string(), hashes[*app_name].clone()))
+            .collect();
+        let options = ImageTagOptions {
+            registry: args.registry.clone(),
+            tag_prefix: args.tag_prefix.clone(),
+            hash_length: args.short_hash_length,
+        };
+        let image_refs = engine.image_refs(&shown, &options)?;
+        if args.format == OutputFormat::Json {
+            writeln!(results, "{}", serde_json::to_string_pretty(&image_refs)?)?;
+        } else {
+            for app_name in output_apps {
+                write_record(&mut results, &image_refs[app_name], args.print0)?;
+            }
+        }
+    } else if args.format == OutputFormat::Github {
+        let output_apps: Vec<&String> = match &selected_apps {
+            Some(selected) => selected.iter().collect(),
+            None => sort_apps(&engine, &apps, &hashes, args.sort_by, &mut ordered_apps)?,
+        };
+        let output_apps: Vec<&String> = output_apps
+            .into_iter()
+            .filter(|app_name| is_selected(app_name))
+            .collect();
+        write_github_output(&mut results, &output_apps)?;
+    } else if args.format == OutputFormat::Json && args.sort_by == SortBy::Topo {
+        // An array keeps the order; --app selects its dependency chain
+        let selected: Vec<JsonOrderedEntry> = sort_apps(&engine, &apps, &hashes, args.sort_by, &mut ordered_apps)?
+            .into_iter()
+            .filter(|app_name| is_selected(app_name))
+            .map(|app_name| JsonOrderedEntry {
+                app: app_name,
+                hash: format_hash(&hashes[app_name]),
+                dir: apps[app_name].relative_dir(&root),
+                meta: apps[app_name].meta.iter().collect(),
+            })
+            .collect();
+        writeln!(results, "{}", serde_json::to_string_pretty(&selected)?)?;
+    } else if args.format == OutputFormat::Json {
+        let selected: BTreeMap<&String, JsonAppEntry> = hashes
+            .iter()
+            .filter(|(app_name, _)| is_requested(app_name))
+            .filter(|(app_name, _)| is_selected(app_name))
+            .map(|(app_name, hash)| {
+                let entry = JsonAppEntry {
+                    hash: format_hash(hash),
+                    dir: apps[app_name].relative_dir(&root),
+                    meta: apps[app_name].meta.iter().collect(),
+                };
+                (app_name, entry)
+            })
+            .collect();
+        writeln!(results, "{}", serde_json::to_string_pretty(&selected)?)?;
+    } else if let Some(selected) = &selected_apps
+        && args.sort_by == SortBy::Topo
+        && selected.iter().all(|app_name| hashes.contains_key(app_name))
+    {
+        // The applications' dependency chains, each ending with the application itself
+        for app in sort_apps(&engine, &apps, &hashes, args.sort_by, &mut ordered_apps)? {
+            if !is_selected(app) {
+                continue;
+            }
+            let formatted_hash = format_hash(&hashes[app]);
             if args.hash_only {
-                println!("{}", formatted_hash);
+                write_record(&mut results, &formatted_hash, args.print0)?;
             } else {
-                println!("{} {}", formatted_hash, app_name);
+                write_record(&mut results, &hash_record(&formatted_hash, app), args.print0)?;
+            }
+        }
+    } else if let Some(selected) = &selected_apps {
+        // Output for the selected applications
+        for app_name in selected {
+            if let Some(hash) = hashes.get(app_name) {
+                if is_selected(app_name) {
+                    let formatted_hash = format_hash(hash);
+                    if args.hash_only {
+                        write_record(&mut results, &formatted_hash, args.print0)?;
+                    } else {
+                        write_record(&mut results, &hash_record(&formatted_hash, app_name), args.print0)?;
+                    }
+                }
+            } else if failures.is_empty() {
+                eprintln!("Application '{}' produced no hash", app_name);
+                std::process::exit(1);
             }
-        } else {
-            eprintln!("Application '{}' not found", app_name);
-            std::process::exit(1);
         }
     } else {
         // Output all applications
-        let mut sorted_apps: Vec<_> = hashes.keys().collect();
-        sorted_apps.sort();
-        for app in sorted_apps {
-            let hash = hashes.get(app).unwrap();
+        for app in sort_apps(&engine, &apps, &hashes, args.sort_by, &mut ordered_apps)? {
+            if !is_selected(app) {
+                continue;
+            }
+            let Some(hash) = hashes.get(app) else {
+                continue;
+            };
             let formatted_hash = format_hash(hash);
-            println!("{} {}", formatted_hash, app);
+            write_record(&mut results, &hash_record(&formatted_hash, app), args.print0)?;
         }
     }
 
+    if output_file.is_none() {
+        std::io::stdout().write_all(&results)?;
+    }
+
     // Statistics
     if args.verbose {
         let elapsed_time = start_time.elapsed();
-        println!();
-        println!("Execution time: {:.2?}", elapsed_time);
-        println!("Applications processed: {}", hashes.len());
+        eprintln!();
+        eprintln!("Execution time: {:.2?}", elapsed_time);
+        eprintln!("Applications processed: {}", hashes.len());
+        print_phase_timings(engine.stats());
+    }
+
+    if args.stats_json {
+        let stats = engine.stats();
+        let summary = serde_json::json!({
+            "elapsed_ms": start_time.elapsed().as_millis() as u64,
+            "apps_processed": hashes.len(),
+            "files_hashed": stats.files_hashed(),
+            "bytes_hashed": stats.bytes_hashed(),
+            "files_reused": stats.files_reused(),
+            "files_skipped": stats.files_skipped(),
+        });
+        eprintln!("{}", summary);
+    }
+
+    if !failures.is_empty() {
+        eprintln!("Failed to hash {} application(s):", failures.len());
+        for (app_name, error) in &failures {
+            eprintln!("  {}: {}", app_name, error);
+        }
+        std::process::exit(1);
+    }
+
+    // Only a complete, successful run replaces the output file
+    if let Some(path) = output_file {
+        write_atomically(path, &results)?;
+        if args.verbose {
+            eprintln!("Results written to {}", path.display());
+        }
+    }
+
+    if (args.quiet && changes_found) || baseline_differs {
+        std::process::exit(1);
     }
 
     Ok(())
 }
 
-fn print_dependency_graph(apps: HashMap<String, App>) {
-    println!("Dependency graph:\n");
-    let mut sorted_apps: Vec<_> = apps.keys().collect();
-    sorted_apps.sort();
+/// Apps chosen with --app or --tag, or None to output every app
+fn select_apps(engine: &YethEngine, args: &Cli, apps: &HashMap<String, App>) -> Result<Option<Vec<String>>> {
+    Ok(match &args.app {
+        Some(pattern) => Some(engine.select_apps(pattern, apps)?),
+        None if !args.tag.is_empty() => Some(engine.select_tagged_apps(&args.tag, apps)?),
+        None => None,
+    })
+}
 
-    for app_name in sorted_apps {
-        let app = apps.get(app_name).unwrap();
-        println!("{}", app_name);
-        if app.dependencies.is_empty() {
-            println!("  └─ (no dependencies)");
-        } else {
-            for (i, dep) in app.dependencies.iter().enumerate() {
-                let prefix = if i == app.dependencies.len() - 1 {
-                    "└─"
-                } else {
-                    "├─"
-                };
+/// Write `content` to a temporary file next to `path` and rename it into place,
+/// so readers never see a partial file
+fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // Temporary files are private; give the result the permissions a plain write would
+    let permissions = match std::fs::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(_) => default_file_permissions(),
+    };
+    let mut builder = tempfile::Builder::new();
+    if let Some(permissions) = permissions {
+        builder.permissions(permissions);
+    }
+    let mut file = builder.tempfile_in(dir)?;
+    file.write_all(content)?;
+    file.persist(path)?;
+    Ok(())
+}
 
-                match dep {
-                    Dependency::App(dep_name) => {
-                        println!("  {} {} (app)", prefix, dep_name);
-                    }
-                    Dependency::Path(path) => {
-                        let path_str = path.display();
-                        let kind = if path.is_file() { "file" } else { "dir" };
-                        println!("  {} {} ({})", prefix, path_str, kind);
-                    }
-                }
-            }
+/// One application in `--format json` output
+#[derive(Serialize)]
+struct JsonAppEntry<'a> {
+    hash: String,
+    /// Application directory relative to the root
+    dir: PathBuf,
+    /// `[app.meta]` from yeth.toml, sorted by key
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    meta: BTreeMap<&'a String, &'a toml::Value>,
+}
+
+/// One application in `--format json --sort-by topo` output
+#[derive(Serialize)]
+struct JsonOrderedEntry<'a> {
+    app: &'a String,
+    hash: String,
+    dir: PathBuf,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    meta: BTreeMap<&'a String, &'a toml::Value>,
+}
+
+/// One application in `--list-apps --format json` output
+#[derive(Serialize)]
+struct ListedApp<'a> {
+    dir: PathBuf,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+}
+
+/// Names of the hashed applications in the requested output order. The topological
+/// order is taken from `ordered_apps`, and stored there when it wasn't sorted yet.
+fn sort_apps<'a>(
+    engine: &YethEngine,
+    apps: &'a HashMap<String, App>,
+    hashes: &HashMap<String, String>,
+    sort_by: SortBy,
+    ordered_apps: &mut Option<Vec<String>>,
+) -> Result<Vec<&'a String>> {
+    let mut sorted_apps: Vec<&String> = apps.keys().filter(|app_name| hashes.contains_key(*app_name)).collect();
+    match sort_by {
+        SortBy::Name => sorted_apps.sort(),
+        SortBy::Path => sorted_apps.sort_by(|a, b| apps[*a].dir.cmp(&apps[*b].dir).then(a.cmp(b))),
+        SortBy::Topo => {
+            let order = match ordered_apps {
+                Some(order) => order,
+                None => ordered_apps.insert(engine.topological_sort(apps)?),
+            };
+            let position: HashMap<&String, usize> = order.iter().enumerate().map(|(i, app_name)| (app_name, i)).collect();
+            sorted_apps.sort_by_key(|app_name| position[*app_name]);
+        }
+    }
+    Ok(sorted_apps)
+}
+
+/// How many of the slowest applications --verbose lists
+const SLOWEST_APPS_SHOWN: usize = 5;
+
+fn print_phase_timings(stats: &RunStats) {
+    eprintln!(
+        "  Discovery: {:.2?} ({} yeth.toml files)",
+        stats.phase_duration(Phase::Discovery),
+        stats.config_files_parsed()
+    );
+    eprintln!("  Sorting: {:.2?}", stats.phase_duration(Phase::Sort));
+    eprintln!(
+        "  Hashing: {:.2?} ({} files)",
+        stats.phase_duration(Phase::Hashing),
+        stats.files_hashed()
+    );
+    if stats.files_skipped() > 0 {
+        eprintln!("  Skipped unreadable files: {}", stats.files_skipped());
+    }
+
+    let slowest = stats.slowest_apps(SLOWEST_APPS_SHOWN);
+    if !slowest.is_empty() {
+        eprintln!("Slowest applications:");
+        for timing in slowest {
+            eprintln!(
+                "  {} {:.2?} ({} files, {} bytes)",
+                timing.app, timing.duration, timing.files, timing.bytes
+            );
+        }
+    }
+}
+
+fn build_config(args: &Cli) -> Result<Config> {
+    let mut builder = Config::builder();
+    if let Some(root) = &args.root {
+        builder = builder.root(root.clone());
+    }
+    if let Some(jobs) = args.jobs {
+        builder = builder.jobs(jobs);
+    }
+    let config = builder
+        .search_upward(!args.no_root_search)
+        .follow_symlinks(args.follow_symlinks)
+        .hash_symlink_targets(args.hash_symlink_targets)
+        .skip_unreadable(args.skip_unreadable)
+        .keep_going(args.keep_going)
+        .lenient(args.lenient)
+        .max_depth(args.max_depth)
+        .tracked_only(args.tracked_only)
+        .expand_env(args.expand_env)
+        .git_rev(args.git_rev.clone())
+        .promote_path_deps(args.promote_path_deps)
+        .quiet(args.quiet)
+        .read_buffer_size(args.read_buffer_size)
+        .use_mmap(args.mmap)
+        .hash_app_name(args.hash_app_name)
+        .normalize_line_endings(args.normalize_eol)
+        .absolute_paths(args.absolute_paths)
+        .salt(args.salt.clone())
+        .strict(args.strict)
+        .deny_external_paths(args.deny_external_paths)
+        .build()?;
+
+    if args.verbose {
+        eprintln!("Root: {} ({})", config.root.display(), config.root_source);
+    }
+    Ok(config)
+}
+
+/// Write `apps=<json array>` to $GITHUB_OUTPUT, or to `out` when it isn't set
+fn write_github_output(out: &mut impl Write, apps: &[&String]) -> Result<()> {
+    let line = format!("apps={}", serde_json::to_string(apps)?);
+    match std::env::var_os("GITHUB_OUTPUT") {
+        Some(path) => {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            writeln!(file, "{}", line)?;
+        }
+        None => writeln!(out, "{}", line)?,
+    }
+    Ok(())
+}
+
+/// Print one output record, terminated by a newline or by NUL with --print0
+fn print_record(record: &str, print0: bool) {
+    if print0 {
+        print!("{}\0", record);
+    } else {
+        println!("{}", record);
+    }
+}
+
+#[cfg(unix)]
+fn default_file_permissions() -> Option<std::fs::Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(std::fs::Permissions::from_mode(0o644))
+}
+
+#[cfg(not(unix))]
+fn default_file_permissions() -> Option<std::fs::Permissions> {
+    None
+}
+
+/// Write one output record to `out`, terminated like `print_record`
+fn write_record(out: &mut impl Write, record: &str, print0: bool) -> std::io::Result<()> {
+    if print0 {
+        write!(out, "{}\0", record)
+    } else {
+        writeln!(out, "{}", record)
+    }
+}
+
+fn print_dependency_graph(trees: Vec<Vec<String>>, print0: bool, quiet: bool) {
+    if !print0 && !quiet {
+        println!("Dependency graph:\n");
+    }
+    for lines in trees {
+        for line in lines {
+            print_record(&line, print0);
+        }
+        if !print0 {
+            println!();
         }
-        println!();
     }
 }
 
@@ -134,10 +799,16 @@ fn run_benchmark(mut args: Cli, iterations: usize) -> Result<()> {
     let original_verbose = args.verbose;
     args.verbose = false;
     
-    println!("Running benchmark with {} iterations...", iterations);
+    if !args.quiet {
+        eprintln!("Running benchmark with {} iterations...", iterations);
+    }
     
     // Create progress bar
-    let pb = ProgressBar::new(iterations as u64);
+    let pb = if args.quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(iterations as u64)
+    };
     pb.set_style(
         ProgressStyle::default_bar()
             .template("[{bar:40}] {pos}/{len} ({percent}%)")
@@ -146,37 +817,36 @@ fn run_benchmark(mut args: Cli, iterations: usize) -> Result<()> {
     );
     
     let mut total_times = Vec::with_capacity(iterations);
-    let mut apps_count = 0;
+
+    // Discover once, each iteration measures hashing only
+    let config = build_config(&args)?;
+    let engine = YethEngine::new(config);
+    let mut apps = engine.discover_apps()?;
+    engine.exclude_apps(&args.exclude_app, &mut apps)?;
+
+    if apps.is_empty() {
+        return Err(YethError::NoApplicationsFound.into());
+    }
+    let apps_count = apps.len();
+    let selected_apps = select_apps(&engine, &args, &apps)?;
     
     for i in 1..=iterations {
         let start_time = Instant::now();
         
         // Run the processing
-        let config = Config::builder().root(args.root.clone()).build()?;
-        let engine = YethEngine::new(config);
-        let apps = engine.discover_apps()?;
-        
-        if apps.is_empty() {
-            return Err(YethError::NoApplicationsFound.into());
-        }
-        
-        // Store apps count from first iteration
-        if i == 1 {
-            apps_count = apps.len();
-        }
-        
-        let ordered_apps = engine.topological_sort(&apps)?;
-        let _hashes = if let Some(app_name) = &args.app {
-            engine.calculate_hashes_for_app(app_name, &apps)?
+        let _hashes = if let Some(selected) = &selected_apps {
+            engine.calculate_hashes_for_apps(selected, &apps)?
+        } else if let Some(app_name) = &args.impact {
+            engine.calculate_impact_hashes(app_name, &apps)?
         } else {
-            engine.calculate_hashes(ordered_apps, &apps)?
+            engine.run_with_apps(&apps)?
         };
         
         let elapsed = start_time.elapsed();
         total_times.push(elapsed);
         
         if original_verbose {
-            println!("Iteration {}: {:.2?}", i, elapsed);
+            pb.println(format!("Iteration {}: {:.2?}", i, elapsed));
         }
         
         pb.inc(1);
@@ -193,7 +863,7 @@ fn run_benchmark(mut args: Cli, iterations: usize) -> Result<()> {
     // Calculate median
     let mut sorted_times = total_times.clone();
     sorted_times.sort();
-    let median_time = if iterations % 2 == 0 {
+    let median_time = if iterations.is_multiple_of(2) {
         // Even number of iterations - average of two middle values
         let mid1 = sorted_times[iterations / 2 - 1];
         let mid2 = sorted_times[iterations / 2];
@@ -212,7 +882,6 @@ fn run_benchmark(mut args: Cli, iterations: usize) -> Result<()> {
         .sum::<f64>() / iterations as f64;
     let std_dev = variance.sqrt();
     
-    println!();
     println!("Benchmark results:");
     println!("  Iterations: {}", iterations);
     println!("  Applications processed: {}", apps_count);
@@ -0,0 +1,112 @@
+/// Output line format given with `--template`, such as `{name}={hash}`.
+/// `{{` and `}}` are literal braces, and `\t`, `\n` and `\\` are escapes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Name,
+    Hash,
+    Dir,
+}
+
+impl Template {
+    /// Parse a template, rejecting unknown placeholders and unbalanced braces
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(format!("unclosed placeholder '{{{}'", placeholder)),
+                        }
+                    }
+                    let part = match placeholder.as_str() {
+                        "name" => Part::Name,
+                        "hash" => Part::Hash,
+                        "dir" => Part::Dir,
+                        _ => {
+                            return Err(format!(
+                                "unknown placeholder '{{{}}}', expected {{name}}, {{hash}} or {{dir}}",
+                                placeholder
+                            ));
+                        }
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(part);
+                }
+                '}' => return Err("unmatched '}', write '}}' for a literal brace".to_string()),
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(other) => {
+                        text.push('\\');
+                        text.push(other);
+                    }
+                    None => text.push('\\'),
+                },
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Fill in the placeholders for one application
+    pub fn render(&self, name: &str, hash: &str, dir: &str) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.as_str(),
+                Part::Name => name,
+                Part::Hash => hash,
+                Part::Dir => dir,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let render = |template: &str| {
+            Template::parse(template)
+                .unwrap()
+                .render("backend", "3177ac1a", "services/backend")
+        };
+
+        assert_eq!(render("{name}={hash}"), "backend=3177ac1a");
+        assert_eq!(render("{dir}\\t{hash}"), "services/backend\t3177ac1a");
+        assert_eq!(render("{{{name}}} {hash}"), "{backend} 3177ac1a");
+        assert_eq!(render("static"), "static");
+
+        assert!(Template::parse("{name}={sha}").unwrap_err().contains("unknown placeholder '{sha}'"));
+        assert!(Template::parse("{name").is_err());
+        assert!(Template::parse("name}").is_err());
+    }
+}
@@ -0,0 +1,754 @@
+use assert_cmd::Command;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn create_app(root: &Path, name: &str, config: &str) {
+    let dir = root.join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("yeth.toml"), config).unwrap();
+    fs::write(dir.join("main.rs"), name).unwrap();
+}
+
+fn yeth(root: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("yeth").unwrap();
+    cmd.arg("--root").arg(root);
+    cmd
+}
+
+#[test]
+fn test_diagnostics_go_to_stderr() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "backend", "[app]\n");
+
+    let output = yeth(temp_dir.path())
+        .args(["-a", "backend", "-H", "--verbose"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Only the hash is on stdout, statistics are on stderr
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    assert_eq!(stdout.trim().len(), 64);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Execution time"));
+}
+
+#[test]
+fn test_quiet_prints_only_hashes() {
+    let temp_dir = TempDir::new().unwrap();
+    // Unknown key produces a warning in lenient mode
+    create_app(temp_dir.path(), "backend", "[app]\nunknown = 1\n");
+
+    let output = yeth(temp_dir.path())
+        .args(["--lenient", "--quiet"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.trim_end().ends_with(" backend"));
+}
+
+#[test]
+fn test_quiet_hash_only_is_a_single_line() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "backend", "[app]\nunknown = 1\n");
+    create_app(temp_dir.path(), "frontend", "[app]\n");
+
+    let output = yeth(temp_dir.path())
+        .args(["--lenient", "--quiet", "--app", "backend", "--hash-only"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    assert_eq!(stdout.trim_end().len(), 64);
+
+    // Quiet and verbose contradict each other
+    let output = yeth(temp_dir.path()).args(["--quiet", "--verbose"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_quiet_changed_uses_exit_code() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "backend", "[app]\n");
+
+    // No yeth.version yet, so the app counts as changed
+    yeth(temp_dir.path())
+        .args(["--changed", "--quiet"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+
+    yeth(temp_dir.path()).arg("--write-versions").assert().success();
+
+    yeth(temp_dir.path())
+        .args(["--changed", "--quiet"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn test_sort_by_topo_puts_dependencies_first() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "a-web", "[app]\ndependencies = [\"z-lib\"]\n");
+    create_app(temp_dir.path(), "z-lib", "[app]\n");
+
+    let app_order = |sort_by: &str| -> Vec<String> {
+        let output = yeth(temp_dir.path()).args(["--sort-by", sort_by]).output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(' ').nth(1).unwrap().to_string())
+            .collect()
+    };
+
+    assert_eq!(app_order("name"), ["a-web", "z-lib"]);
+    assert_eq!(app_order("topo"), ["z-lib", "a-web"]);
+}
+
+#[test]
+fn test_write_lock_then_frozen() {
+    let temp_dir = TempDir::new().unwrap();
+    // The root itself is an app, so the lockfile sits inside it
+    fs::write(temp_dir.path().join("yeth.toml"), "[app]\nname = \"monorepo\"\n").unwrap();
+    create_app(temp_dir.path(), "backend", "[app]\n");
+
+    yeth(temp_dir.path()).arg("--frozen").assert().code(1);
+    yeth(temp_dir.path()).arg("--write-lock").assert().success();
+    yeth(temp_dir.path()).arg("--frozen").assert().success();
+
+    fs::write(temp_dir.path().join("backend").join("main.rs"), "changed").unwrap();
+    let output = yeth(temp_dir.path()).arg("--frozen").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("changed backend:"));
+}
+
+#[test]
+fn test_app_with_cycle_fails_like_full_run() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "api", "[app]\ndependencies = [\"worker\"]\n");
+    create_app(temp_dir.path(), "worker", "[app]\ndependencies = [\"api\"]\n");
+    create_app(temp_dir.path(), "web", "[app]\n");
+
+    let full = yeth(temp_dir.path()).output().unwrap();
+    let single = yeth(temp_dir.path()).args(["--app", "api"]).output().unwrap();
+    assert!(!full.status.success());
+    assert!(!single.status.success());
+
+    let expected = "Circular dependency detected: api -> worker -> api";
+    assert!(String::from_utf8(full.stderr).unwrap().contains(expected));
+    assert!(String::from_utf8(single.stderr).unwrap().contains(expected));
+
+    // An app outside the cycle still hashes on its own
+    yeth(temp_dir.path()).args(["--app", "web"]).assert().success();
+}
+
+#[test]
+fn test_list_apps() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "web", "[app]\n");
+    create_app(&temp_dir.path().join("services"), "api", "[app]\n");
+
+    let output = yeth(temp_dir.path()).arg("--list-apps").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "api services/api\nweb web\n");
+
+    let output = yeth(temp_dir.path()).args(["--list-apps", "--format", "json"]).output().unwrap();
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(listing["api"]["dir"], "services/api");
+}
+
+#[test]
+fn test_output_file_is_replaced_only_on_success() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("repo");
+    create_app(&root, "backend", "[app]\n");
+    let output_path = temp_dir.path().join("versions.txt");
+
+    let output = yeth(&root).arg("--output").arg(&output_path).output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let written = fs::read_to_string(&output_path).unwrap();
+    assert!(written.trim_end().ends_with(" backend"));
+
+    // A failing run leaves the previous results in place
+    create_app(&root, "broken", "[app]\ndependencies = [\"../missing\"]\n");
+    let output = yeth(&root).arg("--output").arg(&output_path).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), written);
+
+    // "-" is stdout
+    fs::remove_dir_all(root.join("broken")).unwrap();
+    let output = yeth(&root).args(["--output", "-", "--format", "json"]).output().unwrap();
+    let hashes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(hashes["backend"]["hash"].is_string());
+}
+
+/// Many apps and files under a file descriptor limit far below their count
+#[cfg(unix)]
+#[test]
+fn test_jobs_bounds_open_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    for i in 0..200 {
+        create_app(root, &format!("app{}", i), "[app]\n");
+    }
+    let big = root.join("app0");
+    for i in 0..3000 {
+        fs::write(big.join(format!("{}.txt", i)), i.to_string()).unwrap();
+    }
+
+    let yeth = assert_cmd::cargo::cargo_bin("yeth");
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(r#"ulimit -n 64 && exec "$0" --root "$1" --jobs 2"#)
+        .arg(&yeth)
+        .arg(root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 200);
+}
+
+#[test]
+fn test_order_topo() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "web", "[app]\ndependencies = [\"api\"]\n");
+    create_app(temp_dir.path(), "api", "[app]\ndependencies = [\"shared\"]\n");
+    create_app(temp_dir.path(), "shared", "[app]\n");
+    create_app(temp_dir.path(), "admin", "[app]\n");
+
+    let names = |args: &[&str]| -> Vec<String> {
+        let output = yeth(temp_dir.path()).args(args).output().unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout.lines().map(|line| line.split(' ').nth(1).unwrap().to_string()).collect()
+    };
+    assert_eq!(names(&["--order", "topo"]), ["admin", "shared", "api", "web"]);
+    assert_eq!(names(&["--order", "topo", "--app", "web"]), ["shared", "api", "web"]);
+    assert_eq!(names(&["--app", "web"]), ["web"]);
+
+    let output = yeth(temp_dir.path())
+        .args(["--order", "topo", "--app", "api", "--format", "json"])
+        .output()
+        .unwrap();
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let apps: Vec<&str> = entries.as_array().unwrap().iter().map(|e| e["app"].as_str().unwrap()).collect();
+    assert_eq!(apps, ["shared", "api"]);
+}
+
+#[test]
+fn test_warn_collisions() {
+    let temp_dir = TempDir::new().unwrap();
+    for name in ["empty1", "empty2"] {
+        let dir = temp_dir.path().join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("yeth.toml"), "[app]\n").unwrap();
+    }
+
+    let output = yeth(temp_dir.path()).arg("--warn-collisions").output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Warning: applications empty1, empty2 share the hash"));
+
+    let output = yeth(temp_dir.path()).output().unwrap();
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_app_glob() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "worker-a", "[app]\ndependencies = [\"shared\"]\n");
+    create_app(temp_dir.path(), "worker-b", "[app]\n");
+    create_app(temp_dir.path(), "shared", "[app]\n");
+
+    let output = yeth(temp_dir.path()).args(["--app", "worker-*"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<&str> = stdout.lines().map(|line| line.split(' ').nth(1).unwrap()).collect();
+    assert_eq!(names, ["worker-a", "worker-b"]);
+
+    let output = yeth(temp_dir.path()).args(["--app", "api-*"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("No application matches 'api-*'"));
+
+    let output = yeth(temp_dir.path()).args(["--app", "worker-*", "--hash-only"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--hash-only needs a single application"));
+
+    let output = yeth(temp_dir.path()).args(["--app", "worker-?", "--hash-only"]).output().unwrap();
+    assert!(!output.status.success());
+    let output = yeth(temp_dir.path()).args(["--app", "worker-[a]", "--hash-only"]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim().len(), 64);
+}
+
+#[test]
+fn test_paths_relative_to_root() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "api", "[app]\ndependencies = [\"./schema\"]\n");
+
+    let output = yeth(temp_dir.path()).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Path dependency 'api/schema' for 'api' not found"), "{}", stderr);
+
+    fs::create_dir_all(temp_dir.path().join("api/schema")).unwrap();
+    let output = yeth(temp_dir.path()).arg("--show-graph").output().unwrap();
+    assert!(String::from_utf8(output.stdout).unwrap().contains("└─ api/schema (dir)"));
+
+    let output = yeth(temp_dir.path()).args(["--show-graph", "--absolute-paths"]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("└─ {}", temp_dir.path().display())), "{}", stdout);
+}
+
+#[test]
+fn test_tag_filter() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "web", "[app]\ntags = [\"frontend\"]\n");
+    create_app(temp_dir.path(), "api", "[app]\ndependencies = [\"shared\"]\ntags = [\"backend\", \"tier1\"]\n");
+    create_app(temp_dir.path(), "billing", "[app]\ntags = [\"backend\"]\n");
+    create_app(temp_dir.path(), "cron", "[app]\ntags = [\"infra\"]\n");
+    create_app(temp_dir.path(), "shared", "[app]\n");
+
+    let names = |args: &[&str]| -> Vec<String> {
+        let output = yeth(temp_dir.path()).args(args).output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout.lines().map(|line| line.split(' ').nth(1).unwrap().to_string()).collect()
+    };
+    // Only tagged apps are printed, though api's dependency is hashed for it
+    assert_eq!(names(&["--tag", "backend"]), ["api", "billing"]);
+    assert_eq!(names(&["--tag", "infra", "--tag", "frontend"]), ["cron", "web"]);
+
+    let all = yeth(temp_dir.path()).output().unwrap();
+    let api_line = String::from_utf8(all.stdout).unwrap().lines().find(|l| l.ends_with(" api")).unwrap().to_string();
+    let tagged = yeth(temp_dir.path()).args(["--tag", "tier1"]).output().unwrap();
+    assert_eq!(String::from_utf8(tagged.stdout).unwrap().trim(), api_line);
+
+    let output = yeth(temp_dir.path()).args(["--tag", "mobile"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("No application is tagged 'mobile'"));
+
+    let output = yeth(temp_dir.path()).args(["--list-apps", "--tag", "backend"]).output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "api api backend,tier1\nbilling billing backend\n");
+}
+
+#[test]
+fn test_path_dependency_inside_app() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "proto", "[app]\n");
+    create_app(temp_dir.path(), "api", "[app]\ndependencies = [\"../proto/schema\"]\n");
+    fs::create_dir_all(temp_dir.path().join("proto/schema")).unwrap();
+
+    // Inside another app: a warning, or an error with --strict
+    let output = yeth(temp_dir.path()).output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Warning: Path dependency 'proto/schema' for 'api' is inside application 'proto', depend on 'proto' instead"),
+        "{}",
+        stderr
+    );
+    let output = yeth(temp_dir.path()).arg("--strict").output().unwrap();
+    assert!(!output.status.success());
+
+    // Inside the app's own directory: always an error
+    create_app(temp_dir.path(), "web", "[app]\ndependencies = [\"./config\"]\n");
+    fs::create_dir_all(temp_dir.path().join("web/config")).unwrap();
+    let output = yeth(temp_dir.path()).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Path dependency 'web/config' for 'web' overlaps the application directory"), "{}", stderr);
+}
+
+#[test]
+fn test_show_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(&temp_dir.path().join("services"), "api", "[app]\n");
+    create_app(temp_dir.path(), "web", "[app]\n");
+
+    let output = yeth(temp_dir.path()).arg("--show-dir").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let records: Vec<Vec<&str>> = stdout.lines().map(|line| line.split(' ').skip(1).collect()).collect();
+    assert_eq!(records, [["api", "services/api"], ["web", "web"]]);
+
+    let output = yeth(temp_dir.path()).args(["--format", "json"]).output().unwrap();
+    let hashes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(hashes["api"]["dir"], "services/api");
+}
+
+#[test]
+fn test_template() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(&temp_dir.path().join("services"), "backend", "[app]\n");
+    create_app(temp_dir.path(), "web", "[app]\n");
+
+    let output = yeth(temp_dir.path()).args(["--app", "backend"]).output().unwrap();
+    let hash = String::from_utf8(output.stdout).unwrap().split(' ').next().unwrap().to_string();
+
+    let output = yeth(temp_dir.path()).args(["--app", "backend", "--template", "{name}={hash}"]).output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("backend={}\n", hash));
+
+    let output = yeth(temp_dir.path()).args(["--template", "{dir}\\t{name}"]).output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "services/backend\tbackend\nweb\tweb\n");
+
+    let output = yeth(temp_dir.path()).args(["--template", "{name} {sha}"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("unknown placeholder '{sha}'"));
+}
+
+#[test]
+fn test_verify() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "common", "[app]\n");
+    create_app(temp_dir.path(), "backend", "[app]\ndependencies = [\"common\"]\n");
+
+    let output = yeth(temp_dir.path()).arg("--verify").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("First run: "));
+    assert!(stdout.contains("Second run: "));
+    assert!(stdout.ends_with("All 2 application hashes match\n"));
+}
+
+#[test]
+fn test_config_at_root_given_as_dot() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("monorepo");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("yeth.toml"), "[app]\n").unwrap();
+
+    let list = || {
+        Command::cargo_bin("yeth")
+            .unwrap()
+            .current_dir(&root)
+            .args(["--root", ".", "--list-apps"])
+            .output()
+            .unwrap()
+    };
+    // The directory name depends on the checkout, so a name has to be set
+    let output = list();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("set `name` under [app]"));
+
+    fs::write(root.join("yeth.toml"), "[app]\nname = \"monorepo\"\n").unwrap();
+    let output = list();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "monorepo .\n");
+}
+
+#[test]
+fn test_exclude_app() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "common", "[app]\n");
+    create_app(temp_dir.path(), "web", "[app]\ndependencies = [\"common\"]\n");
+    create_app(temp_dir.path(), "flaky", "[app]\n");
+
+    let output = yeth(temp_dir.path())
+        .args(["--exclude-app", "fla*", "--sort-by", "topo", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let order: Vec<&str> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["app"].as_str().unwrap())
+        .collect();
+    assert_eq!(order, vec!["common", "web"]);
+
+    // An app another one depends on can't be left out
+    let output = yeth(temp_dir.path()).args(["--exclude-app", "common"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("'web' depends on it"));
+}
+
+#[test]
+fn test_exclude_nested_app() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "platform", "[app]\n");
+    create_app(temp_dir.path(), "platform/workers/emailer", "[app]\n");
+
+    let platform_line = |args: &[&str]| {
+        let output = yeth(temp_dir.path()).args(args).output().unwrap();
+        assert!(output.status.success(), "yeth {:?} failed", args);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout.lines().find(|line| line.ends_with(" platform")).unwrap().to_string()
+    };
+
+    // The excluded app's files don't fall back into its parent's hash
+    assert_eq!(platform_line(&["--exclude-app", "emailer"]), platform_line(&[]));
+}
+
+#[test]
+fn test_subcommands_match_flags() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "common", "[app]\n");
+    create_app(temp_dir.path(), "api", "[app]\ndependencies = [\"common\"]\n");
+
+    let stdout = |args: &[&str]| {
+        let output = yeth(temp_dir.path()).args(args).output().unwrap();
+        assert!(output.status.success(), "yeth {:?} failed", args);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // Bare yeth is `yeth hash`, and options work on either side of the subcommand
+    assert_eq!(stdout(&["hash"]), stdout(&[]));
+    assert_eq!(stdout(&["hash", "--app", "api", "-H"]), stdout(&["--app", "api", "--hash-only"]));
+    assert_eq!(stdout(&["--short-hash", "hash"]), stdout(&["hash", "--short-hash"]));
+
+    assert_eq!(stdout(&["graph"]), stdout(&["--show-graph"]));
+    assert_eq!(stdout(&["graph", "--graph-app", "api"]), stdout(&["-g", "--graph-app", "api"]));
+    assert_eq!(stdout(&["list", "--format", "json"]), stdout(&["--list-apps", "--format", "json"]));
+    assert_eq!(
+        stdout(&["graph", "--format", "dot"]),
+        "digraph yeth {\n  \"api\";\n  \"api\" -> \"common\";\n  \"common\";\n}\n"
+    );
+
+    stdout(&["--write-lock"]);
+    assert_eq!(stdout(&["check"]), stdout(&["--frozen"]));
+    assert!(stdout(&["bench", "2"]).contains("Iterations: 2"));
+    assert!(stdout(&["--bench", "2"]).contains("Iterations: 2"));
+
+    // Combinations that only make sense for hashing are rejected
+    for args in [&["list", "--write-versions"][..], &["check", "--app", "api"], &["--graph-app", "api"], &["--format", "dot"]] {
+        let output = yeth(temp_dir.path()).args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(2), "yeth {:?} should fail", args);
+    }
+}
+
+#[test]
+fn test_init() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_app(root, "common", "[app]\n");
+
+    yeth(root)
+        .args(["init", "api", "--dep", "common"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("Created api/yeth.toml\n");
+    let config = fs::read_to_string(root.join("api").join("yeth.toml")).unwrap();
+    assert!(config.contains("dependencies = [\"common\"]\n"));
+    yeth(root).args(["--app", "api"]).assert().success();
+
+    // An existing config is only replaced with --force
+    let output = yeth(root).args(["init", "api"]).current_dir(root).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("pass --force"));
+    yeth(root).args(["init", "api", "--force"]).current_dir(root).assert().success();
+    let config = fs::read_to_string(root.join("api").join("yeth.toml")).unwrap();
+    assert!(config.contains("dependencies = []\n"));
+
+    // Unknown app dependencies are rejected before anything is written
+    let output = yeth(root).args(["init", "web", "--dep", "comon"]).current_dir(root).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("common"));
+    assert!(!root.join("web").exists());
+
+    // Nested inside an existing app
+    let output = yeth(root).args(["init", "common/tools"]).current_dir(root).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("inside application 'common'"));
+}
+
+#[test]
+fn test_warn_unused_excludes() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "web", "[app]\nexclude = [\"node_module\", \"*.log\"]\n");
+    fs::write(temp_dir.path().join("web").join("debug.log"), "log").unwrap();
+
+    let output = yeth(temp_dir.path()).arg("--warn-unused-excludes").output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Warning: exclude pattern 'node_module' of application 'web' matched nothing\n"));
+    assert!(!stderr.contains("*.log"));
+
+    // Only on request
+    let output = yeth(temp_dir.path()).output().unwrap();
+    assert!(String::from_utf8(output.stderr).unwrap().is_empty());
+}
+
+#[test]
+fn test_color() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "common", "[app]\n");
+    create_app(temp_dir.path(), "api", "[app]\ndependencies = [\"common\"]\n");
+
+    let stdout = |args: &[&str]| {
+        let output = yeth(temp_dir.path()).args(args).output().unwrap();
+        assert!(output.status.success(), "yeth {:?} failed", args);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // Not a terminal, so plain unless forced
+    let plain = stdout(&[]);
+    assert!(!plain.contains('\x1b'));
+    assert_eq!(stdout(&["--color", "never"]), plain);
+    let colored = stdout(&["--color", "always"]);
+    assert!(colored.contains("\x1b[1mapi\x1b[0m\n"));
+    assert!(stdout(&["graph", "--color", "always"]).contains("\x1b[1mcommon\x1b[0m (app)"));
+
+    // Machine formats never get escape codes
+    for args in [
+        &["--color", "always", "--format", "json"][..],
+        &["--color", "always", "--template", "{name}={hash}"],
+        &["--color", "always", "--print0"],
+        &["graph", "--color", "always", "--format", "json"],
+    ] {
+        assert!(!stdout(args).contains('\x1b'), "yeth {:?} is colored", args);
+    }
+
+    let output = yeth(temp_dir.path()).args(["--color", "always", "--app", "missing"]).output().unwrap();
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("\x1b[1m\x1b[31mError:\x1b[0m "));
+}
+
+#[test]
+fn test_image_tag() {
+    let temp_dir = TempDir::new().unwrap();
+    create_app(temp_dir.path(), "Backend", "[app]\n");
+    create_app(temp_dir.path(), "web", "[app]\n");
+
+    let output = yeth(temp_dir.path()).args(["--app", "Backend", "-H", "-s", "-l", "7"]).output().unwrap();
+    let hash = String::from_utf8(output.stdout).unwrap().trim().to_string();
+
+    yeth(temp_dir.path())
+        .args(["--image-tag", "--registry", "registry.example.com/team", "-l", "7", "--app", "Backend"])
+        .assert()
+        .success()
+        .stdout(format!("registry.example.com/team/backend:yeth-{}\n", hash));
+
+    let output = yeth(temp_dir.path())
+        .args(["--image-tag", "--tag-prefix", "v", "--format", "json"])
+        .output()
+        .unwrap();
+    let refs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(refs["web"].as_str().unwrap().starts_with("web:v"));
+    assert_eq!(refs.as_object().unwrap().len(), 2);
+
+    // The tag options only make sense with --image-tag
+    let output = yeth(temp_dir.path()).args(["--registry", "registry.example.com"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+
+    create_app(temp_dir.path(), "+++", "[app]\n");
+    let output = yeth(temp_dir.path()).arg("--image-tag").output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Application '+++' has no characters allowed in an image name"));
+}
+
+#[test]
+fn test_diff() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_app(root, "common", "[app]\n");
+    create_app(root, "api", "[app]\ndependencies = [\"common\"]\n");
+    create_app(root, "web", "[app]\n");
+    create_app(root, "old", "[app]\n");
+
+    let saved = root.join("before.json");
+    let output = yeth(root).args(["--format", "json", "-s"]).output().unwrap();
+    fs::write(&saved, output.stdout).unwrap();
+    let saved = saved.to_str().unwrap();
+
+    yeth(root).args(["--diff", saved]).assert().success().stdout("");
+
+    fs::write(root.join("common").join("main.rs"), "changed").unwrap();
+    fs::remove_dir_all(root.join("old")).unwrap();
+    create_app(root, "new", "[app]\n");
+    yeth(root)
+        .args(["--diff", saved])
+        .assert()
+        .success()
+        .stdout("added new\nremoved old\nchanged api\nchanged common\n");
+
+    let output = yeth(root).args(["--diff", saved, "--format", "json"]).output().unwrap();
+    let diff: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(diff["changed"], serde_json::json!(["api", "common"]));
+
+    // Only the selected application is compared, and --quiet answers through the exit code
+    yeth(root).args(["--diff", saved, "--app", "web"]).assert().success().stdout("");
+    yeth(root).args(["--diff", saved, "--quiet"]).assert().code(1).stdout("");
+}
+
+#[test]
+fn test_baseline() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_app(root, "common", "[app]\n");
+    create_app(root, "api", "[app]\ndependencies = [\"common\"]\n");
+    create_app(root, "old", "[app]\n");
+
+    // The artifact of an earlier run, with short hashes
+    let artifact = root.join("previous.json");
+    let output = yeth(root).args(["--format", "json", "-s"]).output().unwrap();
+    fs::write(&artifact, output.stdout).unwrap();
+    let artifact = artifact.to_str().unwrap();
+    yeth(root)
+        .args(["--baseline", artifact])
+        .assert()
+        .success()
+        .stdout("unchanged api\nunchanged common\nunchanged old\n");
+
+    // Written in the same form for the next run
+    let next = root.join("next.yaml");
+    yeth(root).args(["--baseline-write", next.to_str().unwrap()]).assert().success();
+    yeth(root).args(["--baseline", next.to_str().unwrap(), "-q"]).assert().success().stdout("");
+
+    fs::write(root.join("api").join("main.rs"), "changed").unwrap();
+    fs::remove_dir_all(root.join("old")).unwrap();
+    create_app(root, "new", "[app]\n");
+    yeth(root)
+        .args(["--baseline", artifact])
+        .assert()
+        .code(1)
+        .stdout("changed api\nunchanged common\nnew new\nremoved old\n");
+
+    let output = yeth(root).args(["--baseline", artifact, "--format", "json"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let statuses: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(statuses["old"], "removed");
+}
+
+#[test]
+fn test_output_file_then_input_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_app(root, "common", "[app]\n");
+    create_app(root, "api", "[app]\ndependencies = [\"common\"]\n");
+    create_app(root, "web", "[app]\n");
+
+    let saved = root.join("hashes.toml");
+    let saved_str = saved.to_str().unwrap();
+    yeth(root).args(["--output-file", saved_str]).assert().success();
+    let saved_hashes: toml::Table = toml::from_str(&fs::read_to_string(&saved).unwrap()).unwrap();
+    assert_eq!(saved_hashes.keys().collect::<Vec<_>>(), ["api", "common", "web"]);
+    assert_eq!(saved_hashes["web"].as_str().unwrap().len(), 64);
+
+    yeth(root).args(["--changed", "--input-file", saved_str]).assert().success().stdout("");
+    yeth(root).args(["--diff", saved_str]).assert().success().stdout("");
+
+    fs::write(root.join("common").join("main.rs"), "changed").unwrap();
+    create_app(root, "new", "[app]\n");
+    let output = yeth(root).args(["--changed", "--input-file", saved_str, "-s"]).output().unwrap();
+    let changed: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split(' ').nth(1).unwrap().to_string())
+        .collect();
+    assert_eq!(changed, ["api", "common", "new"]);
+}
@@ -0,0 +1,145 @@
+#![cfg(feature = "async")]
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use yeth::cfg::{App, Config};
+use yeth::error::YethError;
+use yeth::observer::HashObserver;
+use yeth::version_writes::VersionFormat;
+use yeth::YethEngine;
+
+fn create_app(root: &Path, name: &str, config: &str) {
+    let dir = root.join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("yeth.toml"), config).unwrap();
+    fs::write(dir.join("main.rs"), name).unwrap();
+}
+
+fn fixture() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("shared")).unwrap();
+    fs::write(root.join("shared").join("schema.sql"), "create table").unwrap();
+    create_app(root, "common", "[app]\n");
+    create_app(root, "api", "[app]\ndependencies = [\"common\", \"../shared\"]\n");
+    create_app(root, "web", "[app]\ndependencies = [\"api\"]\n");
+    temp_dir
+}
+
+fn engine(root: &Path) -> YethEngine {
+    YethEngine::new(Config::builder().root(root.to_path_buf()).build().unwrap())
+}
+
+#[tokio::test]
+async fn test_async_matches_sync() {
+    let temp_dir = fixture();
+    let engine = engine(temp_dir.path());
+
+    let sync_apps = engine.discover_apps().unwrap();
+    let async_apps = engine.discover_apps_async().await.unwrap();
+    assert_eq!(async_apps, sync_apps);
+
+    let sync_hashes = engine.run().unwrap();
+    assert_eq!(engine.run_async().await.unwrap(), sync_hashes);
+    assert_eq!(engine.run_with_apps_async(&async_apps).await.unwrap(), sync_hashes);
+
+    let order = engine.topological_sort_async(&async_apps).await.unwrap();
+    assert_eq!(order, engine.topological_sort(&sync_apps).unwrap());
+    assert_eq!(engine.calculate_hashes_async(order, &async_apps).await.unwrap(), sync_hashes);
+
+    let selected = vec!["api".to_string()];
+    assert_eq!(
+        engine.calculate_hashes_for_apps_async(&selected, &async_apps).await.unwrap(),
+        engine.calculate_hashes_for_apps(&selected, &sync_apps).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_async_errors_match_sync() {
+    let temp_dir = fixture();
+    create_app(temp_dir.path(), "broken", "[app]\ndependencies = [\"missing\"]\n");
+    let engine = engine(temp_dir.path());
+
+    let sync_error = engine.run().unwrap_err();
+    let async_error = engine.run_async().await.unwrap_err();
+    assert_eq!(async_error.to_string(), sync_error.to_string());
+}
+
+#[tokio::test]
+async fn test_run_streaming_async() {
+    let temp_dir = fixture();
+    let engine = engine(temp_dir.path());
+    let apps = engine.discover_apps_async().await.unwrap();
+
+    let mut receiver = engine.run_streaming_async(&apps).await;
+    let mut streamed = Vec::new();
+    while let Some(result) = receiver.recv().await {
+        streamed.push(result.unwrap());
+    }
+    // Dependencies come first, and every hash matches the sync run
+    let order: Vec<&str> = streamed.iter().map(|(app_name, _)| app_name.as_str()).collect();
+    assert_eq!(order, ["common", "api", "web"]);
+    let sync_hashes = engine.run().unwrap();
+    assert_eq!(streamed.into_iter().collect::<HashMap<_, _>>(), sync_hashes);
+
+    // A failure ends the stream
+    create_app(temp_dir.path(), "broken", "[app]\ndependencies = [\"missing\"]\n");
+    let apps = engine.discover_apps_async().await.unwrap();
+    let mut receiver = engine.run_streaming_async(&apps).await;
+    let error = receiver.recv().await.unwrap().unwrap_err();
+    assert_eq!(error.to_string(), engine.run().unwrap_err().to_string());
+    assert!(receiver.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn test_run_streaming_async_options_while_streaming() {
+    let temp_dir = fixture();
+    let engine = engine(temp_dir.path());
+    let apps = engine.discover_apps_async().await.unwrap();
+
+    // The streaming task may still hold the stats when an option is set
+    let mut receiver = engine.run_streaming_async(&apps).await;
+    let engine = engine.with_app_timings();
+    while let Some(result) = receiver.recv().await {
+        result.unwrap();
+    }
+    engine.run_with_apps(&apps).unwrap();
+    assert_eq!(engine.stats().slowest_apps(10).len(), apps.len());
+}
+
+#[tokio::test]
+async fn test_run_streaming_async_task_panic() {
+    struct Panicking;
+    impl HashObserver for Panicking {
+        fn on_app_start(&self, _app: &App) {
+            panic!("observer failed");
+        }
+    }
+
+    let temp_dir = fixture();
+    let engine = engine(temp_dir.path()).with_observer(Panicking);
+    let apps = engine.discover_apps_async().await.unwrap();
+
+    // A panic in the hashing task ends the stream with an error
+    let mut receiver = engine.run_streaming_async(&apps).await;
+    let error = receiver.recv().await.unwrap().unwrap_err();
+    assert!(matches!(error, YethError::TaskFailed(_)), "{:?}", error);
+    assert!(receiver.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn test_apply_version_writes_async() {
+    let temp_dir = fixture();
+    let engine = engine(temp_dir.path());
+    let apps = engine.discover_apps_async().await.unwrap();
+    let hashes = engine.run_with_apps_async(&apps).await.unwrap();
+
+    let actions = engine.plan_version_writes(&apps, &hashes, VersionFormat::Plain).unwrap();
+    engine.apply_version_writes_async(actions).await.unwrap();
+    for (app_name, hash) in &hashes {
+        let written = fs::read_to_string(apps[app_name].dir.join("yeth.version")).unwrap();
+        assert_eq!(&written, hash);
+    }
+}